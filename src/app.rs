@@ -2,100 +2,583 @@
 //!
 //! This module exposes the main application workflow
 
+#[cfg(feature = "sqlite")]
+use crate::database::Store;
 use crate::{
-    database::{QuoteDatabase, TradeDatabase},
-    module730::{Module730, Stdout as StdoutPaginate},
-    tax::{GainsAndLosses, Taxes},
+    database::{
+        Adjustments, Anonymizer, BitpandaApi, CsvMapping, HypotheticalTrades, ImportSource,
+        IsinSymbols, Profile, QuoteDatabase, TradeDatabase, TradeMerge, TradeQuery, TradeSummary,
+        Venue, WalletDatabase,
+    },
+    module730::{Module730, Paginate, Stdout as StdoutPaginate, YearSummary},
+    report::{PaginateProfile, PaginateTrades, Stdout as StdoutReport},
+    tax::{
+        CarriedLosses, CostBasis, GainsAndLosses, GainsAndLossesCalculator, Inventory,
+        LossHarvesting, SelfTransfers, Taxes,
+    },
+    timezone,
 };
 
-use bitpanda_csv::{AsyncBitpandaTradeParser, Fiat, Trade};
+use bitpanda_csv::{Asset, AsyncBitpandaTradeParser, Fiat, Trade};
 use chrono::prelude::*;
 use chrono::{DateTime, FixedOffset};
 use rust_decimal::Decimal;
 use spinners::{Spinner, Spinners};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use std::rc::Rc;
 use tokio::fs::File;
 use tokio::io::BufReader;
 
+mod batch;
+mod input;
+mod validate;
+
+pub use batch::{Batch, Years};
+pub use validate::Validation;
+
 /// Application container
 pub struct App {
     trades: TradeDatabase,
     since: DateTime<FixedOffset>,
     to: DateTime<FixedOffset>,
+    self_transfers: SelfTransfers,
+    cost_basis: CostBasis,
+    export_dir: Option<PathBuf>,
+    explain: bool,
+    opening_inventory: Option<Inventory>,
+    closing_inventory: Option<PathBuf>,
+    carried_losses: CarriedLosses,
+    harvest: bool,
+    what_if: HypotheticalTrades,
+    year_to_date: bool,
+    /// The taxpayer and the Bitpanda account
+    profile: Profile,
+    /// Quotes already loaded; if not set, they are loaded when running the application
+    quotes: Option<QuoteDatabase>,
+    /// Local store where the quotes and the summary of the year are kept
+    #[cfg(feature = "sqlite")]
+    store: Option<Rc<Store>>,
+}
+
+/// The results of a run which are carried to the next year
+pub struct Outcome {
+    pub summary: YearSummary,
+    /// Lots held at the end of the time range
+    pub closing_inventory: Inventory,
+    /// Losses which can be used in the next years
+    pub carried_losses: CarriedLosses,
 }
 
 impl App {
     /// Setup a new application
-    pub async fn setup(year: i32, csv_file: &Path) -> anyhow::Result<Self> {
-        // open file
-        info!("parsing CSV file {}", csv_file.display());
-        let csv_file = File::open(csv_file).await?;
-        let reader = BufReader::new(csv_file);
-        let trades = AsyncBitpandaTradeParser::parse(reader).await?;
+    pub fn setup(year: i32, trades: Vec<(Venue, Trade)>) -> anyhow::Result<Self> {
         // calc date range according to Italian timezone
-        let since = FixedOffset::east_opt(3600)
-            .unwrap()
-            .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
-            .unwrap();
-        let to = FixedOffset::east_opt(3600)
-            .unwrap()
-            .with_ymd_and_hms(year, 12, 31, 23, 59, 59)
-            .unwrap();
+        let (since, to) = timezone::year_range(year)?;
+        Self::setup_range(trades, since, to)
+    }
+
+    /// Setup a new application for the current year, from the beginning of the year to now
+    pub fn setup_year_to_date(trades: Vec<(Venue, Trade)>) -> anyhow::Result<Self> {
+        let to = timezone::now();
+        let (since, _) = timezone::year_range(to.year())?;
+        let mut app = Self::setup_range(trades, since, to)?;
+        app.year_to_date = true;
+        Ok(app)
+    }
+
+    /// Parse the trades from the Bitpanda CSV files at `csv_files` (files, directories or glob patterns),
+    /// from the exports of the other venues at `imports`, whose securities are mapped to their asset on Bitpanda through
+    /// `isin_symbols`, and, if set, from the Bitpanda `api`.
+    /// Each trade is returned along with the venue where it has been made.
+    /// Trades found in more than one source are taken once.
+    pub async fn parse_trades(
+        csv_files: &[PathBuf],
+        imports: &[ImportSource],
+        isin_symbols: &IsinSymbols,
+        api: Option<&BitpandaApi>,
+    ) -> anyhow::Result<Vec<(Venue, Trade)>> {
+        let csv_files = input::resolve(csv_files)?;
+        let mut merge = TradeMerge::default();
+        for path in csv_files.iter() {
+            // open file
+            info!("parsing CSV file {}", path.display());
+            let csv_file = File::open(path).await?;
+            let reader = BufReader::new(csv_file);
+            merge.add(
+                path,
+                Venue::Bitpanda,
+                AsyncBitpandaTradeParser::parse(reader).await?,
+            );
+        }
+        for source in imports.iter() {
+            let importer = source.importer(isin_symbols)?;
+            info!(
+                "importing {} trades from {}",
+                importer.venue(),
+                source.path.display()
+            );
+            let export = tokio::fs::read(&source.path).await?;
+            merge.add(
+                &source.path,
+                importer.venue(),
+                importer.import(&mut export.as_slice())?,
+            );
+        }
+        if let Some(api) = api {
+            info!("fetching trades from the Bitpanda API");
+            merge.add(
+                Path::new(BitpandaApi::SOURCE),
+                Venue::Bitpanda,
+                api.fetch_trades().await?,
+            );
+        }
+        let (trades, report) = merge.finish();
+        if report.sources.len() > 1 {
+            StdoutReport.paginate_merge_report(&report)?;
+        }
+        Ok(trades)
+    }
+
+    /// Add the new `trades` to the `store`, if set, and take all the trades stored
+    #[cfg(feature = "sqlite")]
+    pub fn store_trades(
+        trades: Vec<(Venue, Trade)>,
+        store: Option<&Store>,
+    ) -> anyhow::Result<Vec<(Venue, Trade)>> {
+        match store {
+            Some(store) => {
+                store.insert_trades(&trades)?;
+                store.trades()
+            }
+            None => Ok(trades),
+        }
+    }
+
+    /// Apply the manual `adjustments` to `trades`
+    pub fn adjust_trades(
+        trades: Vec<(Venue, Trade)>,
+        adjustments: &Adjustments,
+    ) -> anyhow::Result<Vec<(Venue, Trade)>> {
+        if adjustments.is_empty() {
+            return Ok(trades);
+        }
+        let (trades, report) = adjustments.apply(trades)?;
+        StdoutReport.paginate_adjustments(&report)?;
+        Ok(trades)
+    }
+
+    /// Read the profile from the header of the first Bitpanda CSV file at `csv_files` which has one
+    pub fn read_profile(csv_files: &[PathBuf]) -> anyhow::Result<Profile> {
+        for path in input::resolve(csv_files)?.iter() {
+            let profile = Profile::read_csv_header(path)?;
+            if !profile.is_empty() {
+                return Ok(profile);
+            }
+        }
+        Ok(Profile::default())
+    }
+
+    /// Read the CSV at `csv_file` through the mapping at `mapping`, without failing on the rows which can't be mapped,
+    /// and output the result
+    pub async fn dry_run_mapping(mapping: &Path, csv_file: &Path) -> anyhow::Result<()> {
+        let mapping = CsvMapping::load(mapping)?;
+        info!("reading {} through the mapping", csv_file.display());
+        let export = tokio::fs::read(csv_file).await?;
+        let report = mapping.dry_run(&mut export.as_slice())?;
+        StdoutReport.paginate_mapping(&report)
+    }
+
+    /// Print the trades which satisfy `query`, with their totals; as JSON if `json` is set
+    pub fn query(trades: Vec<(Venue, Trade)>, query: TradeQuery, json: bool) -> anyhow::Result<()> {
+        let trades = TradeDatabase::from(trades);
+        let summary = TradeSummary::from(&trades.select(query));
+        if json {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &summary)?;
+            writeln!(stdout)?;
+            return Ok(());
+        }
+        StdoutReport.paginate_query(&summary)
+    }
+
+    /// Print the summaries of the taxes of the years kept in the `store`
+    #[cfg(feature = "sqlite")]
+    pub fn history(store: &Store) -> anyhow::Result<()> {
+        let reports = store.reports()?;
+        info!("found {} yearly reports in the store", reports.len());
+        StdoutPaginate.paginate_summary(&reports)
+    }
+
+    /// Write the Bitpanda CSV at `csv_file` without the personal data to `output`, with the amounts multiplied by
+    /// `scale`, if set
+    pub fn anonymize(csv_file: &Path, output: &Path, scale: Option<Decimal>) -> anyhow::Result<()> {
+        info!(
+            "anonymizing {} into {}",
+            csv_file.display(),
+            output.display()
+        );
+        let reader = std::io::BufReader::new(std::fs::File::open(csv_file)?);
+        let writer = std::fs::File::create(output)?;
+        let report = Anonymizer::default()
+            .with_scale(scale)
+            .anonymize(reader, writer)?;
+        StdoutReport.paginate_anonymized(&report, output)
+    }
+
+    /// Setup a new application for the time range `since` => `to`
+    pub fn setup_range(
+        trades: Vec<(Venue, Trade)>,
+        since: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Self> {
+        if since > to {
+            anyhow::bail!("invalid time range {} => {}", since, to);
+        }
         info!("working on time range {} => {}", since, to);
         // filter by date
-        let trades: Vec<Trade> = trades
+        let trades: Vec<(Venue, Trade)> = trades
             .into_iter()
-            .filter(|trade| since <= trade.timestamp() && to >= trade.timestamp())
+            .filter(|(_, trade)| since <= trade.timestamp() && to >= trade.timestamp())
             .collect();
         info!("working on a total amount of {} trades", trades.len());
         let trades = TradeDatabase::from(trades);
-        Ok(App { trades, since, to })
+        Ok(App {
+            trades,
+            since,
+            to,
+            self_transfers: SelfTransfers::default(),
+            cost_basis: CostBasis::default(),
+            export_dir: None,
+            explain: false,
+            opening_inventory: None,
+            closing_inventory: None,
+            carried_losses: CarriedLosses::default(),
+            harvest: false,
+            what_if: HypotheticalTrades::default(),
+            year_to_date: false,
+            profile: Profile::default(),
+            quotes: None,
+            #[cfg(feature = "sqlite")]
+            store: None,
+        })
+    }
+
+    /// Set the withdrawals which are transfers to the investor's own wallets
+    pub fn with_self_transfers(mut self, self_transfers: SelfTransfers) -> Self {
+        self.self_transfers = self_transfers;
+        self
+    }
+
+    /// Set the cost basis of the crypto deposited from outside Bitpanda
+    pub fn with_cost_basis(mut self, cost_basis: CostBasis) -> Self {
+        self.cost_basis = cost_basis;
+        self
+    }
+
+    /// Set the directory where to export gains and losses
+    pub fn with_export_dir(mut self, export_dir: Option<PathBuf>) -> Self {
+        self.export_dir = export_dir;
+        self
+    }
+
+    /// Set whether to explain how each field of the 730 has been calculated
+    pub fn with_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    /// Set the inventory of the lots held at the end of the previous year
+    pub fn with_opening_inventory(mut self, inventory: Option<Inventory>) -> Self {
+        self.opening_inventory = inventory;
+        self
+    }
+
+    /// Set the file where to write the inventory of the lots held at the end of the year
+    pub fn with_closing_inventory(mut self, path: Option<PathBuf>) -> Self {
+        self.closing_inventory = path;
+        self
+    }
+
+    /// Set the losses of the previous years not yet used
+    pub fn with_carried_losses(mut self, carried_losses: CarriedLosses) -> Self {
+        self.carried_losses = carried_losses;
+        self
+    }
+
+    /// Set whether to suggest the positions to sell to offset the realized gains
+    pub fn with_harvest(mut self, harvest: bool) -> Self {
+        self.harvest = harvest;
+        self
+    }
+
+    /// Set the hypothetical trades to simulate
+    pub fn with_what_if(mut self, what_if: HypotheticalTrades) -> Self {
+        self.what_if = what_if;
+        self
+    }
+
+    /// Set the profile of the taxpayer and of the Bitpanda account
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Set the quotes to use, instead of loading them
+    pub fn with_quotes(mut self, quotes: QuoteDatabase) -> Self {
+        self.quotes = Some(quotes);
+        self
+    }
+
+    /// Set the local store where the quotes are cached and the summary of the year is kept
+    #[cfg(feature = "sqlite")]
+    pub fn with_store(mut self, store: Option<Rc<Store>>) -> Self {
+        self.store = store;
+        self
     }
 
     /// Run application
-    pub async fn run(self) -> anyhow::Result<()> {
-        let quotes = self.load_quotes_database().await?;
+    pub async fn run(mut self) -> anyhow::Result<Outcome> {
+        if !self.profile.is_empty() {
+            StdoutReport.paginate_profile(&self.profile)?;
+        }
+        // trades with the hypothetical trades, if any, which are made on Bitpanda
+        let scenario = if self.what_if.is_empty() {
+            None
+        } else {
+            let hypothetical = self.what_if.trades(&self.trades)?;
+            Some(
+                self.trades.extended(
+                    hypothetical
+                        .into_iter()
+                        .map(|trade| (Venue::Bitpanda, trade))
+                        .collect(),
+                ),
+            )
+        };
+        let quotes = match self.quotes.take() {
+            Some(quotes) => quotes,
+            None => {
+                self.load_quotes_database(scenario.as_ref().unwrap_or(&self.trades))
+                    .await?
+            }
+        };
         debug!("quotes loaded");
         info!(
             "current FIAT balance: € {}",
             self.trades.all().fiat_balance(Fiat::Eur)
         );
         debug!("taxes setup");
-        let taxes = Taxes::new(&self.trades, &quotes, self.since, self.to);
-        let average_balance = self.calc_average_balance(&taxes)?;
+        let taxes = Taxes::new(&self.trades, &quotes, self.since, self.to)
+            .with_account_opened_at(self.profile.account_opened_at)
+            .with_opening_inventory(self.opening_inventory.as_ref());
+        let average_balances = self.calc_average_balance(&taxes)?;
+        let average_balance: Decimal = average_balances.iter().map(|(_, x)| *x).sum();
         info!("Average balance is: € {}", average_balance);
-        let ivafe = self.calc_ivafe(&taxes, average_balance);
+        let mut ivafe = self.calc_ivafe(&taxes, average_balance);
         info!("IVAFE is: € {}", ivafe);
-        let capitals_diff = self.calc_gains_and_losses(&taxes)?;
+        let mut calculator = self.gains_and_losses_calculator(&self.trades, &quotes);
+        let capitals_diff = self.calc_gains_and_losses(&taxes, &mut calculator)?;
+        let open_positions = calculator.open_positions(&quotes);
+        let netting = taxes.net_losses(&capitals_diff, &self.carried_losses);
+        let year_to_date = if self.year_to_date {
+            let estimate = taxes.year_to_date(average_balance, &netting, &open_positions)?;
+            // IVAFE is due only for the days elapsed
+            ivafe = estimate.ivafe_to_date;
+            info!("IVAFE to date is: € {}", ivafe);
+            Some(estimate)
+        } else {
+            None
+        };
+        let capital_gains_tax = netting.tax_to_pay();
         info!(
-            "gains: € {}; losses: € {}; diff: € {}; total taxes to pay: € {}",
+            "gains: € {}; losses: € {}; diff: € {}; carried losses used: € {}; total taxes to pay: € {}",
             capitals_diff.gains_value(),
             capitals_diff.losses_value(),
             capitals_diff.gains_value() + capitals_diff.losses_value(),
-            capitals_diff.tax_to_pay()
+            netting.carried_losses_used(),
+            capital_gains_tax
         );
         // repr output
         debug!("preparing 730...");
-        let m730 = Module730::prepare(average_balance, ivafe, &capitals_diff)?;
+        let m730 = Module730::prepare(
+            &average_balances,
+            taxes.period_days(),
+            ivafe,
+            &capitals_diff,
+            &netting,
+        )?;
         debug!("730 ready; writing data to output...");
-        m730.output(StdoutPaginate::default(), &capitals_diff)?;
+        m730.output(StdoutPaginate, &capitals_diff)?;
+        if self.explain {
+            StdoutPaginate.paginate_explain(&m730)?;
+        }
+        StdoutPaginate.paginate_self_transfers(&calculator.self_transfers_report())?;
+        StdoutPaginate.paginate_cost_basis(calculator.cost_basis_report())?;
+        StdoutPaginate.paginate_open_positions(&open_positions)?;
+        if let Some(estimate) = year_to_date.as_ref() {
+            StdoutPaginate.paginate_year_to_date(estimate)?;
+        }
+        if self.harvest {
+            StdoutPaginate
+                .paginate_harvesting(&LossHarvesting::new(&open_positions, &netting).advise())?;
+        }
+        let carried_losses = netting.carry_forward();
+        let closing_inventory = calculator.closing_inventory(self.to);
+        // the lots replayed from the opening inventory must match the CSV balances of the trades of the period
+        if let Some(opening_inventory) = self.opening_inventory.as_ref() {
+            let balances = WalletDatabase::load(&self.trades.all());
+            StdoutPaginate.paginate_inventory_mismatches(
+                &closing_inventory.reconcile(opening_inventory, &balances),
+            )?;
+        }
+        if let Some(path) = self.closing_inventory.as_deref() {
+            info!("writing closing inventory to {}", path.display());
+            closing_inventory.save(path)?;
+        }
+        if let Some(export_dir) = self.export_dir.as_deref() {
+            self.export_gains_and_losses(export_dir, &capitals_diff)?;
+        }
+        if let Some(scenario) = scenario {
+            self.simulate(&scenario, &quotes, &m730, capital_gains_tax + ivafe)?;
+        }
+
+        let summary = YearSummary::new(
+            self.to.year(),
+            &capitals_diff,
+            capital_gains_tax,
+            ivafe,
+            carried_losses.available_amount(self.to.year() + 1),
+        );
+        #[cfg(feature = "sqlite")]
+        self.store_summary(&summary)?;
+
+        Ok(Outcome {
+            summary,
+            closing_inventory,
+            carried_losses,
+        })
+    }
+
+    /// Setup the gains and losses calculator for `trades`
+    fn gains_and_losses_calculator(
+        &self,
+        trades: &TradeDatabase,
+        quotes: &QuoteDatabase,
+    ) -> GainsAndLossesCalculator {
+        let mut cost_basis = self.cost_basis.clone();
+        cost_basis.resolve_market_prices(trades, quotes);
+        let mut calculator = GainsAndLossesCalculator::default()
+            .with_self_transfers(self.self_transfers.clone())
+            .with_cost_basis(cost_basis);
+        if let Some(inventory) = self.opening_inventory.clone() {
+            if inventory.date >= self.since {
+                warn!(
+                    "opening inventory date {} is not before {}",
+                    inventory.date, self.since
+                );
+            }
+            calculator = calculator.with_opening_inventory(inventory);
+        }
+        calculator
+    }
+
+    /// Run the 730 pipeline on the `scenario` trades, which include the hypothetical trades,
+    /// and output the changes compared to the module calculated on the actual trades
+    fn simulate(
+        &self,
+        scenario: &TradeDatabase,
+        quotes: &QuoteDatabase,
+        m730: &Module730,
+        total_tax: Decimal,
+    ) -> anyhow::Result<()> {
+        debug!("simulating hypothetical trades");
+        let taxes = Taxes::new(scenario, quotes, self.since, self.to)
+            .with_account_opened_at(self.profile.account_opened_at)
+            .with_opening_inventory(self.opening_inventory.as_ref());
+        let average_balances = self.calc_average_balance(&taxes)?;
+        let ivafe = self.calc_ivafe(&taxes, average_balances.iter().map(|(_, x)| *x).sum());
+        let mut calculator = self.gains_and_losses_calculator(scenario, quotes);
+        let capitals_diff = self.calc_gains_and_losses(&taxes, &mut calculator)?;
+        let netting = taxes.net_losses(&capitals_diff, &self.carried_losses);
+        let simulated_m730 = Module730::prepare(
+            &average_balances,
+            taxes.period_days(),
+            ivafe,
+            &capitals_diff,
+            &netting,
+        )?;
+        StdoutPaginate.paginate_what_if(
+            &m730.compare(&simulated_m730),
+            total_tax,
+            netting.tax_to_pay() + ivafe,
+        )
+    }
+
+    /// Export matched lots and gains and losses to CSV files in `export_dir`
+    fn export_gains_and_losses(
+        &self,
+        export_dir: &Path,
+        gains_and_losses: &GainsAndLosses,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(export_dir)?;
+        let lots_path = export_dir.join("matched_lots.csv");
+        info!("exporting matched lots to {}", lots_path.display());
+        gains_and_losses.export_lots(std::fs::File::create(lots_path)?)?;
+        let capitals_path = export_dir.join("gains_and_losses.csv");
+        info!("exporting gains and losses to {}", capitals_path.display());
+        gains_and_losses.export_capital_diffs(std::fs::File::create(capitals_path)?)?;
 
         Ok(())
     }
 
-    /// Load quotes database from trades
-    async fn load_quotes_database(&self) -> anyhow::Result<QuoteDatabase> {
+    /// Load quotes database from trades and from the lots of the opening inventory
+    async fn load_quotes_database(&self, trades: &TradeDatabase) -> anyhow::Result<QuoteDatabase> {
         debug!("loading quotes from {} to {}...", self.since, self.to);
         let mut sp = Spinner::new(Spinners::Dots, "loading asset prices...".to_string());
-        let quotes = QuoteDatabase::load(&self.trades, self.since, self.to).await?;
+        let held = self.held_assets();
+        #[cfg(feature = "sqlite")]
+        let quotes = match self.store.as_deref() {
+            Some(store) => store.load_quotes(trades, &held, self.since, self.to).await,
+            None => QuoteDatabase::load(trades, &held, self.since, self.to).await,
+        }?;
+        #[cfg(not(feature = "sqlite"))]
+        let quotes = QuoteDatabase::load(trades, &held, self.since, self.to).await?;
         sp.stop();
         Ok(quotes)
     }
 
-    fn calc_average_balance(&self, taxes: &Taxes) -> anyhow::Result<Decimal> {
+    /// Returns the assets held in the opening inventory, which must be quoted even if they are not traded
+    fn held_assets(&self) -> Vec<Asset> {
+        self.opening_inventory
+            .as_ref()
+            .map(|inventory| inventory.assets())
+            .unwrap_or_default()
+    }
+
+    /// Keep the summary of the year in the store, if set.
+    /// Only whole years without hypothetical trades are kept
+    #[cfg(feature = "sqlite")]
+    fn store_summary(&self, summary: &YearSummary) -> anyhow::Result<()> {
+        let Some(store) = self.store.as_deref() else {
+            return Ok(());
+        };
+        if self.year_to_date
+            || !self.what_if.is_empty()
+            || (self.since, self.to) != timezone::year_range(summary.year)?
+        {
+            debug!("not storing the summary of a partial year or of a scenario");
+            return Ok(());
+        }
+        info!("storing the summary of {}", summary.year);
+        store.insert_report(summary)
+    }
+
+    fn calc_average_balance(&self, taxes: &Taxes) -> anyhow::Result<Vec<(Venue, Decimal)>> {
         debug!("calculating IVAFE");
         let mut sp = Spinner::new(Spinners::Dots, "Calculating IVAFE...".to_string());
-        let avg_balance = taxes.average_balance();
+        let avg_balance = taxes.average_balance_by_venue();
         sp.stop();
         avg_balance
     }
@@ -108,13 +591,17 @@ impl App {
         ivafe
     }
 
-    fn calc_gains_and_losses(&self, taxes: &Taxes) -> anyhow::Result<GainsAndLosses> {
+    fn calc_gains_and_losses(
+        &self,
+        taxes: &Taxes,
+        calculator: &mut GainsAndLossesCalculator,
+    ) -> anyhow::Result<GainsAndLosses> {
         debug!("calculating gains and losses");
         let mut sp = Spinner::new(
             Spinners::Dots,
             "Calculating capital gains and losses...".to_string(),
         );
-        let capital_diff = taxes.capital_gains_and_losses();
+        let capital_diff = taxes.capital_gains_and_losses(calculator);
         sp.stop();
         capital_diff
     }
@@ -130,9 +617,15 @@ mod test {
     #[tokio::test]
     async fn should_init_app_from_args() {
         crate::mock::log();
-        let app = App::setup(2022, Path::new("./test/bitpanda.csv"))
-            .await
-            .unwrap();
+        let trades = App::parse_trades(
+            &[PathBuf::from("./test/bitpanda.csv")],
+            &[],
+            &IsinSymbols::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        let app = App::setup(2022, trades).unwrap();
         assert_eq!(app.trades.all().trades().len(), 12);
     }
 }
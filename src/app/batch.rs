@@ -0,0 +1,207 @@
+//! # Batch
+//!
+//! This module exposes the batch run of the application over several years, where the lots held and the unused
+//! losses at the end of a year are carried to the next one.
+
+use bitpanda_csv::{Asset, Trade};
+use chrono::{DateTime, FixedOffset};
+use spinners::{Spinner, Spinners};
+use std::ops::RangeInclusive;
+#[cfg(feature = "sqlite")]
+use std::rc::Rc;
+use std::str::FromStr;
+
+use super::App;
+#[cfg(feature = "sqlite")]
+use crate::database::Store;
+use crate::database::{QuoteDatabase, TradeDatabase, Venue};
+use crate::module730::{Paginate, Stdout as StdoutPaginate, YearSummary};
+use crate::timezone;
+
+/// A range of years, expressed as `<first>..<last>` (e.g. `2021..2025`); both years are included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Years(RangeInclusive<i32>);
+
+impl FromStr for Years {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((first, last)) = s.split_once("..") else {
+            anyhow::bail!("invalid years '{}'; expected '<first>..<last>'", s);
+        };
+        let first = i32::from_str(first.trim())?;
+        let last = i32::from_str(last.trim_start_matches('=').trim())?;
+        if first > last {
+            anyhow::bail!("invalid years '{}'; {} is after {}", s, first, last);
+        }
+        Ok(Self(first..=last))
+    }
+}
+
+/// Batch run over several years
+pub struct Batch {
+    years: Years,
+    trades: Vec<(Venue, Trade)>,
+    #[cfg(feature = "sqlite")]
+    store: Option<Rc<Store>>,
+    quotes: Option<QuoteDatabase>,
+}
+
+impl Batch {
+    /// Setup the batch run over `years` of `trades`
+    pub fn new(years: Years, trades: Vec<(Venue, Trade)>) -> Self {
+        Self {
+            years,
+            trades,
+            #[cfg(feature = "sqlite")]
+            store: None,
+            quotes: None,
+        }
+    }
+
+    /// Set the local store where the quotes are cached and the summary of each year is kept
+    #[cfg(feature = "sqlite")]
+    pub fn with_store(mut self, store: Option<Rc<Store>>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Set the quotes of the whole time range, instead of loading them
+    #[cfg(test)]
+    pub fn with_quotes(mut self, quotes: QuoteDatabase) -> Self {
+        self.quotes = Some(quotes);
+        self
+    }
+
+    /// Run the application for each year and return the summary of each year.
+    /// `configure` is called with the year to setup the application of each year; the opening inventory and the carried losses
+    /// it sets are used only for the first year, then the ones at the end of the previous year are used.
+    pub async fn run<F>(mut self, configure: F) -> anyhow::Result<Vec<YearSummary>>
+    where
+        F: Fn(i32, App) -> App,
+    {
+        let (first_since, _) = timezone::year_range(*self.years.0.start())?;
+        let (_, last_to) = timezone::year_range(*self.years.0.end())?;
+        let mut quotes = self.quotes.take();
+        let mut summaries = Vec::new();
+        let mut carry_over = None;
+        for year in self.years.0.clone() {
+            info!("running year {}", year);
+            StdoutPaginate.paginate_year_header(year)?;
+            let (since, to) = timezone::year_range(year)?;
+            let app = App::setup_range(self.trades.clone(), since, to)?;
+            #[cfg(feature = "sqlite")]
+            let app = app.with_store(self.store.clone());
+            let mut app = configure(year, app);
+            if let Some((inventory, carried_losses)) = carry_over.take() {
+                app = app
+                    .with_opening_inventory(Some(inventory))
+                    .with_carried_losses(carried_losses);
+            }
+            // quotes are loaded at the first year, so that the lots of its opening inventory are quoted too
+            let year_quotes = match quotes.as_ref() {
+                Some(quotes) => quotes.at(to),
+                None => {
+                    let loaded = self
+                        .load_quotes_database(first_since, last_to, &app.held_assets())
+                        .await?;
+                    quotes.insert(loaded).at(to)
+                }
+            };
+            let app = app.with_quotes(year_quotes);
+            let outcome = app.run().await?;
+            summaries.push(outcome.summary);
+            carry_over = Some((outcome.closing_inventory, outcome.carried_losses));
+        }
+        StdoutPaginate.paginate_summary(&summaries)?;
+        Ok(summaries)
+    }
+
+    /// Load the quotes of the whole time range once, so that they are shared by all the years
+    async fn load_quotes_database(
+        &self,
+        since: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+        held: &[Asset],
+    ) -> anyhow::Result<QuoteDatabase> {
+        debug!("loading quotes from {} to {}...", since, to);
+        let mut sp = Spinner::new(Spinners::Dots, "loading asset prices...".to_string());
+        let trades = TradeDatabase::from(self.trades.clone());
+        #[cfg(feature = "sqlite")]
+        let quotes = match self.store.as_deref() {
+            Some(store) => store.load_quotes(&trades, held, since, to).await,
+            None => QuoteDatabase::load(&trades, held, since, to).await,
+        }?;
+        #[cfg(not(feature = "sqlite"))]
+        let quotes = QuoteDatabase::load(&trades, held, since, to).await?;
+        sp.stop();
+        Ok(quotes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::finance::{Quote, Quotes};
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{Asset, CryptoCurrency, Currency};
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
+
+    #[test]
+    fn should_parse_years() {
+        assert_eq!(Years::from_str("2021..2025").unwrap(), Years(2021..=2025));
+        assert_eq!(Years::from_str("2021..=2022").unwrap(), Years(2021..=2022));
+        assert!(Years::from_str("2025..2021").is_err());
+        assert!(Years::from_str("2021").is_err());
+    }
+
+    #[tokio::test]
+    async fn should_offset_gains_with_losses_of_previous_year() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::carried_loss_mock();
+        let btc = Asset::Currency(Currency::Crypto(CryptoCurrency::Btc));
+        let (_, to) = timezone::year_range(2023).unwrap();
+        let quotes = QuoteDatabase::from_history(
+            HashMap::from([(
+                btc,
+                Quotes::from(vec![
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+                        dec!(20000),
+                    ),
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap(),
+                        dec!(30000),
+                    ),
+                ]),
+            )]),
+            to,
+        );
+        let summaries = Batch::new(
+            Years(2022..=2023),
+            trades
+                .all()
+                .with_venues()
+                .map(|(venue, trade)| (venue, trade.clone()))
+                .collect(),
+        )
+        .with_quotes(quotes)
+        .run(|_, app| app)
+        .await
+        .unwrap();
+        assert_eq!(summaries.len(), 2);
+        // loss of 500 in 2022, carried to 2023
+        assert_eq!(summaries[0].losses, dec!(-500));
+        assert_eq!(summaries[0].capital_gains_tax, Decimal::ZERO);
+        assert_eq!(summaries[0].carried_losses, dec!(500));
+        // gain of 800 in 2023, of which only 300 are taxed
+        assert_eq!(summaries[1].gains, dec!(800));
+        assert_eq!(summaries[1].capital_gains_tax, dec!(78));
+        assert_eq!(summaries[1].carried_losses, Decimal::ZERO);
+    }
+}
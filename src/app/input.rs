@@ -0,0 +1,106 @@
+//! # Input
+//!
+//! This module resolves the CSV inputs provided by the user, which can be files, directories or glob patterns
+
+use std::path::{Path, PathBuf};
+
+/// Resolve `inputs` into the list of CSV files to read:
+///
+/// - files are taken as they are;
+/// - directories are replaced by the CSV files they contain;
+/// - paths whose file name contains `*` or `?` are replaced by the files matching the pattern.
+///
+/// The files found in a directory or by a pattern are sorted by name.
+pub fn resolve(inputs: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs.iter() {
+        let pattern = input
+            .file_name()
+            .and_then(|x| x.to_str())
+            .filter(|x| x.contains('*') || x.contains('?'));
+        let found = if let Some(pattern) = pattern {
+            let dir = match input.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            list_files(dir, |name| matches(pattern, name))?
+        } else if input.is_dir() {
+            list_files(input, |name| name.to_lowercase().ends_with(".csv"))?
+        } else {
+            vec![input.clone()]
+        };
+        if found.is_empty() {
+            anyhow::bail!("no CSV file found at {}", input.display());
+        }
+        files.extend(found);
+    }
+    debug!("resolved CSV inputs: {:?}", files);
+    Ok(files)
+}
+
+/// List the files in `dir` whose name satisfies `filter`, sorted by name
+fn list_files<F>(dir: &Path, filter: F) -> anyhow::Result<Vec<PathBuf>>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.file_name().and_then(|x| x.to_str()).map(&filter) == Some(true) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns whether `name` matches the glob `pattern`, where `*` matches any sequence of characters and `?` any
+/// single character
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_chars(&pattern, &name)
+}
+
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches_chars(&pattern[1..], name)
+                || (!name.is_empty() && matches_chars(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches_chars(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches_chars(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_match_glob() {
+        assert!(matches("*.csv", "bitpanda.csv"));
+        assert!(matches("bitpanda-202?.csv", "bitpanda-2022.csv"));
+        assert!(matches("*2022*", "bitpanda-2022.csv"));
+        assert!(!matches("*.csv", "bitpanda.json"));
+        assert!(!matches("bitpanda-202?.csv", "bitpanda-22.csv"));
+    }
+
+    #[test]
+    fn should_resolve_inputs() {
+        crate::mock::log();
+        let expected = vec![PathBuf::from("./test/bitpanda.csv")];
+        assert_eq!(resolve(&[PathBuf::from("./test")]).unwrap(), expected);
+        assert_eq!(resolve(&[PathBuf::from("./test/*.csv")]).unwrap(), expected);
+        assert_eq!(
+            resolve(&[PathBuf::from("./test/bitpanda.csv")]).unwrap(),
+            expected
+        );
+        assert!(resolve(&[PathBuf::from("./test/*.json")]).is_err());
+    }
+}
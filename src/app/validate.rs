@@ -0,0 +1,123 @@
+//! # Validate
+//!
+//! This module exposes the validation of the trades, which replays them through the lot ledger without fetching
+//! any quote, to find the problems which would make the calculation fail or be wrong.
+
+use bitpanda_csv::Trade;
+
+use crate::database::{Profile, TradeDatabase, Venue};
+use crate::report::{PaginateProfile, PaginateValidation, Stdout as StdoutReport};
+use crate::tax::{CostBasis, GainsAndLossesCalculator, Inventory, SelfTransfers, ValidationReport};
+
+/// Validation of the trades
+pub struct Validation {
+    trades: TradeDatabase,
+    self_transfers: SelfTransfers,
+    cost_basis: CostBasis,
+    opening_inventory: Option<Inventory>,
+    profile: Profile,
+}
+
+impl Validation {
+    /// Setup the validation of `trades`
+    pub fn new(trades: Vec<(Venue, Trade)>) -> Self {
+        Self {
+            trades: TradeDatabase::from(trades),
+            self_transfers: SelfTransfers::default(),
+            cost_basis: CostBasis::default(),
+            opening_inventory: None,
+            profile: Profile::default(),
+        }
+    }
+
+    /// Set the withdrawals to treat as transfers to wallets owned by the investor
+    pub fn with_self_transfers(mut self, self_transfers: SelfTransfers) -> Self {
+        self.self_transfers = self_transfers;
+        self
+    }
+
+    /// Set the cost basis of the assets deposited from outside Bitpanda
+    pub fn with_cost_basis(mut self, cost_basis: CostBasis) -> Self {
+        self.cost_basis = cost_basis;
+        self
+    }
+
+    /// Set the lots held before the first trade
+    pub fn with_opening_inventory(mut self, inventory: Option<Inventory>) -> Self {
+        self.opening_inventory = inventory;
+        self
+    }
+
+    /// Set the profile of the taxpayer, which heads the report
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Replay the trades and output the problems found
+    pub fn run(self) -> anyhow::Result<ValidationReport> {
+        let mut calculator = GainsAndLossesCalculator::default()
+            .with_self_transfers(self.self_transfers)
+            .with_cost_basis(self.cost_basis);
+        if let Some(inventory) = self.opening_inventory {
+            calculator = calculator.with_opening_inventory(inventory);
+        }
+        let report = calculator.validate(&self.trades);
+        if !self.profile.is_empty() {
+            StdoutReport.paginate_profile(&self.profile)?;
+        }
+        info!(
+            "validated {} trades; found {} issues",
+            report.trades,
+            report.issues.len()
+        );
+        StdoutReport.paginate_validation(&report)?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::app::App;
+    use crate::database::IsinSymbols;
+    use crate::tax::ValidationIssueKind;
+
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn should_validate_trades() {
+        crate::mock::log();
+        let trades = App::parse_trades(
+            &[PathBuf::from("./test/bitpanda.csv")],
+            &[],
+            &IsinSymbols::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        let count = trades.len();
+        let report = Validation::new(trades).run().unwrap();
+        assert_eq!(report.trades, count);
+        // the export starts after the first buys
+        assert!(report.is_blocking());
+        assert_eq!(report.issues.len(), 4);
+        assert!(matches!(
+            report.issues[0].kind,
+            ValidationIssueKind::TransferAsSplit { .. }
+        ));
+        assert!(matches!(
+            report.issues[1].kind,
+            ValidationIssueKind::SellWithoutBuy { .. }
+        ));
+        assert_eq!(
+            report.issues[2].kind,
+            ValidationIssueKind::NegativeBalance {
+                available: dec!(0.01329013),
+                required: dec!(0.05039663)
+            }
+        );
+    }
+}
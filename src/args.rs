@@ -3,7 +3,15 @@
 //! CLI arguments
 
 use argh::FromArgs;
+use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, InOut, TransactionType};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
 
+use crate::app::Years;
+use crate::database::{parse_asset, HypotheticalTrade, ImportSource, TradeOrder, TradeQuery};
+use crate::timezone;
+
+use std::ops::Bound;
 use std::path::PathBuf;
 
 #[derive(FromArgs)]
@@ -13,13 +21,348 @@ Please, consider supporting the author <https://ko-fi.com/veeso>"
 )]
 pub struct Args {
     #[argh(option, description = "the year to calculate the taxes for")]
-    pub year: i32,
+    pub year: Option<i32>,
+    #[argh(
+        option,
+        description = "first day of a custom reporting period (YYYY-MM-DD); requires --to"
+    )]
+    pub from: Option<NaiveDate>,
+    #[argh(
+        option,
+        description = "last day of a custom reporting period (YYYY-MM-DD); requires --from"
+    )]
+    pub to: Option<NaiveDate>,
+    #[argh(
+        option,
+        description = "run for several years, e.g. 2021..2025, carrying the open lots and the unused losses to the next year"
+    )]
+    pub years: Option<Years>,
+    #[argh(
+        switch,
+        description = "estimate the taxes of the current year, from the beginning of the year to today"
+    )]
+    pub ytd: bool,
+    #[argh(
+        option,
+        description = "import the trades exported by another venue, as <venue>:<path> (e.g. coinbase:coinbase.csv, kraken:trades.csv, trade-republic:transactions.csv)"
+    )]
+    pub import: Vec<ImportSource>,
+    #[argh(
+        option,
+        description = "JSON file mapping the ISINs of the imported securities to their symbol on Bitpanda (e.g. US0378331005 to AAPL), so that they can be quoted"
+    )]
+    pub isin_symbols: Option<PathBuf>,
+    #[argh(
+        switch,
+        description = "fetch the trades from the Bitpanda API, with the API key in the BITPANDA_API_KEY environment variable"
+    )]
+    pub bitpanda_api: bool,
+    #[argh(
+        switch,
+        description = "keep the trades, the quotes and the yearly reports in the local SQLite database under the user's data directory, so that later runs only import the new trades"
+    )]
+    pub store: bool,
+    #[argh(
+        option,
+        description = "path of the local SQLite database; implies --store"
+    )]
+    pub store_file: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON file with the manual adjustments (trades to add, drop or reclassify) to apply to the CSV trades"
+    )]
+    pub adjustments: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON file with the profile of the taxpayer (e.g. codice fiscale); it overrides the data read from the CSV"
+    )]
+    pub profile: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON file with the cost basis of the crypto deposited from outside Bitpanda"
+    )]
+    pub cost_basis: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON file which lists the crypto withdrawals towards your own wallets"
+    )]
+    pub self_transfers: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON file with the losses of the previous years not yet used"
+    )]
+    pub carried_losses: Option<PathBuf>,
+    #[argh(
+        switch,
+        description = "suggest which positions to sell before the end of the year to offset the realized gains"
+    )]
+    pub harvest: bool,
+    #[argh(
+        option,
+        description = "simulate a hypothetical trade, e.g. \"sell 0.5 BTC 2022-12-20 16000\" (<buy|sell> <quantity> <asset> <date> <unit price>)"
+    )]
+    pub what_if: Vec<HypotheticalTrade>,
+    #[argh(option, description = "JSON file with hypothetical trades to simulate")]
+    pub what_if_file: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "JSON inventory of the lots held at the end of the previous year"
+    )]
+    pub opening_inventory: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "write the JSON inventory of the lots held at the end of the year to this file"
+    )]
+    pub closing_inventory: Option<PathBuf>,
+    #[argh(
+        option,
+        description = "directory where to export the matched lots and the gains and losses as CSV (a subdirectory for each year with --years)"
+    )]
+    pub export_dir: Option<PathBuf>,
+    #[argh(
+        switch,
+        short = 'e',
+        description = "explain how each field of the 730 has been calculated"
+    )]
+    pub explain: bool,
     #[argh(switch, short = 'D', description = "enable TRACE log level")]
     pub debug: bool,
     #[argh(switch, short = 'v', description = "verbose mode")]
     pub verbose: bool,
     #[argh(switch, short = 'V', description = "print version")]
     pub version: bool,
-    #[argh(positional, description = "the csv file to read trades from")]
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+    #[argh(
+        positional,
+        description = "the csv files to read trades from; directories and glob patterns (e.g. 'exports/*.csv') are accepted"
+    )]
+    pub csv_files: Vec<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Validate(ValidateArgs),
+    Mapping(MappingArgs),
+    Anonymize(AnonymizeArgs),
+    Query(QueryArgs),
+    History(HistoryArgs),
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "validate",
+    description = "replay the trades without fetching any quote and report the problems found"
+)]
+pub struct ValidateArgs {
+    #[argh(
+        positional,
+        description = "the csv files to read trades from; directories and glob patterns (e.g. 'exports/*.csv') are accepted"
+    )]
+    pub csv_files: Vec<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "mapping",
+    description = "dry-run a CSV mapping, printing the trades read and the rows which couldn't be mapped"
+)]
+pub struct MappingArgs {
+    #[argh(positional, description = "the JSON file with the CSV mapping")]
+    pub mapping: PathBuf,
+    #[argh(positional, description = "the CSV file to read through the mapping")]
     pub csv_file: PathBuf,
 }
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "anonymize",
+    description = "rewrite a Bitpanda CSV without the personal data, so that it can be attached to a bug report"
+)]
+pub struct AnonymizeArgs {
+    #[argh(
+        option,
+        description = "multiply the amounts by this factor (e.g. 0.37), leaving the market prices untouched"
+    )]
+    pub scale: Option<Decimal>,
+    #[argh(positional, description = "the Bitpanda CSV file to anonymize")]
+    pub csv_file: PathBuf,
+    #[argh(positional, description = "where to write the anonymized CSV file")]
+    pub output: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "history",
+    description = "print the summary of the taxes of the years kept in the local SQLite database"
+)]
+pub struct HistoryArgs {}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "query",
+    description = "print the trades which satisfy the filters, with the totals for each asset and asset class"
+)]
+pub struct QueryArgs {
+    #[argh(
+        option,
+        from_str_fn(parse_asset_arg),
+        description = "select the trades of this asset (e.g. ADA); can be repeated"
+    )]
+    pub asset: Vec<Asset>,
+    #[argh(
+        option,
+        from_str_fn(parse_asset_class),
+        description = "select the trades of this asset class: fiat, stock, crypto, etf, commodity or metal; can be repeated"
+    )]
+    pub class: Vec<AssetClass>,
+    #[argh(
+        option,
+        long = "type",
+        from_str_fn(parse_transaction_type),
+        description = "select the trades of this type: buy, sell, deposit, withdrawal or transfer; can be repeated"
+    )]
+    pub transaction_type: Vec<TransactionType>,
+    #[argh(
+        option,
+        from_str_fn(parse_in_out),
+        description = "select the trades with this direction: incoming or outgoing"
+    )]
+    pub in_out: Option<InOut>,
+    #[argh(
+        option,
+        from_str_fn(parse_fiat),
+        description = "select the trades in this FIAT currency (e.g. EUR)"
+    )]
+    pub fiat: Option<Fiat>,
+    #[argh(option, description = "select the trades since this day (YYYY-MM-DD)")]
+    pub from: Option<NaiveDate>,
+    #[argh(option, description = "select the trades until this day (YYYY-MM-DD)")]
+    pub to: Option<NaiveDate>,
+    #[argh(option, description = "minimum FIAT amount of the trades")]
+    pub min_fiat: Option<Decimal>,
+    #[argh(option, description = "maximum FIAT amount of the trades")]
+    pub max_fiat: Option<Decimal>,
+    #[argh(option, description = "minimum asset amount of the trades")]
+    pub min_amount: Option<Decimal>,
+    #[argh(option, description = "maximum asset amount of the trades")]
+    pub max_amount: Option<Decimal>,
+    #[argh(
+        option,
+        description = "select the trade with this transaction ID; can be repeated"
+    )]
+    pub id: Vec<String>,
+    #[argh(
+        option,
+        description = "sort the trades by date, -date, amount or -amount (descending with -)"
+    )]
+    pub order: Option<TradeOrder>,
+    #[argh(switch, description = "print the trades and the totals as JSON")]
+    pub json: bool,
+    #[argh(
+        positional,
+        description = "the csv files to read trades from; directories and glob patterns (e.g. 'exports/*.csv') are accepted"
+    )]
+    pub csv_files: Vec<PathBuf>,
+}
+
+impl QueryArgs {
+    /// Build the trade query from the filters; repeated filters are in OR
+    pub fn query(&self) -> TradeQuery {
+        let mut query = TradeQuery::default()
+            .and(any(&self.asset, |x| {
+                TradeQuery::default().asset_eq(x.clone())
+            }))
+            .and(any(&self.class, |x| TradeQuery::default().asset_class(*x)))
+            .and(any(&self.transaction_type, |x| {
+                TradeQuery::default().transaction_type(*x)
+            }));
+        if let Some(in_out) = self.in_out {
+            query = query.in_out(in_out);
+        }
+        if let Some(fiat) = self.fiat {
+            query = query.fiat(fiat);
+        }
+        if let Some(from) = self.from {
+            query = query.after(timezone::start_of_day(from));
+        }
+        if let Some(to) = self.to {
+            query = query.before(timezone::end_of_day(to));
+        }
+        if self.min_fiat.is_some() || self.max_fiat.is_some() {
+            query = query.amount_fiat((bound(self.min_fiat), bound(self.max_fiat)));
+        }
+        if self.min_amount.is_some() || self.max_amount.is_some() {
+            query = query.amount_asset((bound(self.min_amount), bound(self.max_amount)));
+        }
+        if !self.id.is_empty() {
+            query = query.transaction_ids(&self.id);
+        }
+        if let Some(order) = self.order {
+            query = query.order_by(order);
+        }
+        query
+    }
+}
+
+/// Returns the query satisfied by the trades which satisfy the query of at least one of `values`
+fn any<T>(values: &[T], query: impl Fn(&T) -> TradeQuery) -> TradeQuery {
+    values
+        .iter()
+        .map(query)
+        .reduce(TradeQuery::or)
+        .unwrap_or_default()
+}
+
+/// Returns the inclusive bound of a range, if set
+fn bound(value: Option<Decimal>) -> Bound<Decimal> {
+    value.map(Bound::Included).unwrap_or(Bound::Unbounded)
+}
+
+fn parse_asset_arg(value: &str) -> Result<Asset, String> {
+    parse_asset(value).map_err(|_| format!("unknown asset '{}'", value))
+}
+
+fn parse_asset_class(value: &str) -> Result<AssetClass, String> {
+    match value.to_lowercase().as_str() {
+        "fiat" => Ok(AssetClass::Fiat),
+        "stock" => Ok(AssetClass::Stock),
+        "crypto" | "cryptocurrency" => Ok(AssetClass::Cryptocurrency),
+        "etf" => Ok(AssetClass::Etf),
+        "commodity" => Ok(AssetClass::Commodity),
+        "metal" => Ok(AssetClass::Metal),
+        _ => Err(format!("unknown asset class '{}'", value)),
+    }
+}
+
+fn parse_transaction_type(value: &str) -> Result<TransactionType, String> {
+    match value.to_lowercase().as_str() {
+        "buy" => Ok(TransactionType::Buy),
+        "sell" => Ok(TransactionType::Sell),
+        "deposit" => Ok(TransactionType::Deposit),
+        "withdrawal" => Ok(TransactionType::Withdrawal),
+        "transfer" => Ok(TransactionType::Transfer),
+        _ => Err(format!("unknown transaction type '{}'", value)),
+    }
+}
+
+fn parse_in_out(value: &str) -> Result<InOut, String> {
+    match value.to_lowercase().as_str() {
+        "incoming" | "in" => Ok(InOut::Incoming),
+        "outgoing" | "out" => Ok(InOut::Outgoing),
+        _ => Err(format!("unknown direction '{}'", value)),
+    }
+}
+
+fn parse_fiat(value: &str) -> Result<Fiat, String> {
+    match parse_asset(&value.to_uppercase()) {
+        Ok(Asset::Currency(Currency::Fiat(fiat))) => Ok(fiat),
+        _ => Err(format!("unknown FIAT currency '{}'", value)),
+    }
+}
@@ -0,0 +1,250 @@
+//! # Anonymizer
+//!
+//! This module exposes the anonymizer of the Bitpanda CSV, which removes the personal data from an export
+//! so that it can be attached to a bug report
+
+use rust_decimal::Decimal;
+use std::io::{BufRead, Write};
+
+/// Beginning of the column headers line
+const COLUMN_HEADERS: &str = "\"Transaction ID\"";
+/// Fake identity written in place of the name and birth date of the account holder
+const FAKE_IDENTITY: &str = "\"Mario Rossi, 1970-01-01\"";
+/// Fake email written in place of the email of the account holder
+const FAKE_EMAIL: &str = "mario.rossi@example.com";
+/// Position of the transaction ID
+const COL_TRANSACTION_ID: usize = 0;
+/// Position of the amounts which are scaled: "Amount Fiat", "Amount Asset", "Fee" and "Spread"
+const COL_AMOUNTS: [usize; 4] = [4, 6, 12, 14];
+
+/// Rewrites a Bitpanda CSV without the personal data of the account holder:
+///
+/// - the name, the birth date and the email in the lines before the column headers are replaced by fake ones;
+/// - the transaction IDs are replaced by their hash, keeping the type prefix (e.g. `T` for trades). The same ID is
+///   always hashed into the same value, so the trades found in more than one export are still matched;
+/// - the amounts are optionally multiplied by a scale factor, leaving the market prices untouched.
+///
+/// The opening date of the account and everything else are kept, so the anonymized CSV is parsed as the original one
+/// and the taxes calculated on it are structurally equivalent.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    scale: Option<Decimal>,
+}
+
+/// The outcome of an anonymization
+#[derive(Debug)]
+pub struct AnonymizeReport {
+    /// Lines of the header which have been redacted
+    pub redacted_lines: usize,
+    /// Trades rewritten
+    pub trades: usize,
+    pub scale: Option<Decimal>,
+}
+
+impl Anonymizer {
+    /// Multiply the amounts by `scale`
+    pub fn with_scale(mut self, scale: Option<Decimal>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Read the Bitpanda CSV from `reader` and write the anonymized one to `writer`
+    pub fn anonymize(
+        &self,
+        reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> anyhow::Result<AnonymizeReport> {
+        let mut report = AnonymizeReport {
+            redacted_lines: 0,
+            trades: 0,
+            scale: self.scale,
+        };
+        let mut lines = reader.lines();
+        // header; written as it is, since the parser looks for the exact column headers line
+        for line in lines.by_ref() {
+            let line = line?;
+            let redacted = Self::redact_header_line(&line);
+            if redacted.is_some() {
+                report.redacted_lines += 1;
+            }
+            let is_column_headers = line.starts_with(COLUMN_HEADERS);
+            writeln!(writer, "{}", redacted.unwrap_or(line))?;
+            if is_column_headers {
+                break;
+            }
+        }
+        // trades
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(writer);
+        let body = lines.collect::<Result<Vec<_>, _>>()?.join("\n");
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(body.as_bytes());
+        for record in reader.records() {
+            let record = record?;
+            let fields = record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| match i {
+                    COL_TRANSACTION_ID => Ok(hash_transaction_id(field)),
+                    i if COL_AMOUNTS.contains(&i) => self.scale_amount(field),
+                    _ => Ok(field.to_string()),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            writer.write_record(&fields)?;
+            report.trades += 1;
+        }
+        writer.flush()?;
+        debug!(
+            "anonymized {} trades; {} header lines redacted",
+            report.trades, report.redacted_lines
+        );
+        Ok(report)
+    }
+
+    /// Returns the redacted header `line`, if it contains personal data
+    fn redact_header_line(line: &str) -> Option<String> {
+        let value = line.trim().trim_matches('"').trim();
+        if value.contains('@') && !value.contains(char::is_whitespace) {
+            return Some(FAKE_EMAIL.to_string());
+        }
+        match value.rsplit_once(',') {
+            Some((_, birth_date))
+                if chrono::NaiveDate::parse_from_str(birth_date.trim(), "%Y-%m-%d").is_ok() =>
+            {
+                Some(FAKE_IDENTITY.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Multiply the amount in `field` by the scale; placeholders (e.g. `-`) are kept
+    fn scale_amount(&self, field: &str) -> anyhow::Result<String> {
+        let (Some(scale), Ok(amount)) = (self.scale, field.trim().parse::<Decimal>()) else {
+            return Ok(field.to_string());
+        };
+        let scaled = (amount * scale).round_dp(amount.scale());
+        Ok(format!("{:.*}", amount.scale() as usize, scaled))
+    }
+}
+
+/// Hash the transaction ID into an ID with the same format (e.g. `T2f5b2c3e-...`), keeping the type prefix
+fn hash_transaction_id(id: &str) -> String {
+    let (prefix, uuid) = match id.chars().next() {
+        Some(c) if c.is_ascii_uppercase() => id.split_at(1),
+        _ => ("", id),
+    };
+    let high = fnv1a(uuid.as_bytes(), 0xcbf29ce484222325);
+    let low = fnv1a(uuid.as_bytes(), high);
+    format!(
+        "{}{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        prefix,
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// FNV-1a hash of `bytes`, which is stable across platforms and releases
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    bytes.iter().fold(seed, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::database::Profile;
+
+    use bitpanda_csv::AsyncBitpandaTradeParser;
+    use pretty_assertions::assert_eq;
+
+    async fn anonymize(scale: Option<Decimal>) -> (AnonymizeReport, String) {
+        let csv = std::fs::read("./test/bitpanda.csv").unwrap();
+        let mut output = Vec::new();
+        let report = Anonymizer::default()
+            .with_scale(scale)
+            .anonymize(csv.as_slice(), &mut output)
+            .unwrap();
+        (report, String::from_utf8(output).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_anonymize_csv() {
+        crate::mock::log();
+        let original = AsyncBitpandaTradeParser::parse(
+            std::fs::read("./test/bitpanda.csv").unwrap().as_slice(),
+        )
+        .await
+        .unwrap();
+        let (report, output) = anonymize(None).await;
+        assert_eq!(report.redacted_lines, 2);
+        assert_eq!(report.trades, original.len());
+        assert!(!output.contains("Omar Gabberone"));
+        assert!(!output.contains("omarthegabber"));
+        // the header is still read
+        let profile = Profile::parse_csv_header(output.as_bytes()).unwrap();
+        assert_eq!(profile.name.as_deref(), Some("Mario Rossi"));
+        assert_eq!(profile.email.as_deref(), Some(FAKE_EMAIL));
+        assert_eq!(
+            profile.account_opened_at,
+            Some(chrono::NaiveDate::from_ymd_opt(2022, 3, 1).unwrap())
+        );
+        // the trades are the same, but the IDs
+        let anonymized = AsyncBitpandaTradeParser::parse(output.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(anonymized.len(), original.len());
+        for (original, anonymized) in original.iter().zip(anonymized.iter()) {
+            assert_ne!(original.transaction_id(), anonymized.transaction_id());
+            if original
+                .transaction_id()
+                .starts_with(|c: char| c.is_ascii_uppercase())
+            {
+                assert_eq!(
+                    original.transaction_id().chars().next(),
+                    anonymized.transaction_id().chars().next()
+                );
+            }
+            assert_eq!(original.timestamp(), anonymized.timestamp());
+            assert_eq!(original.transaction_type(), anonymized.transaction_type());
+            assert_eq!(original.asset(), anonymized.asset());
+            assert_eq!(original.amount_fiat(), anonymized.amount_fiat());
+            assert_eq!(original.amount_asset(), anonymized.amount_asset());
+        }
+        // hashing is deterministic
+        assert_eq!(anonymize(None).await.1, output);
+    }
+
+    #[tokio::test]
+    async fn should_scale_amounts() {
+        crate::mock::log();
+        let original = AsyncBitpandaTradeParser::parse(
+            std::fs::read("./test/bitpanda.csv").unwrap().as_slice(),
+        )
+        .await
+        .unwrap();
+        let (_, output) = anonymize(Some(dec!(2))).await;
+        let anonymized = AsyncBitpandaTradeParser::parse(output.as_bytes())
+            .await
+            .unwrap();
+        for (original, anonymized) in original.iter().zip(anonymized.iter()) {
+            assert_eq!(original.amount_fiat() * dec!(2), anonymized.amount_fiat());
+            assert_eq!(
+                original.amount_asset().map(|x| x * dec!(2)),
+                anonymized.amount_asset()
+            );
+            assert_eq!(
+                original.asset_market_price(),
+                anonymized.asset_market_price()
+            );
+        }
+    }
+}
@@ -2,10 +2,22 @@
 //!
 //! This module exposes databases
 
+mod anonymizer;
+mod profile;
 mod quote;
+#[cfg(feature = "sqlite")]
+mod store;
 mod trade;
 mod wallet;
 
+pub use anonymizer::{AnonymizeReport, Anonymizer};
+pub use profile::Profile;
 pub use quote::QuoteDatabase;
-pub use trade::{TradeDatabase, TradeQuery, TradeSet};
+#[cfg(feature = "sqlite")]
+pub use store::Store;
+pub use trade::{
+    asset_name, parse_asset, AdjustmentKind, Adjustments, AdjustmentsReport, BitpandaApi,
+    CsvMapping, HypotheticalTrade, HypotheticalTrades, ImportSource, IsinSymbols, MappingReport,
+    MergeReport, TradeDatabase, TradeMerge, TradeOrder, TradeQuery, TradeSet, TradeSummary, Venue,
+};
 pub use wallet::WalletDatabase;
@@ -0,0 +1,209 @@
+//! # Profile
+//!
+//! This module exposes the profile of the taxpayer and of the Bitpanda account, taken from the lines which precede
+//! the column headers of the Bitpanda CSV and from the data supplied by the user
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Prefix of the line with the opening date of the account
+const ACCOUNT_OPENED_AT: &str = "Account opened at:";
+/// Beginning of the column headers line
+const COLUMN_HEADERS: &str = "Transaction ID";
+
+/// The taxpayer and the Bitpanda account.
+///
+/// The user-supplied data is loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "codice_fiscale": "GBBMRO98S02F205X",
+///     "name": "Omar Gabberone"
+/// }
+/// ```
+///
+/// where each field is optional and overrides the value read from the CSV.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub birth_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub codice_fiscale: Option<String>,
+    /// Opening date of the Bitpanda account; no asset is held at Bitpanda before this date
+    #[serde(default)]
+    pub account_opened_at: Option<NaiveDate>,
+}
+
+impl Profile {
+    /// Load the user-supplied profile from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading profile from {}", path.display());
+        let file = File::open(path)?;
+        let profile: Self = serde_json::from_reader(file)?;
+        info!("loaded profile {:?}", profile.redacted());
+        Ok(profile)
+    }
+
+    /// Read the profile from the lines preceding the column headers of the Bitpanda CSV at `path`
+    pub fn read_csv_header(path: &Path) -> anyhow::Result<Self> {
+        debug!("reading profile from {}", path.display());
+        Self::parse_csv_header(BufReader::new(File::open(path)?))
+    }
+
+    /// Fill the fields which are missing with the ones of `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            name: self.name.or(other.name),
+            birth_date: self.birth_date.or(other.birth_date),
+            email: self.email.or(other.email),
+            codice_fiscale: self.codice_fiscale.or(other.codice_fiscale),
+            account_opened_at: self.account_opened_at.or(other.account_opened_at),
+        }
+    }
+
+    /// Returns whether no field of the profile is known
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Parse the lines before the column headers, which are like:
+    ///
+    /// ```txt
+    /// "Disclaimer: All data is without guarantee, errors and changes are reserved."
+    /// "Omar Gabberone, 1998-11-02"
+    /// omarthegabber@gmail.com
+    /// "Account opened at: 3/1/22, 4:46 PM"
+    /// ```
+    pub fn parse_csv_header(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut profile = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim().trim_matches('"').trim();
+            if line.starts_with(COLUMN_HEADERS) {
+                break;
+            }
+            if let Some(opened_at) = line.strip_prefix(ACCOUNT_OPENED_AT) {
+                profile.account_opened_at = Self::parse_opening_date(opened_at.trim());
+                if profile.account_opened_at.is_none() {
+                    warn!("could not parse the account opening date '{}'", opened_at);
+                }
+            } else if line.contains('@') && !line.contains(char::is_whitespace) {
+                profile.email = Some(line.to_string());
+            } else if let Some((name, birth_date)) = line.rsplit_once(',') {
+                if let Ok(birth_date) = NaiveDate::parse_from_str(birth_date.trim(), "%Y-%m-%d") {
+                    profile.name = Some(name.trim().to_string());
+                    profile.birth_date = Some(birth_date);
+                }
+            }
+        }
+        debug!("profile from CSV: {:?}", profile.redacted());
+        Ok(profile)
+    }
+
+    /// Returns the profile with the personal data masked, so that it can be logged
+    fn redacted(&self) -> Self {
+        let mask = |value: &Option<String>| value.as_ref().map(|x| redact(x));
+        Self {
+            name: mask(&self.name),
+            birth_date: None,
+            email: mask(&self.email),
+            codice_fiscale: mask(&self.codice_fiscale),
+            account_opened_at: self.account_opened_at,
+        }
+    }
+
+    /// Parse the opening date of the account, which is written as `M/D/YY, h:mm AM`
+    fn parse_opening_date(value: &str) -> Option<NaiveDate> {
+        NaiveDateTime::parse_from_str(value, "%m/%d/%y, %I:%M %p")
+            .map(|x| x.date())
+            .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+            .ok()
+    }
+}
+
+/// Mask `value`, keeping only its first character (e.g. `Omar` => `O***`)
+fn redact(value: &str) -> String {
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if i == 0 || !c.is_alphanumeric() {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_read_profile_from_csv() {
+        crate::mock::log();
+        let profile = Profile::read_csv_header(&PathBuf::from("./test/bitpanda.csv")).unwrap();
+        assert_eq!(profile.name.as_deref(), Some("Omar Gabberone"));
+        assert_eq!(
+            profile.birth_date,
+            Some(NaiveDate::from_ymd_opt(1998, 11, 2).unwrap())
+        );
+        assert_eq!(profile.email.as_deref(), Some("omarthegabber@gmail.com"));
+        assert_eq!(
+            profile.account_opened_at,
+            Some(NaiveDate::from_ymd_opt(2022, 3, 1).unwrap())
+        );
+        assert!(profile.codice_fiscale.is_none());
+    }
+
+    #[test]
+    fn should_override_csv_profile_with_user_profile() {
+        crate::mock::log();
+        let csv = Profile::parse_csv_header(
+            "\"Mario Rossi, 1980-01-31\"\n\"Account opened at: 12/24/21, 10:05 AM\"\n\"Transaction ID\",Timestamp\n\"Luigi Verdi, 1990-01-01\"\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(csv.name.as_deref(), Some("Mario Rossi"));
+        assert_eq!(
+            csv.account_opened_at,
+            Some(NaiveDate::from_ymd_opt(2021, 12, 24).unwrap())
+        );
+        let user: Profile = serde_json::from_str(
+            r#"{ "codice_fiscale": "RSSMRA80A31H501U", "account_opened_at": "2022-01-10" }"#,
+        )
+        .unwrap();
+        let profile = user.or(csv);
+        assert_eq!(profile.name.as_deref(), Some("Mario Rossi"));
+        assert_eq!(profile.codice_fiscale.as_deref(), Some("RSSMRA80A31H501U"));
+        assert_eq!(
+            profile.account_opened_at,
+            Some(NaiveDate::from_ymd_opt(2022, 1, 10).unwrap())
+        );
+        assert!(!profile.is_empty());
+        assert!(Profile::default().is_empty());
+    }
+
+    #[test]
+    fn should_redact_profile() {
+        crate::mock::log();
+        let profile = Profile::read_csv_header(&PathBuf::from("./test/bitpanda.csv"))
+            .unwrap()
+            .redacted();
+        assert_eq!(profile.name.as_deref(), Some("O*** *********"));
+        assert_eq!(profile.email.as_deref(), Some("o************@*****.***"));
+        assert!(profile.birth_date.is_none());
+        assert!(profile.account_opened_at.is_some());
+    }
+}
@@ -3,44 +3,121 @@
 //! This module exposes the database for quotes based on assets
 
 use bitpanda_csv::AssetClass;
-use chrono::{DateTime, FixedOffset, Utc};
+use chrono::{DateTime, FixedOffset};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use crate::database::{TradeDatabase, TradeQuery};
-use crate::finance::{BitpandaClient, YahooFinanceClient};
-use bitpanda_csv::Asset;
+use crate::finance::{BitpandaClient, Quotes, YahooFinanceClient};
+use bitpanda_csv::{Asset, Currency};
 
 mod symbols;
 use symbols::YahooFinanceSymbols;
 
 /// The quote database stores the asset quotations for all the symbols provided
 pub struct QuoteDatabase {
+    /// Price of the assets at the end of the time range
     quotes: HashMap<Asset, Decimal>,
+    /// Quotations of the assets along the time range; shared by the databases returned by `at`
+    history: Arc<HashMap<Asset, Quotes>>,
 }
 
 impl QuoteDatabase {
     /// Load quote database
     pub async fn load(
         trades: &TradeDatabase,
+        held: &[Asset],
         from: DateTime<FixedOffset>,
         to: DateTime<FixedOffset>,
     ) -> anyhow::Result<Self> {
-        let assets = trades
-            .select(TradeQuery::default().after(from).before(to))
-            .collect_assets();
+        let assets = Self::assets(trades, held, from, to);
         let from = DateTime::from(from);
-        let to = DateTime::from(to);
-        let yahoo_finance = YahooFinanceClient::new(from, to).await?;
-        let bitpanda = BitpandaClient::init(from, to).await?;
+        let to_utc = DateTime::from(to);
+        let yahoo_finance = YahooFinanceClient::new(from, to_utc).await?;
+        let bitpanda = BitpandaClient::init(from, to_utc).await?;
         debug!("collected {} assets from trades", assets.len());
-        let mut quotes = HashMap::with_capacity(assets.len());
+        let mut history = HashMap::with_capacity(assets.len());
         debug!("sorting assets by exchange...");
         let assets = AssetsSortedByExchange::from(assets);
         // get prices
-        Self::assets_price_from_bitpanda(&mut quotes, &bitpanda, &assets.bitpanda, to).await?;
-        Self::assets_price_from_yahoo(&mut quotes, &yahoo_finance, &assets.yahoo, to).await?;
-        Ok(Self { quotes })
+        Self::assets_price_from_bitpanda(&mut history, &bitpanda, &assets.bitpanda).await?;
+        Self::assets_price_from_yahoo(&mut history, &yahoo_finance, &assets.yahoo).await?;
+        Ok(Self::from_history(history, to))
+    }
+
+    /// Instantiate the database from the quotations of the assets, with the prices at `to`
+    pub fn from_history(history: HashMap<Asset, Quotes>, to: DateTime<FixedOffset>) -> Self {
+        let quotes = history
+            .iter()
+            .filter(|(_, quotation)| !quotation.is_empty())
+            .map(|(asset, quotation)| {
+                let price = quotation.price_at(DateTime::from(to));
+                debug!("price of {} at {}: {}", asset, to, price);
+                (asset.clone(), price)
+            })
+            .collect();
+        Self {
+            quotes,
+            history: Arc::new(history),
+        }
+    }
+
+    /// Returns the assets traded between `from` and `to` and the `held` ones (e.g. the lots of the opening inventory),
+    /// whose quotations are loaded
+    pub fn assets(
+        trades: &TradeDatabase,
+        held: &[Asset],
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = trades
+            .select(TradeQuery::default().after(from).before(to))
+            .collect_assets()
+            .into_iter()
+            .map(|(asset, _)| asset)
+            .collect();
+        for asset in held.iter() {
+            if !assets.contains(asset) {
+                assets.push(asset.clone());
+            }
+        }
+        assets
+    }
+
+    /// Returns the quotations of the assets along the time range
+    #[cfg(feature = "sqlite")]
+    pub fn history(&self) -> &HashMap<Asset, Quotes> {
+        &self.history
+    }
+
+    /// Returns a database with the prices of the assets at `date`, sharing the quotations with `self`.
+    /// Assets without any quotation are not priced
+    pub fn at(&self, date: DateTime<FixedOffset>) -> Self {
+        let quotes = self
+            .history
+            .keys()
+            .filter_map(|asset| {
+                self.price_at(asset, date)
+                    .map(|price| (asset.clone(), price))
+            })
+            .collect();
+        Self {
+            quotes,
+            history: self.history.clone(),
+        }
+    }
+
+    /// Returns whether the quotations of `asset` can be looked up.
+    /// The assets quoted through Yahoo Finance must have a symbol mapped to them, while the ones quoted through Bitpanda
+    /// must be listed there: currencies and metals always are, the other assets only if they are in `bitpanda_assets`
+    /// (e.g. the assets traded on Bitpanda)
+    pub fn is_quotable(asset: &Asset, class: AssetClass, bitpanda_assets: &HashSet<Asset>) -> bool {
+        match (class, asset) {
+            (AssetClass::Fiat, _) => YahooFinanceSymbols::is_mapped(asset),
+            (_, Asset::Currency(_) | Asset::Metal(_)) => true,
+            (_, asset) => bitpanda_assets.contains(asset),
+        }
     }
 
     /// Get price for asset
@@ -48,52 +125,67 @@ impl QuoteDatabase {
         self.quotes.get(asset).cloned()
     }
 
+    /// Get price for asset at the provided date.
+    /// Returns `None` if there are no quotations for the asset
+    pub fn price_at(&self, asset: &Asset, date: DateTime<FixedOffset>) -> Option<Decimal> {
+        self.history
+            .get(asset)
+            .filter(|quotation| !quotation.is_empty())
+            .map(|quotation| quotation.price_at(DateTime::from(date)))
+    }
+
+    /// Get price for asset at the provided date, only if it is covered by the quotations loaded.
+    /// Returns `None` if there are no quotations for the asset or `date` precedes all of them
+    pub fn quoted_price_at(&self, asset: &Asset, date: DateTime<FixedOffset>) -> Option<Decimal> {
+        self.history
+            .get(asset)
+            .and_then(|quotation| quotation.quoted_price_at(DateTime::from(date)))
+    }
+
     // -- loaders
 
     async fn assets_price_from_yahoo(
-        quotes: &mut HashMap<Asset, Decimal>,
+        history: &mut HashMap<Asset, Quotes>,
         yahoo_finance: &YahooFinanceClient,
         assets: &[Asset],
-        price_at: DateTime<Utc>,
     ) -> anyhow::Result<()> {
         debug!("getting assets price from Yahoo");
         for asset in assets.iter() {
             let symbol = YahooFinanceSymbols::lookup(asset);
             debug!("got symbol {} for {}", symbol, asset);
             let quotation = yahoo_finance.get_symbol_quotes(&symbol).await?;
-            let price = quotation.price_at(price_at);
-            debug!(
-                "got quotation for {}; price at {}: {}",
-                symbol, price_at, price
-            );
-            quotes.insert(asset.clone(), price);
+            debug!("got quotation for {}", symbol);
+            history.insert(asset.clone(), quotation);
         }
 
         Ok(())
     }
 
     async fn assets_price_from_bitpanda(
-        quotes: &mut HashMap<Asset, Decimal>,
+        history: &mut HashMap<Asset, Quotes>,
         bitpanda: &BitpandaClient,
         assets: &[Asset],
-        price_at: DateTime<Utc>,
     ) -> anyhow::Result<()> {
         debug!("getting asset price from Bitpanda");
         let quotations = bitpanda.get_symbols_quotes(assets).await?;
         for (asset, quotation) in quotations.into_iter() {
-            let price = quotation.price_at(price_at);
-            debug!(
-                "got quotation for {}; price at {}: {}",
-                asset.to_string(),
-                price_at,
-                price
-            );
-            quotes.insert(asset, price);
+            debug!("got quotation for {}", asset);
+            history.insert(asset, quotation);
         }
         Ok(())
     }
 }
 
+#[cfg(test)]
+impl From<HashMap<Asset, Decimal>> for QuoteDatabase {
+    fn from(quotes: HashMap<Asset, Decimal>) -> Self {
+        Self {
+            quotes,
+            history: Arc::new(HashMap::new()),
+        }
+    }
+}
+
 /// A struct which contains the assets sorted by the exchange to query to get prices
 #[derive(Default)]
 struct AssetsSortedByExchange {
@@ -101,16 +193,16 @@ struct AssetsSortedByExchange {
     yahoo: Vec<Asset>,
 }
 
-impl From<Vec<(Asset, AssetClass)>> for AssetsSortedByExchange {
-    fn from(assets: Vec<(Asset, AssetClass)>) -> Self {
+impl From<Vec<Asset>> for AssetsSortedByExchange {
+    fn from(assets: Vec<Asset>) -> Self {
         let mut sorted_assets = Self::default();
-        for (asset, class) in assets.into_iter() {
-            match (asset, class) {
+        for asset in assets.into_iter() {
+            match asset {
                 // yahoo is just for fiat
-                (asset, AssetClass::Fiat) => {
+                Asset::Currency(Currency::Fiat(_)) => {
                     sorted_assets.yahoo.push(asset);
                 }
-                (asset, _) => {
+                asset => {
                     sorted_assets.bitpanda.push(asset);
                 }
             }
@@ -123,6 +215,7 @@ impl From<Vec<(Asset, AssetClass)>> for AssetsSortedByExchange {
 mod test {
 
     use super::*;
+    use crate::finance::Quote;
     use crate::mock::database::DatabaseTradeMock;
 
     use chrono::prelude::*;
@@ -133,18 +226,41 @@ mod test {
         crate::mock::log();
         let trades = DatabaseTradeMock::mock();
         assert!(
-            QuoteDatabase::load(&trades, date(2022, 1, 1), date(2022, 12, 31))
+            QuoteDatabase::load(&trades, &[], date(2022, 1, 1), date(2022, 12, 31))
                 .await
                 .is_ok()
         );
     }
 
+    #[test]
+    fn should_collect_traded_and_held_assets() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::mock();
+        let (from, to) = (date(2022, 1, 1), date(2022, 12, 31));
+        let traded = QuoteDatabase::assets(&trades, &[], from, to);
+        let amazon = Asset::Ticker(String::from("AMZN"));
+        let nvidia = Asset::Ticker(String::from("NVDA"));
+        assert!(traded.contains(&amazon));
+        let assets = QuoteDatabase::assets(&trades, &[amazon, nvidia.clone()], from, to);
+        assert_eq!(assets.len(), traded.len() + 1);
+        assert!(assets.contains(&nvidia));
+        let sorted = AssetsSortedByExchange::from(assets);
+        assert!(sorted.bitpanda.contains(&nvidia));
+        assert!(sorted
+            .yahoo
+            .iter()
+            .all(|x| matches!(x, Asset::Currency(Currency::Fiat(_)))));
+    }
+
     #[test]
     fn should_get_price() {
         crate::mock::log();
         let mut quotes = HashMap::new();
         quotes.insert(Asset::Ticker(String::from("AMZN")), dec!(124.08));
-        let db = QuoteDatabase { quotes };
+        let db = QuoteDatabase {
+            quotes,
+            history: Arc::new(HashMap::new()),
+        };
         assert_eq!(
             db.price(&Asset::Ticker(String::from("AMZN"))).unwrap(),
             dec!(124.08)
@@ -152,6 +268,42 @@ mod test {
         assert!(db.price(&Asset::Ticker(String::from("ADBE"))).is_none());
     }
 
+    #[test]
+    fn should_get_price_at_date() {
+        crate::mock::log();
+        let mut history = HashMap::new();
+        history.insert(
+            Asset::Ticker(String::from("AMZN")),
+            Quotes::from(vec![
+                Quote::eur(DateTime::from(date(2022, 3, 1)), dec!(140.0)),
+                Quote::eur(DateTime::from(date(2022, 6, 1)), dec!(110.0)),
+            ]),
+        );
+        let db = QuoteDatabase {
+            quotes: HashMap::new(),
+            history: Arc::new(history),
+        };
+        assert_eq!(
+            db.price_at(&Asset::Ticker(String::from("AMZN")), date(2022, 4, 1))
+                .unwrap(),
+            dec!(140.0)
+        );
+        assert_eq!(
+            db.price_at(&Asset::Ticker(String::from("AMZN")), date(2022, 7, 1))
+                .unwrap(),
+            dec!(110.0)
+        );
+        assert!(db
+            .price_at(&Asset::Ticker(String::from("ADBE")), date(2022, 7, 1))
+            .is_none());
+        // database at date
+        assert_eq!(
+            db.at(date(2022, 4, 1))
+                .price(&Asset::Ticker(String::from("AMZN"))),
+            Some(dec!(140.0))
+        );
+    }
+
     fn date(year: i32, month: u32, day: u32) -> DateTime<FixedOffset> {
         FixedOffset::west_opt(3600)
             .unwrap()
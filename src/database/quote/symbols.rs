@@ -8,6 +8,12 @@ use bitpanda_csv::{Asset, CryptoCurrency, Currency, Fiat};
 pub struct YahooFinanceSymbols;
 
 impl YahooFinanceSymbols {
+    /// Returns whether there is a symbol mapped to the asset.
+    /// Tickers, metals and Hong Kong stocks fall back to a symbol derived from their name
+    pub fn is_mapped(asset: &Asset) -> bool {
+        matches!(asset, Asset::Currency(_))
+    }
+
     /// Get yahoo finance name for an asset
     pub fn lookup(asset: &Asset) -> String {
         match asset {
@@ -259,4 +265,14 @@ mod test {
             "1177.HK"
         );
     }
+
+    #[test]
+    fn should_tell_whether_asset_is_mapped() {
+        assert!(YahooFinanceSymbols::is_mapped(&Asset::Currency(
+            Currency::Fiat(Fiat::Usd)
+        )));
+        assert!(!YahooFinanceSymbols::is_mapped(&Asset::Ticker(
+            String::from("AMZN")
+        )));
+    }
 }
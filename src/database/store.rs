@@ -0,0 +1,454 @@
+//! # Store
+//!
+//! This module exposes the local SQLite database which keeps the imported trades, the quotations and the yearly
+//! reports between runs
+
+use bitpanda_csv::{Asset, Trade};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::trade::{asset_name, parse_csv_row, TradeBuilder};
+use super::{QuoteDatabase, TradeDatabase, Venue};
+use crate::finance::{Quote, Quotes};
+use crate::module730::YearSummary;
+
+/// Directory of the application, under the data directory of the user
+const APP_DIR: &str = "bitpanda730";
+/// Name of the database file
+const DATABASE_FILE: &str = "bitpanda730.sqlite";
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS trades (
+    venue TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    record TEXT NOT NULL,
+    imported_at TEXT NOT NULL,
+    PRIMARY KEY (venue, transaction_id)
+);
+CREATE INDEX IF NOT EXISTS trades_timestamp ON trades (timestamp);
+CREATE TABLE IF NOT EXISTS quotes (
+    asset TEXT NOT NULL,
+    date TEXT NOT NULL,
+    price TEXT NOT NULL,
+    PRIMARY KEY (asset, date)
+);
+CREATE TABLE IF NOT EXISTS quote_ranges (
+    asset TEXT NOT NULL,
+    since TEXT NOT NULL,
+    until TEXT NOT NULL,
+    PRIMARY KEY (asset, since, until)
+);
+CREATE TABLE IF NOT EXISTS reports (
+    year INTEGER PRIMARY KEY NOT NULL,
+    gains TEXT NOT NULL,
+    losses TEXT NOT NULL,
+    capital_gains_tax TEXT NOT NULL,
+    ivafe TEXT NOT NULL,
+    carried_losses TEXT NOT NULL,
+    computed_at TEXT NOT NULL
+);
+"#;
+
+/// The local store, an embedded SQLite database which works fully offline.
+///
+/// - trades are kept as rows of the Bitpanda CSV, along with their venue, and deduplicated by venue and transaction
+///   ID, so that the same exports can be
+///   imported every year and only the new trades are added;
+/// - the quotations are kept along with the time ranges they have been loaded for, so that they are fetched only once;
+/// - the summary of the taxes of each year is kept, so that the history can be compared across years.
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    /// Open the store at `path`, creating it if it doesn't exist
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        debug!("opening store at {}", path.display());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::setup(Connection::open(path)?)
+    }
+
+    /// Returns the default path of the store, under the data directory of the user
+    /// (e.g. `~/.local/share/bitpanda730/bitpanda730.sqlite` on Linux)
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        match dirs::data_dir() {
+            Some(dir) => Ok(dir.join(APP_DIR).join(DATABASE_FILE)),
+            None => anyhow::bail!("could not find the data directory of the user"),
+        }
+    }
+
+    fn setup(connection: Connection) -> anyhow::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    // -- trades
+
+    /// Insert the `trades` which are not stored yet. Returns the amount of trades inserted
+    pub fn insert_trades(&self, trades: &[(Venue, Trade)]) -> anyhow::Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        let imported_at = timestamp(Utc::now());
+        let mut inserted = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO trades (venue, transaction_id, timestamp, asset, record, imported_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (venue, trade) in trades.iter() {
+                inserted += stmt.execute(params![
+                    venue.to_string(),
+                    trade.transaction_id(),
+                    timestamp(trade.timestamp()),
+                    asset_name(&trade.asset()),
+                    TradeBuilder::from(trade).csv_row()?,
+                    imported_at,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        info!("stored {} new trades out of {}", inserted, trades.len());
+        Ok(inserted)
+    }
+
+    /// Returns all the trades stored, with their venue, sorted by timestamp
+    pub fn trades(&self) -> anyhow::Result<Vec<(Venue, Trade)>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT venue, transaction_id, record FROM trades ORDER BY timestamp, rowid",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut trades = Vec::new();
+        for row in rows {
+            let (venue, transaction_id, record) = row?;
+            match parse_csv_row(&record)? {
+                Some(trade) => trades.push((Venue::from_str(&venue)?, trade)),
+                None => anyhow::bail!("invalid stored trade {}", transaction_id),
+            }
+        }
+        debug!("loaded {} trades from store", trades.len());
+        Ok(trades)
+    }
+
+    // -- quotes
+
+    /// Load the quotes of the assets traded between `from` and `to` and of the `held` ones.
+    /// If the quotations of all the assets have already been loaded for the time range, the stored ones are used,
+    /// otherwise they are fetched and stored
+    pub async fn load_quotes(
+        &self,
+        trades: &TradeDatabase,
+        held: &[Asset],
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> anyhow::Result<QuoteDatabase> {
+        let assets = QuoteDatabase::assets(trades, held, from, to);
+        if let Some(history) = self.stored_quotes(&assets, from, to)? {
+            info!("using the stored quotes from {} to {}", from, to);
+            return Ok(QuoteDatabase::from_history(history, to));
+        }
+        let quotes = QuoteDatabase::load(trades, held, from, to).await?;
+        self.insert_quotes(&quotes, &assets, from, to)?;
+        Ok(quotes)
+    }
+
+    /// Returns the stored quotations of `assets`, if all of them have been loaded for the time range `from` => `to`
+    fn stored_quotes(
+        &self,
+        assets: &[Asset],
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Option<HashMap<Asset, Quotes>>> {
+        let mut covered = self.connection.prepare(
+            "SELECT COUNT(*) FROM quote_ranges WHERE asset = ?1 AND since <= ?2 AND until >= ?3",
+        )?;
+        let mut quotes = self
+            .connection
+            .prepare("SELECT date, price FROM quotes WHERE asset = ?1 ORDER BY date")?;
+        let mut history = HashMap::with_capacity(assets.len());
+        for asset in assets.iter() {
+            let name = asset_name(asset);
+            let ranges: usize = covered
+                .query_row(params![name, timestamp(from), timestamp(to)], |row| {
+                    row.get(0)
+                })?;
+            if ranges == 0 {
+                debug!("quotes of {} from {} to {} are not stored", asset, from, to);
+                return Ok(None);
+            }
+            let rows = quotes.query_map(params![name], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut quotation = Vec::new();
+            for row in rows {
+                let (date, price) = row?;
+                quotation.push(Quote::eur(
+                    DateTime::parse_from_rfc3339(&date)?.with_timezone(&Utc),
+                    Decimal::from_str(&price)?,
+                ));
+            }
+            history.insert(asset.clone(), Quotes::from(quotation));
+        }
+        Ok(Some(history))
+    }
+
+    /// Store the quotations of `quotes`, which have been loaded for `assets` in the time range `from` => `to`.
+    /// The range is recorded for each asset requested, even if no quotation has been found for it
+    fn insert_quotes(
+        &self,
+        quotes: &QuoteDatabase,
+        assets: &[Asset],
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> anyhow::Result<()> {
+        let tx = self.connection.unchecked_transaction()?;
+        {
+            let mut insert_quote = tx.prepare(
+                "INSERT OR REPLACE INTO quotes (asset, date, price) VALUES (?1, ?2, ?3)",
+            )?;
+            let mut insert_range = tx.prepare(
+                "INSERT OR IGNORE INTO quote_ranges (asset, since, until) VALUES (?1, ?2, ?3)",
+            )?;
+            for (asset, quotation) in quotes.history().iter() {
+                let name = asset_name(asset);
+                for quote in quotation.iter() {
+                    insert_quote.execute(params![
+                        name,
+                        timestamp(quote.date),
+                        quote.price.to_string()
+                    ])?;
+                }
+            }
+            for asset in assets.iter() {
+                insert_range.execute(params![asset_name(asset), timestamp(from), timestamp(to)])?;
+            }
+        }
+        tx.commit()?;
+        debug!("stored quotes of {} assets", assets.len());
+        Ok(())
+    }
+
+    // -- reports
+
+    /// Store the summary of the taxes of a year, replacing the one previously computed
+    pub fn insert_report(&self, summary: &YearSummary) -> anyhow::Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO reports (year, gains, losses, capital_gains_tax, ivafe, carried_losses, computed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                summary.year,
+                summary.gains.to_string(),
+                summary.losses.to_string(),
+                summary.capital_gains_tax.to_string(),
+                summary.ivafe.to_string(),
+                summary.carried_losses.to_string(),
+                timestamp(Utc::now()),
+            ],
+        )?;
+        debug!("stored report of {}", summary.year);
+        Ok(())
+    }
+
+    /// Returns the summaries of the taxes stored, sorted by year
+    pub fn reports(&self) -> anyhow::Result<Vec<YearSummary>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT year, gains, losses, capital_gains_tax, ivafe, carried_losses FROM reports ORDER BY year",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                [
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ],
+            ))
+        })?;
+        let mut reports = Vec::new();
+        for row in rows {
+            let (year, [gains, losses, capital_gains_tax, ivafe, carried_losses]) = row?;
+            reports.push(YearSummary {
+                year,
+                gains: Decimal::from_str(&gains)?,
+                losses: Decimal::from_str(&losses)?,
+                capital_gains_tax: Decimal::from_str(&capital_gains_tax)?,
+                ivafe: Decimal::from_str(&ivafe)?,
+                carried_losses: Decimal::from_str(&carried_losses)?,
+            });
+        }
+        Ok(reports)
+    }
+}
+
+/// Format `date` in UTC with a fixed width, so that the stored timestamps are sorted as strings
+fn timestamp<Tz: chrono::TimeZone>(date: DateTime<Tz>) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+impl Store {
+    /// Open a store in memory
+    fn memory() -> Self {
+        Self::setup(Connection::open_in_memory().unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use chrono::prelude::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_store_trades_once() {
+        crate::mock::log();
+        let store = Store::memory();
+        let trades = mock_trades();
+        let (first, second) = trades.split_at(trades.len() / 2);
+        assert_eq!(store.insert_trades(first).unwrap(), first.len());
+        // the second import contains the trades already stored
+        assert_eq!(store.insert_trades(&trades).unwrap(), second.len());
+        assert_eq!(store.insert_trades(&trades).unwrap(), 0);
+        // the same transaction ID on another venue is another trade
+        let mut trades = trades.clone();
+        trades.push((Venue::Kraken, trades[0].1.clone()));
+        assert_eq!(store.insert_trades(&trades).unwrap(), 1);
+        let stored = store.trades().unwrap();
+        assert_eq!(stored.len(), trades.len());
+        let mut expected = trades.clone();
+        expected.sort_by_key(|(_, x)| x.timestamp());
+        for ((stored_venue, stored), (venue, trade)) in stored.iter().zip(expected.iter()) {
+            assert_eq!(stored_venue, venue);
+            assert_eq!(stored.transaction_id(), trade.transaction_id());
+            assert_eq!(stored.timestamp(), trade.timestamp());
+            assert_eq!(stored.transaction_type(), trade.transaction_type());
+            assert_eq!(stored.asset(), trade.asset());
+            assert_eq!(stored.amount_fiat(), trade.amount_fiat());
+            assert_eq!(stored.amount_asset(), trade.amount_asset());
+            assert_eq!(stored.fee(), trade.fee());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reuse_stored_quotes() {
+        crate::mock::log();
+        let store = Store::memory();
+        let trades = DatabaseTradeMock::mock();
+        let (from, to) = (date(2022, 1, 1), date(2022, 12, 31));
+        let assets = traded_assets(&trades, from, to);
+        assert!(!assets.is_empty());
+        // nothing stored yet
+        assert!(store.stored_quotes(&assets, from, to).unwrap().is_none());
+        let history = assets
+            .iter()
+            .map(|asset| {
+                (
+                    asset.clone(),
+                    Quotes::from(vec![
+                        Quote::eur(DateTime::from(date(2022, 3, 1)), dec!(10.5)),
+                        Quote::eur(DateTime::from(date(2022, 9, 1)), dec!(12.25)),
+                    ]),
+                )
+            })
+            .collect();
+        store
+            .insert_quotes(&QuoteDatabase::from_history(history, to), &assets, from, to)
+            .unwrap();
+        // quotes are not fetched
+        let quotes = store.load_quotes(&trades, &[], from, to).await.unwrap();
+        let asset = &assets[0];
+        assert_eq!(quotes.price(asset), Some(dec!(12.25)));
+        assert_eq!(quotes.price_at(asset, date(2022, 4, 1)), Some(dec!(10.5)));
+        // a wider time range is not covered
+        assert!(store
+            .stored_quotes(std::slice::from_ref(asset), date(2021, 1, 1), to)
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reuse_stored_quotes_of_asset_without_quotation() {
+        crate::mock::log();
+        let store = Store::memory();
+        let trades = DatabaseTradeMock::mock();
+        let (from, to) = (date(2022, 1, 1), date(2022, 12, 31));
+        let assets = traded_assets(&trades, from, to);
+        assert!(assets.len() > 1);
+        // only the first asset has been quoted
+        let history = HashMap::from([(
+            assets[0].clone(),
+            Quotes::from(vec![Quote::eur(
+                DateTime::from(date(2022, 3, 1)),
+                dec!(10.5),
+            )]),
+        )]);
+        store
+            .insert_quotes(&QuoteDatabase::from_history(history, to), &assets, from, to)
+            .unwrap();
+        // quotes are not fetched again for the assets without a quotation
+        let quotes = store.load_quotes(&trades, &[], from, to).await.unwrap();
+        assert_eq!(quotes.price(&assets[0]), Some(dec!(10.5)));
+        assert_eq!(quotes.price(&assets[1]), None);
+    }
+
+    #[test]
+    fn should_store_reports() {
+        crate::mock::log();
+        let store = Store::memory();
+        let report = |year: i32, gains: Decimal| YearSummary {
+            year,
+            gains,
+            losses: dec!(-10.0),
+            capital_gains_tax: dec!(26.0),
+            ivafe: dec!(2.0),
+            carried_losses: Decimal::ZERO,
+        };
+        store.insert_report(&report(2023, dec!(100.0))).unwrap();
+        store.insert_report(&report(2022, dec!(50.0))).unwrap();
+        // computed again
+        store.insert_report(&report(2023, dec!(120.0))).unwrap();
+        assert_eq!(
+            store.reports().unwrap(),
+            vec![report(2022, dec!(50.0)), report(2023, dec!(120.0))]
+        );
+    }
+
+    fn traded_assets(
+        trades: &TradeDatabase,
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> Vec<Asset> {
+        QuoteDatabase::assets(trades, &[], from, to)
+    }
+
+    fn mock_trades() -> Vec<(Venue, Trade)> {
+        DatabaseTradeMock::mock()
+            .all()
+            .with_venues()
+            .map(|(venue, trade)| (venue, trade.clone()))
+            .collect()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(year, month, day, 12, 0, 0)
+            .unwrap()
+    }
+}
@@ -2,36 +2,132 @@
 //!
 //! This module defines the trade database
 
-use bitpanda_csv::Trade;
+use bitpanda_csv::{Asset, Trade};
+use chrono::{DateTime, FixedOffset};
+use std::collections::{BTreeMap, HashMap};
 
+mod adjustments;
+mod builder;
+mod hypothetical;
+mod import;
+mod merge;
 mod query;
 mod set;
-pub use query::Query as TradeQuery;
+mod summary;
+mod venue;
+pub use adjustments::{AdjustmentKind, Adjustments, AdjustmentsReport};
+pub use builder::{asset_name, parse_asset};
+#[cfg(feature = "sqlite")]
+pub use builder::{parse_csv_row, TradeBuilder};
+pub use hypothetical::{HypotheticalTrade, HypotheticalTrades};
+pub use import::{BitpandaApi, CsvMapping, ImportSource, IsinSymbols, MappingReport};
+pub use merge::{MergeReport, TradeMerge};
+pub use query::{Order as TradeOrder, Query as TradeQuery};
 pub use set::Set as TradeSet;
+pub use summary::Summary as TradeSummary;
+pub use venue::Venue;
 
 /// The trade database contains all the trades parsed from the CSV
-/// and exposes methods to query the trade datas
+/// and exposes methods to query the trade datas.
+///
+/// Each trade is kept along with the venue where it has been made.
+/// Trades are kept sorted by timestamp and indexed by asset, so that the queries on a time range or on an asset
+/// don't scan the whole database
 #[derive(Debug, Clone)]
 pub struct TradeDatabase {
-    trades: Vec<Trade>,
+    trades: Vec<(Venue, Trade)>,
+    /// Position of the trades of each asset, sorted by timestamp
+    assets: HashMap<Asset, Vec<usize>>,
+}
+
+impl From<Vec<(Venue, Trade)>> for TradeDatabase {
+    fn from(mut trades: Vec<(Venue, Trade)>) -> Self {
+        // NOTE: the sort is stable, so trades at the same timestamp keep their order
+        trades.sort_by_key(|(_, trade)| trade.timestamp());
+        let mut assets: HashMap<Asset, Vec<usize>> = HashMap::new();
+        for (i, (_, trade)) in trades.iter().enumerate() {
+            assets.entry(trade.asset()).or_default().push(i);
+        }
+        Self { trades, assets }
+    }
 }
 
 impl From<Vec<Trade>> for TradeDatabase {
+    /// Instantiate the database from trades made on Bitpanda
     fn from(trades: Vec<Trade>) -> Self {
-        Self { trades }
+        Self::from(
+            trades
+                .into_iter()
+                .map(|trade| (Venue::Bitpanda, trade))
+                .collect::<Vec<_>>(),
+        )
     }
 }
 
 impl TradeDatabase {
     /// select all trades.
     /// Shorthand for `select(TradeQuery::default())`
-    pub fn all(&self) -> TradeSet {
+    pub fn all(&self) -> TradeSet<'_> {
         self.select(TradeQuery::default())
     }
 
-    /// Select only trades which satisfies the query
-    pub fn select(&self, query: TradeQuery) -> TradeSet {
-        query.select(&self.trades)
+    /// Returns a new database with the trades of this database and `trades`, sorted by timestamp
+    pub fn extended(&self, trades: Vec<(Venue, Trade)>) -> Self {
+        let mut all_trades = self.trades.clone();
+        all_trades.extend(trades);
+        Self::from(all_trades)
+    }
+
+    /// Split the database by the venue where the trades have been made
+    pub fn by_venue(&self) -> BTreeMap<Venue, TradeDatabase> {
+        let mut venues: BTreeMap<Venue, Vec<(Venue, Trade)>> = BTreeMap::new();
+        for (venue, trade) in self.trades.iter() {
+            venues
+                .entry(*venue)
+                .or_default()
+                .push((*venue, trade.clone()));
+        }
+        venues
+            .into_iter()
+            .map(|(venue, trades)| (venue, TradeDatabase::from(trades)))
+            .collect()
+    }
+
+    /// Select only trades which satisfies the query.
+    /// Only the trades in the time range of the query and of the asset it requires, if any, are scanned
+    pub fn select(&self, query: TradeQuery) -> TradeSet<'_> {
+        let (after, before) = query.time_range();
+        let start = after
+            .map(|date| self.position(date, false))
+            .unwrap_or_default();
+        let end = before
+            .map(|date| self.position(date, true))
+            .unwrap_or(self.trades.len());
+        if start >= end {
+            return query.select(std::iter::empty());
+        }
+        match query.asset() {
+            Some(asset) => {
+                let positions = self
+                    .assets
+                    .get(asset)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let first = positions.partition_point(|i| *i < start);
+                let last = positions.partition_point(|i| *i < end);
+                let trades = positions[first..last].iter().map(|i| &self.trades[*i]);
+                query.select(trades)
+            }
+            None => query.select(&self.trades[start..end]),
+        }
+    }
+
+    /// Returns the position of the first trade after `date`, or at `date` if not `inclusive`
+    fn position(&self, date: DateTime<FixedOffset>, inclusive: bool) -> usize {
+        self.trades.partition_point(|(_, x)| match inclusive {
+            true => x.timestamp() <= date,
+            false => x.timestamp() < date,
+        })
     }
 }
 
@@ -40,7 +136,7 @@ mod test {
 
     use super::*;
     use crate::mock::database::DatabaseTradeMock;
-    use bitpanda_csv::{Asset, Fiat};
+    use bitpanda_csv::{Asset, AssetClass, CryptoCurrency, Currency, Fiat, InOut, TransactionType};
 
     use pretty_assertions::assert_eq;
 
@@ -51,6 +147,29 @@ mod test {
         assert_eq!(db.all().trades().len(), 15);
     }
 
+    #[test]
+    fn should_split_by_venue() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let trade = builder::TradeBuilder::new(
+            "TQ1",
+            db.all().trades()[0].timestamp(),
+            TransactionType::Buy,
+            InOut::Outgoing,
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc)),
+            AssetClass::Cryptocurrency,
+        )
+        .build()
+        .unwrap();
+        let venues = db.extended(vec![(Venue::Kraken, trade)]).by_venue();
+        assert_eq!(
+            venues.keys().copied().collect::<Vec<Venue>>(),
+            vec![Venue::Bitpanda, Venue::Kraken]
+        );
+        assert_eq!(venues[&Venue::Bitpanda].all().trades().len(), 15);
+        assert_eq!(venues[&Venue::Kraken].all().trades().len(), 1);
+    }
+
     #[test]
     fn should_group_by_asset() {
         crate::mock::log();
@@ -89,7 +208,7 @@ mod test {
         let db = DatabaseTradeMock::mock();
         let date = FixedOffset::east_opt(3600)
             .unwrap()
-            .with_ymd_and_hms(2022, 08, 15, 0, 0, 0)
+            .with_ymd_and_hms(2022, 8, 15, 0, 0, 0)
             .unwrap();
         assert_eq!(
             db.select(TradeQuery::default().before(date))
@@ -97,4 +216,43 @@ mod test {
             dec!(7934.88)
         );
     }
+
+    #[test]
+    fn should_select_through_indices() {
+        crate::mock::log();
+        use chrono::prelude::*;
+        let db = DatabaseTradeMock::mock();
+        let since = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 7, 5, 0, 0, 0)
+            .unwrap();
+        let to = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 9, 1, 0, 0, 0)
+            .unwrap();
+        // same result of a full scan
+        for query in [
+            TradeQuery::default().after(since).before(to),
+            TradeQuery::default().before(to),
+            TradeQuery::default().after(to).before(since),
+            TradeQuery::default().asset_eq(Asset::Ticker(String::from("AMZN"))),
+            TradeQuery::default()
+                .asset_eq(Asset::Currency(Currency::Fiat(Fiat::Eur)))
+                .after(since),
+        ] {
+            let indexed = db
+                .select(query.clone())
+                .trades()
+                .iter()
+                .map(|x| x.transaction_id().to_string())
+                .collect::<Vec<_>>();
+            let scanned = query
+                .select(&db.trades)
+                .trades()
+                .iter()
+                .map(|x| x.transaction_id().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(indexed, scanned);
+        }
+    }
 }
@@ -0,0 +1,392 @@
+//! # Adjustments
+//!
+//! This module exposes the manual adjustments applied on top of the trades parsed from the CSV, to fix mistakes
+//! and ambiguities of the Bitpanda exports without editing them.
+
+use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, InOut, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use super::builder::{parse_asset, TradeBuilder};
+use super::Venue;
+
+/// Manual adjustments to the trades.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "add": [
+///         {
+///             "transaction_id": "ADJ-0001",
+///             "timestamp": "2021-03-10T10:00:00+01:00",
+///             "transaction_type": "buy",
+///             "in_out": "outgoing",
+///             "amount_fiat": 500.0,
+///             "amount_asset": 0.01,
+///             "asset": "BTC",
+///             "asset_class": "Cryptocurrency",
+///             "asset_market_price": 50000.0,
+///             "reason": "buy missing from the export"
+///         }
+///     ],
+///     "drop": [
+///         { "transaction_id": "T2cbcc5dd-67c1-4ded-8020-000000000000", "reason": "duplicated by Bitpanda" }
+///     ],
+///     "override": [
+///         { "transaction_id": "C04e9125e-9688-4fbb-b23b-000000000000", "transaction_type": "transfer", "reason": "gift" },
+///         { "venue": "kraken", "transaction_id": "TQ1AAA-BBBBB-CCCCCC", "asset_class": "Cryptocurrency" }
+///     ]
+/// }
+/// ```
+///
+/// Trades are identified by venue and transaction ID; the venue is Bitpanda if missing.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Adjustments {
+    /// Synthetic trades to add
+    #[serde(default)]
+    add: Vec<AddTrade>,
+    /// Trades to suppress
+    #[serde(default)]
+    drop: Vec<DropTrade>,
+    /// Trades whose transaction type or asset class must be changed
+    #[serde(default, rename = "override")]
+    overrides: Vec<OverrideTrade>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AddTrade {
+    #[serde(default)]
+    venue: Venue,
+    transaction_id: String,
+    timestamp: DateTime<FixedOffset>,
+    transaction_type: TransactionType,
+    in_out: InOut,
+    #[serde(default)]
+    amount_fiat: Decimal,
+    #[serde(default)]
+    amount_asset: Option<Decimal>,
+    /// Asset name as written in the Bitpanda CSV
+    asset: String,
+    asset_class: AssetClass,
+    #[serde(default)]
+    asset_market_price: Option<Decimal>,
+    #[serde(default)]
+    fee: Option<Decimal>,
+    /// Asset name of the fee as written in the Bitpanda CSV; EUR if missing
+    #[serde(default)]
+    fee_asset: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DropTrade {
+    #[serde(default)]
+    venue: Venue,
+    transaction_id: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OverrideTrade {
+    #[serde(default)]
+    venue: Venue,
+    transaction_id: String,
+    #[serde(default)]
+    transaction_type: Option<TransactionType>,
+    #[serde(default)]
+    asset_class: Option<AssetClass>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Report of the adjustments applied to the trades
+#[derive(Debug, Default, Clone)]
+pub struct AdjustmentsReport {
+    pub applied: Vec<AppliedAdjustment>,
+    /// Adjustments referring to a transaction ID which doesn't exist
+    pub unmatched: Vec<AppliedAdjustment>,
+}
+
+/// An adjustment applied to a trade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedAdjustment {
+    pub kind: AdjustmentKind,
+    pub transaction_id: String,
+    /// Description of the change
+    pub change: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AdjustmentKind {
+    Add,
+    Drop,
+    Override,
+}
+
+impl Adjustments {
+    /// Load adjustments from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading adjustments from {}", path.display());
+        let file = File::open(path)?;
+        let adjustments: Self = serde_json::from_reader(file)?;
+        info!(
+            "found {} trades to add, {} to drop and {} to override",
+            adjustments.add.len(),
+            adjustments.drop.len(),
+            adjustments.overrides.len()
+        );
+        Ok(adjustments)
+    }
+
+    /// Returns whether there is no adjustment
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.drop.is_empty() && self.overrides.is_empty()
+    }
+
+    /// Apply the adjustments to `trades`: trades are dropped first, then overridden and finally the synthetic
+    /// trades are added. The returned trades are sorted by timestamp
+    pub fn apply(
+        &self,
+        trades: Vec<(Venue, Trade)>,
+    ) -> anyhow::Result<(Vec<(Venue, Trade)>, AdjustmentsReport)> {
+        let mut report = AdjustmentsReport::default();
+        // drop
+        let mut dropped = HashSet::new();
+        let mut trades: Vec<(Venue, Trade)> =
+            trades
+                .into_iter()
+                .filter(|(venue, trade)| {
+                    match self.drop.iter().position(|x| {
+                        x.venue == *venue && x.transaction_id == trade.transaction_id()
+                    }) {
+                        Some(i) => {
+                            let drop = &self.drop[i];
+                            dropped.insert(i);
+                            report.applied.push(AppliedAdjustment {
+                                kind: AdjustmentKind::Drop,
+                                transaction_id: drop.transaction_id.clone(),
+                                change: format!("{:?} {}", trade.transaction_type(), trade.asset()),
+                                reason: drop.reason.clone(),
+                            });
+                            false
+                        }
+                        None => true,
+                    }
+                })
+                .collect();
+        for (i, drop) in self.drop.iter().enumerate() {
+            if !dropped.contains(&i) {
+                report.unmatched.push(AppliedAdjustment {
+                    kind: AdjustmentKind::Drop,
+                    transaction_id: drop.transaction_id.clone(),
+                    change: String::default(),
+                    reason: drop.reason.clone(),
+                });
+            }
+        }
+        // override
+        for adjustment in self.overrides.iter() {
+            let Some((_, trade)) = trades.iter_mut().find(|(venue, x)| {
+                *venue == adjustment.venue && x.transaction_id() == adjustment.transaction_id
+            }) else {
+                report.unmatched.push(AppliedAdjustment {
+                    kind: AdjustmentKind::Override,
+                    transaction_id: adjustment.transaction_id.clone(),
+                    change: String::default(),
+                    reason: adjustment.reason.clone(),
+                });
+                continue;
+            };
+            let mut changes = Vec::new();
+            let mut builder = TradeBuilder::from(&*trade);
+            if let Some(transaction_type) = adjustment.transaction_type {
+                changes.push(format!(
+                    "{:?} => {:?}",
+                    trade.transaction_type(),
+                    transaction_type
+                ));
+                builder = builder.with_transaction_type(transaction_type);
+            }
+            if let Some(asset_class) = adjustment.asset_class {
+                changes.push(format!("{:?} => {:?}", trade.asset_class(), asset_class));
+                builder = builder.with_asset_class(asset_class);
+            }
+            *trade = builder.build()?;
+            report.applied.push(AppliedAdjustment {
+                kind: AdjustmentKind::Override,
+                transaction_id: adjustment.transaction_id.clone(),
+                change: changes.join(", "),
+                reason: adjustment.reason.clone(),
+            });
+        }
+        // add
+        for adjustment in self.add.iter() {
+            if trades.iter().any(|(venue, x)| {
+                *venue == adjustment.venue && x.transaction_id() == adjustment.transaction_id
+            }) {
+                anyhow::bail!(
+                    "can't add trade {}: transaction ID already exists",
+                    adjustment.transaction_id
+                );
+            }
+            let trade = adjustment.trade()?;
+            report.applied.push(AppliedAdjustment {
+                kind: AdjustmentKind::Add,
+                transaction_id: adjustment.transaction_id.clone(),
+                change: format!(
+                    "{:?} {} {}",
+                    trade.transaction_type(),
+                    trade.amount_asset().unwrap_or_default(),
+                    trade.asset()
+                ),
+                reason: adjustment.reason.clone(),
+            });
+            trades.push((adjustment.venue, trade));
+        }
+        for unmatched in report.unmatched.iter() {
+            warn!(
+                "adjustment {:?} refers to transaction {}, which doesn't exist",
+                unmatched.kind, unmatched.transaction_id
+            );
+        }
+        trades.sort_by_key(|(_, x)| x.timestamp());
+        Ok((trades, report))
+    }
+}
+
+impl AddTrade {
+    /// Build the synthetic trade
+    fn trade(&self) -> anyhow::Result<Trade> {
+        let mut builder = TradeBuilder::new(
+            &self.transaction_id,
+            self.timestamp,
+            self.transaction_type,
+            self.in_out,
+            parse_asset(&self.asset)?,
+            self.asset_class,
+        )
+        .with_amount_fiat(self.amount_fiat);
+        if let Some(amount_asset) = self.amount_asset {
+            builder = builder.with_amount_asset(amount_asset);
+        }
+        if let Some(price) = self.asset_market_price {
+            builder = builder.with_asset_market_price(price);
+        }
+        if let Some(fee) = self.fee {
+            let fee_asset = match self.fee_asset.as_deref() {
+                Some(name) => match parse_asset(name)? {
+                    Asset::Currency(currency) => currency,
+                    asset => anyhow::bail!("invalid fee asset {}", asset),
+                },
+                None => Currency::Fiat(Fiat::Eur),
+            };
+            builder = builder.with_fee(fee, fee_asset);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::CryptoCurrency;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_apply_adjustments() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::mock().trades;
+        let dropped = trades[0].1.transaction_id().to_string();
+        let overridden = trades[1].1.clone();
+        let adjustments: Adjustments = serde_json::from_str(&format!(
+            r#"{{
+                "add": [{{
+                    "transaction_id": "ADJ-0001",
+                    "timestamp": "2021-03-10T10:00:00+01:00",
+                    "transaction_type": "buy",
+                    "in_out": "outgoing",
+                    "amount_fiat": 500.0,
+                    "amount_asset": 0.01,
+                    "asset": "BTC",
+                    "asset_class": "Cryptocurrency",
+                    "asset_market_price": 50000.0,
+                    "fee": 1.5,
+                    "reason": "missing buy"
+                }}],
+                "drop": [
+                    {{ "transaction_id": "{}" }},
+                    {{ "venue": "kraken", "transaction_id": "{}" }},
+                    {{ "transaction_id": "UNKNOWN" }}
+                ],
+                "override": [{{ "transaction_id": "{}", "transaction_type": "transfer", "reason": "gift" }}]
+            }}"#,
+            dropped,
+            overridden.transaction_id(),
+            overridden.transaction_id()
+        ))
+        .unwrap();
+        assert!(!adjustments.is_empty());
+        let (adjusted, report) = adjustments.apply(trades.clone()).unwrap();
+        assert_eq!(adjusted.len(), trades.len());
+        assert!(!adjusted.iter().any(|(_, x)| x.transaction_id() == dropped));
+        // added trade is the oldest one
+        let (venue, added) = &adjusted[0];
+        assert_eq!(*venue, Venue::Bitpanda);
+        assert_eq!(added.transaction_id(), "ADJ-0001");
+        assert_eq!(
+            added.asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+        );
+        assert_eq!(added.fee(), Some(dec!(1.5)));
+        let (_, trade) = adjusted
+            .iter()
+            .find(|(_, x)| x.transaction_id() == overridden.transaction_id())
+            .unwrap();
+        assert_eq!(trade.transaction_type(), TransactionType::Transfer);
+        assert_eq!(trade.amount_fiat(), overridden.amount_fiat());
+        assert_eq!(trade.asset(), overridden.asset());
+        // report
+        assert_eq!(
+            report
+                .applied
+                .iter()
+                .map(|x| x.kind)
+                .collect::<Vec<AdjustmentKind>>(),
+            vec![
+                AdjustmentKind::Drop,
+                AdjustmentKind::Override,
+                AdjustmentKind::Add
+            ]
+        );
+        assert_eq!(report.applied[1].reason.as_deref(), Some("gift"));
+        // the trades are matched on the venue too
+        assert_eq!(report.unmatched.len(), 2);
+        assert_eq!(
+            report.unmatched[0].transaction_id.as_str(),
+            overridden.transaction_id()
+        );
+        assert_eq!(report.unmatched[1].transaction_id.as_str(), "UNKNOWN");
+    }
+
+    #[test]
+    fn should_not_add_existing_trade() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::mock().trades;
+        let adjustments: Adjustments = serde_json::from_str(&format!(
+            r#"{{ "add": [{{ "transaction_id": "{}", "timestamp": "2021-03-10T10:00:00+01:00", "transaction_type": "deposit", "in_out": "incoming", "asset": "EUR", "asset_class": "Fiat" }}] }}"#,
+            trades[0].1.transaction_id()
+        ))
+        .unwrap();
+        assert!(adjustments.apply(trades).is_err());
+    }
+}
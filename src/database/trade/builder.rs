@@ -0,0 +1,309 @@
+//! # Builder
+//!
+//! This module exposes a builder for trades which don't come from a Bitpanda CSV (e.g. hypothetical trades).
+//! Since `Trade` can only be deserialized, the trade is written as a Bitpanda CSV row and parsed back.
+
+use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, InOut, Metal, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+
+/// Column headers of the Bitpanda CSV
+pub const BITPANDA_CSV_COL_HEADER: [&str; 16] = [
+    "Transaction ID",
+    "Timestamp",
+    "Transaction Type",
+    "In/Out",
+    "Amount Fiat",
+    "Fiat",
+    "Amount Asset",
+    "Asset",
+    "Asset market price",
+    "Asset market price currency",
+    "Asset class",
+    "Product ID",
+    "Fee",
+    "Fee asset",
+    "Spread",
+    "Spread Currency",
+];
+
+/// The value used by the Bitpanda CSV for empty fields
+const NONE: &str = "-";
+
+/// Trade builder
+#[derive(Debug, Clone)]
+pub struct TradeBuilder {
+    transaction_id: String,
+    timestamp: DateTime<FixedOffset>,
+    transaction_type: TransactionType,
+    in_out: InOut,
+    amount_fiat: Decimal,
+    fiat: Fiat,
+    amount_asset: Option<Decimal>,
+    asset: Asset,
+    asset_market_price: Option<Decimal>,
+    asset_class: AssetClass,
+    product_id: Option<u64>,
+    fee: Option<(Decimal, Currency)>,
+    spread: Option<(Decimal, Fiat)>,
+}
+
+impl From<&Trade> for TradeBuilder {
+    fn from(trade: &Trade) -> Self {
+        Self {
+            transaction_id: trade.transaction_id().to_string(),
+            timestamp: trade.timestamp(),
+            transaction_type: trade.transaction_type(),
+            in_out: trade.in_out(),
+            amount_fiat: trade.amount_fiat(),
+            fiat: trade.fiat(),
+            amount_asset: trade.amount_asset(),
+            asset: trade.asset(),
+            asset_market_price: trade.asset_market_price(),
+            asset_class: trade.asset_class(),
+            product_id: trade.product_id(),
+            fee: trade.fee().zip(trade.fee_asset()),
+            spread: trade.spread().zip(trade.spread_currency()),
+        }
+    }
+}
+
+impl TradeBuilder {
+    /// Instantiate a new `TradeBuilder` of `asset`. Amounts are zero and the fiat is EUR
+    pub fn new(
+        transaction_id: impl ToString,
+        timestamp: DateTime<FixedOffset>,
+        transaction_type: TransactionType,
+        in_out: InOut,
+        asset: Asset,
+        asset_class: AssetClass,
+    ) -> Self {
+        Self {
+            transaction_id: transaction_id.to_string(),
+            timestamp,
+            transaction_type,
+            in_out,
+            amount_fiat: Decimal::ZERO,
+            fiat: Fiat::Eur,
+            amount_asset: None,
+            asset,
+            asset_market_price: None,
+            asset_class,
+            product_id: None,
+            fee: None,
+            spread: None,
+        }
+    }
+
+    /// Set the transaction type of the trade
+    pub fn with_transaction_type(mut self, transaction_type: TransactionType) -> Self {
+        self.transaction_type = transaction_type;
+        self
+    }
+
+    /// Set the asset class of the trade
+    pub fn with_asset_class(mut self, asset_class: AssetClass) -> Self {
+        self.asset_class = asset_class;
+        self
+    }
+
+    /// Set the FIAT currency the trade is settled in
+    pub fn with_fiat(mut self, fiat: Fiat) -> Self {
+        self.fiat = fiat;
+        self
+    }
+
+    /// Set the FIAT amount of the trade
+    pub fn with_amount_fiat(mut self, amount_fiat: Decimal) -> Self {
+        self.amount_fiat = amount_fiat;
+        self
+    }
+
+    /// Set the asset quantity of the trade
+    pub fn with_amount_asset(mut self, amount_asset: Decimal) -> Self {
+        self.amount_asset = Some(amount_asset);
+        self
+    }
+
+    /// Set the market price of the asset
+    pub fn with_asset_market_price(mut self, price: Decimal) -> Self {
+        self.asset_market_price = Some(price);
+        self
+    }
+
+    /// Set the fee paid for the trade
+    pub fn with_fee(mut self, fee: Decimal, fee_asset: Currency) -> Self {
+        self.fee = Some((fee, fee_asset));
+        self
+    }
+
+    /// Build the trade
+    pub fn build(self) -> anyhow::Result<Trade> {
+        parse_csv_row(&self.csv_row()?)?
+            .ok_or_else(|| anyhow::anyhow!("could not build trade {}", self.transaction_id))
+    }
+
+    /// Write the trade as a row of the Bitpanda CSV, without the column headers
+    pub fn csv_row(&self) -> anyhow::Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(self.record())?;
+        Ok(String::from_utf8(writer.into_inner()?)?
+            .trim_end()
+            .to_string())
+    }
+
+    /// Write the trade as a Bitpanda CSV record
+    fn record(&self) -> Vec<String> {
+        let optional = |value: Option<Decimal>| {
+            value
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| NONE.to_string())
+        };
+        vec![
+            self.transaction_id.clone(),
+            self.timestamp.to_rfc3339(),
+            format!("{:?}", self.transaction_type).to_lowercase(),
+            format!("{:?}", self.in_out).to_lowercase(),
+            self.amount_fiat.to_string(),
+            format!("{:?}", self.fiat).to_uppercase(),
+            optional(self.amount_asset),
+            asset_name(&self.asset),
+            optional(self.asset_market_price),
+            match self.asset_market_price {
+                Some(_) => format!("{:?}", self.fiat).to_uppercase(),
+                None => NONE.to_string(),
+            },
+            asset_class_name(self.asset_class).to_string(),
+            self.product_id
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| NONE.to_string()),
+            optional(self.fee.map(|(fee, _)| fee)),
+            self.fee
+                .map(|(_, currency)| Asset::Currency(currency).to_string())
+                .unwrap_or_else(|| NONE.to_string()),
+            optional(self.spread.map(|(spread, _)| spread)),
+            self.spread
+                .map(|(_, fiat)| format!("{:?}", fiat).to_uppercase())
+                .unwrap_or_else(|| NONE.to_string()),
+        ]
+    }
+}
+
+/// Parse a row of the Bitpanda CSV, written by `TradeBuilder::csv_row`.
+/// Returns `None` if the row is empty
+pub fn parse_csv_row(row: &str) -> anyhow::Result<Option<Trade>> {
+    let csv = format!("{}\n{}", BITPANDA_CSV_COL_HEADER.join(","), row);
+    match csv::Reader::from_reader(csv.as_bytes())
+        .deserialize::<Trade>()
+        .next()
+    {
+        Some(trade) => Ok(Some(trade?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the name of the asset as written in the Bitpanda CSV
+pub fn asset_name(asset: &Asset) -> String {
+    match asset {
+        Asset::Metal(Metal::Gold) => String::from("Gold"),
+        Asset::Metal(Metal::Palladium) => String::from("Palladium"),
+        Asset::Metal(Metal::Platinum) => String::from("Platinum"),
+        Asset::Metal(Metal::Silver) => String::from("Silver"),
+        asset => asset.to_string(),
+    }
+}
+
+/// Parse the asset from its name in the Bitpanda CSV
+pub fn parse_asset(name: &str) -> anyhow::Result<Asset> {
+    if let Ok(id) = name.parse::<i64>() {
+        return Ok(Asset::HongKong(id));
+    }
+    Ok(serde_json::from_value(serde_json::Value::String(
+        name.to_string(),
+    ))?)
+}
+
+/// Returns the name of the asset class as written in the Bitpanda CSV
+pub fn asset_class_name(asset_class: AssetClass) -> &'static str {
+    match asset_class {
+        AssetClass::Fiat => "Fiat",
+        AssetClass::Stock => "Stock (derivative)",
+        AssetClass::Cryptocurrency => "Cryptocurrency",
+        AssetClass::Etf => "ETF (derivative)",
+        AssetClass::Commodity => "Commodity",
+        AssetClass::Metal => "Metal",
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use bitpanda_csv::CryptoCurrency;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_build_trade() {
+        crate::mock::log();
+        let timestamp = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 12, 20, 12, 0, 0)
+            .unwrap();
+        let trade = TradeBuilder::new(
+            "H1",
+            timestamp,
+            TransactionType::Sell,
+            InOut::Incoming,
+            Asset::Metal(Metal::Gold),
+            AssetClass::Metal,
+        )
+        .with_amount_fiat(dec!(150.0))
+        .with_amount_asset(dec!(2.5))
+        .with_asset_market_price(dec!(60.0))
+        .with_fee(dec!(0.5), Currency::Fiat(Fiat::Eur))
+        .build()
+        .unwrap();
+        assert_eq!(trade.transaction_id(), "H1");
+        assert_eq!(trade.timestamp(), timestamp);
+        assert_eq!(trade.transaction_type(), TransactionType::Sell);
+        assert_eq!(trade.in_out(), InOut::Incoming);
+        assert_eq!(trade.amount_fiat(), dec!(150.0));
+        assert_eq!(trade.amount_asset(), Some(dec!(2.5)));
+        assert_eq!(trade.asset(), Asset::Metal(Metal::Gold));
+        assert_eq!(trade.asset_class(), AssetClass::Metal);
+        assert_eq!(trade.asset_market_price(), Some(dec!(60.0)));
+        assert_eq!(trade.fee(), Some(dec!(0.5)));
+        // rebuild from trade
+        let rebuilt = TradeBuilder::from(&trade).build().unwrap();
+        assert_eq!(rebuilt, trade);
+        let rebuilt = TradeBuilder::from(&trade)
+            .with_transaction_type(TransactionType::Transfer)
+            .with_asset_class(AssetClass::Commodity)
+            .build()
+            .unwrap();
+        assert_eq!(rebuilt.transaction_type(), TransactionType::Transfer);
+        assert_eq!(rebuilt.asset_class(), AssetClass::Commodity);
+        assert_eq!(rebuilt.amount_asset(), Some(dec!(2.5)));
+    }
+
+    #[test]
+    fn should_parse_asset() {
+        crate::mock::log();
+        assert_eq!(
+            parse_asset("BTC").unwrap(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+        );
+        assert_eq!(parse_asset("Gold").unwrap(), Asset::Metal(Metal::Gold));
+        assert_eq!(parse_asset("1177").unwrap(), Asset::HongKong(1177));
+        assert_eq!(
+            parse_asset("AMZN").unwrap(),
+            Asset::Ticker(String::from("AMZN"))
+        );
+        assert_eq!(
+            parse_asset(&asset_name(&Asset::Metal(Metal::Silver))).unwrap(),
+            Asset::Metal(Metal::Silver)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! # Hypothetical
+//!
+//! This module exposes the hypothetical trades, used to simulate the effects of future trades on taxes
+
+use bitpanda_csv::{AssetClass, InOut, Trade, TransactionType};
+use chrono::{NaiveDate, TimeZone};
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::builder::{parse_asset, TradeBuilder};
+use super::{TradeDatabase, TradeOrder, TradeQuery};
+use crate::timezone::Rome;
+
+/// Prefix of the transaction ID of the hypothetical trades
+const TRANSACTION_ID_PREFIX: &str = "WHATIF";
+
+/// A list of hypothetical trades.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "trades": [
+///         { "side": "sell", "quantity": 0.5, "asset": "BTC", "date": "2022-12-20", "price": 16000.0 },
+///         { "side": "buy", "quantity": 2, "asset": "VWCE", "asset_class": "ETF (derivative)", "date": "2022-12-21", "price": 95.0 }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct HypotheticalTrades {
+    #[serde(default)]
+    trades: Vec<HypotheticalTrade>,
+}
+
+/// A hypothetical buy or sell.
+///
+/// On the command line it is expressed as `<buy|sell> <quantity> <asset> <YYYY-MM-DD> <unit price>`, e.g. `sell 0.5 BTC 2022-12-20 16000`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HypotheticalTrade {
+    /// Buy or sell
+    side: Side,
+    /// Quantity of asset
+    quantity: Decimal,
+    /// Asset name as written in the Bitpanda CSV
+    asset: String,
+    /// Asset class as written in the Bitpanda CSV; if missing, it is taken from the trades of the same asset
+    #[serde(default)]
+    asset_class: Option<AssetClass>,
+    /// Day of the trade
+    date: NaiveDate,
+    /// Price of a unit of asset in EUR
+    price: Decimal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Side {
+    Buy,
+    Sell,
+}
+
+impl FromStr for HypotheticalTrade {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let [side, quantity, asset, date, price] = tokens.as_slice() else {
+            anyhow::bail!(
+                "invalid hypothetical trade '{}'; expected '<buy|sell> <quantity> <asset> <YYYY-MM-DD> <unit price>'",
+                s
+            );
+        };
+        let side = match side.to_lowercase().as_str() {
+            "buy" => Side::Buy,
+            "sell" => Side::Sell,
+            other => anyhow::bail!("invalid trade side '{}'; expected buy or sell", other),
+        };
+        Ok(Self {
+            side,
+            quantity: Decimal::from_str(quantity)?,
+            asset: asset.to_string(),
+            asset_class: None,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d")?,
+            price: Decimal::from_str(price)?,
+        })
+    }
+}
+
+impl From<Vec<HypotheticalTrade>> for HypotheticalTrades {
+    fn from(trades: Vec<HypotheticalTrade>) -> Self {
+        Self { trades }
+    }
+}
+
+impl HypotheticalTrades {
+    /// Load hypothetical trades from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading hypothetical trades from {}", path.display());
+        let file = File::open(path)?;
+        let trades: Self = serde_json::from_reader(file)?;
+        info!("found {} hypothetical trades", trades.trades.len());
+        Ok(trades)
+    }
+
+    /// Add the hypothetical trades of `other`
+    pub fn extend(&mut self, other: Self) {
+        self.trades.extend(other.trades);
+    }
+
+    /// Returns whether there is no hypothetical trade
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+
+    /// Convert the hypothetical trades into trades.
+    /// The asset class of the trades without one is taken from the latest trade of the same asset in `trades`
+    pub fn trades(&self, trades: &TradeDatabase) -> anyhow::Result<Vec<Trade>> {
+        self.trades
+            .iter()
+            .enumerate()
+            .map(|(i, hypothetical)| {
+                let asset = parse_asset(&hypothetical.asset)?;
+                let asset_class = match hypothetical.asset_class.or_else(|| {
+                    trades
+                        .select(
+                            TradeQuery::default()
+                                .asset_eq(asset.clone())
+                                .order_by(TradeOrder::TimestampDesc),
+                        )
+                        .trades()
+                        .first()
+                        .map(|trade| trade.asset_class())
+                }) {
+                    Some(class) => class,
+                    None => anyhow::bail!(
+                        "could not find the asset class of {}; set it in the hypothetical trades file",
+                        hypothetical.asset
+                    ),
+                };
+                let (transaction_type, in_out) = match hypothetical.side {
+                    Side::Buy => (TransactionType::Buy, InOut::Outgoing),
+                    Side::Sell => (TransactionType::Sell, InOut::Incoming),
+                };
+                let timestamp = Rome
+                    .from_local_datetime(&hypothetical.date.and_hms_opt(12, 0, 0).unwrap())
+                    .unwrap()
+                    .fixed_offset();
+                TradeBuilder::new(
+                    format!("{}-{:04}", TRANSACTION_ID_PREFIX, i + 1),
+                    timestamp,
+                    transaction_type,
+                    in_out,
+                    asset,
+                    asset_class,
+                )
+                .with_amount_fiat(hypothetical.quantity * hypothetical.price)
+                .with_amount_asset(hypothetical.quantity)
+                .with_asset_market_price(hypothetical.price)
+                .build()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{Asset, Metal};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_hypothetical_trade_from_str() {
+        crate::mock::log();
+        let trade = HypotheticalTrade::from_str("sell 0.5 BTC 2022-12-20 16000").unwrap();
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.quantity, dec!(0.5));
+        assert_eq!(trade.asset.as_str(), "BTC");
+        assert_eq!(trade.price, dec!(16000));
+        assert!(HypotheticalTrade::from_str("sell 0.5 BTC").is_err());
+        assert!(HypotheticalTrade::from_str("hodl 0.5 BTC 2022-12-20 16000").is_err());
+    }
+
+    #[test]
+    fn should_convert_hypothetical_trades_into_trades() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let mut hypothetical = HypotheticalTrades::from(vec![HypotheticalTrade::from_str(
+            "sell 1 AMZN 2022-12-20 90",
+        )
+        .unwrap()]);
+        hypothetical.extend(
+            serde_json::from_str(
+                r#"{ "trades": [{ "side": "buy", "quantity": 2, "asset": "Gold", "asset_class": "Metal", "date": "2022-12-21", "price": 55.0 }] }"#,
+            )
+            .unwrap(),
+        );
+        let trades = hypothetical.trades(&db).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].transaction_id(), "WHATIF-0001");
+        assert_eq!(trades[0].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[0].asset_class(), AssetClass::Stock);
+        assert_eq!(trades[0].amount_fiat(), dec!(90));
+        assert_eq!(trades[1].asset(), Asset::Metal(Metal::Gold));
+        assert_eq!(trades[1].amount_fiat(), dec!(110.0));
+        // unknown asset class
+        let hypothetical = HypotheticalTrades::from(vec![HypotheticalTrade::from_str(
+            "buy 1 NVDA 2022-12-20 150",
+        )
+        .unwrap()]);
+        assert!(hypothetical.trades(&db).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! # Import
+//!
+//! This module exposes the importers of the trades exported by other brokers and exchanges.
+//! The exported rows are mapped into the Bitpanda trade model, so that they can be stored in the same `TradeDatabase`.
+
+use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, Trade};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::builder::parse_asset;
+use super::Venue;
+use crate::timezone;
+
+mod api;
+mod coinbase;
+mod kraken;
+mod mapping;
+mod one_trading;
+mod trade_republic;
+
+pub use api::BitpandaApi;
+use coinbase::CoinbaseImporter;
+use kraken::KrakenImporter;
+pub use mapping::{CsvMapping, MappingReport};
+use one_trading::OneTradingImporter;
+pub use trade_republic::IsinSymbols;
+use trade_republic::TradeRepublicImporter;
+
+/// An importer maps the trades exported by a venue into the Bitpanda trade model
+pub trait Importer {
+    /// The venue the trades are exported from
+    fn venue(&self) -> Venue;
+
+    /// Read the trades from the export, keeping the transaction IDs of the venue
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>>;
+}
+
+/// A file exported by a venue other than Bitpanda, expressed as `<venue>:<path>` (e.g. `kraken:trades.csv`)
+/// or as `<mapping.json>:<path>` to read it through a CSV mapping (see `CsvMapping`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportSource {
+    pub format: ImportFormat,
+    pub path: PathBuf,
+}
+
+/// How an imported file is read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// The native export of the venue
+    Venue(Venue),
+    /// A CSV read through the mapping at the path
+    Mapping(PathBuf),
+}
+
+impl FromStr for ImportSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((format, path)) = s.split_once(':') else {
+            anyhow::bail!("invalid import '{}'; expected '<venue>:<path>'", s);
+        };
+        let format = if format.ends_with(".json") {
+            ImportFormat::Mapping(PathBuf::from(format))
+        } else {
+            ImportFormat::Venue(Venue::from_str(format)?)
+        };
+        Ok(Self {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl ImportSource {
+    /// Get the importer of the source; the securities are mapped to their asset on Bitpanda through `isin_symbols`
+    pub fn importer(&self, isin_symbols: &IsinSymbols) -> anyhow::Result<Box<dyn Importer>> {
+        match &self.format {
+            ImportFormat::Venue(Venue::Bitpanda) => {
+                anyhow::bail!("the Bitpanda CSV files must be passed as positional arguments")
+            }
+            ImportFormat::Venue(Venue::Coinbase) => Ok(Box::new(CoinbaseImporter)),
+            ImportFormat::Venue(Venue::Kraken) => Ok(Box::new(KrakenImporter)),
+            ImportFormat::Venue(Venue::OneTrading) => Ok(Box::new(OneTradingImporter)),
+            ImportFormat::Venue(Venue::TradeRepublic) => {
+                Ok(Box::new(TradeRepublicImporter::new(isin_symbols.clone())))
+            }
+            ImportFormat::Mapping(path) => Ok(Box::new(CsvMapping::load(path)?)),
+        }
+    }
+}
+
+/// Parse an amount written with `decimal` (`.` or `,`) as decimal separator, which may have a currency symbol and the
+/// thousands separators of the same locale (e.g. `€1,234.56` with `.`, `1.234,56 €` with `,`). An empty amount is zero.
+/// Fails if the separators don't fit the locale (e.g. `1,5` with `.`), since the amount would be ambiguous
+fn parse_amount(value: &str, decimal: char) -> anyhow::Result<Decimal> {
+    let thousands = match decimal {
+        '.' => ',',
+        ',' => '.',
+        _ => anyhow::bail!("invalid decimal separator '{}'", decimal),
+    };
+    let number: String = value
+        .chars()
+        .filter(|x| x.is_ascii_digit() || matches!(x, '.' | ',' | '-'))
+        .collect();
+    if number.is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    let (integer, fraction) = number.split_once(decimal).unwrap_or((&number, ""));
+    let groups: Vec<&str> = integer.trim_start_matches('-').split(thousands).collect();
+    let grouped = groups.len() == 1
+        || ((1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|x| x.len() == 3));
+    if !grouped || fraction.contains([decimal, thousands]) {
+        anyhow::bail!(
+            "ambiguous amount '{}' with '{}' as decimal separator",
+            value,
+            decimal
+        );
+    }
+    let integer = integer.replace(thousands, "");
+    let number = match fraction.is_empty() {
+        true => integer,
+        false => format!("{}.{}", integer, fraction),
+    };
+    Decimal::from_str(&number).map_err(|err| anyhow::anyhow!("invalid amount '{}': {}", value, err))
+}
+
+/// Parse the symbol of a FIAT currency
+fn parse_fiat(symbol: &str) -> anyhow::Result<Fiat> {
+    match parse_asset(symbol)? {
+        Asset::Currency(Currency::Fiat(fiat)) => Ok(fiat),
+        _ => anyhow::bail!("{} is not a FIAT currency", symbol),
+    }
+}
+
+/// Parse a UTC timestamp, written either as RFC3339 or as `YYYY-MM-DD hh:mm:ss[.fff][ UTC]`
+fn parse_utc_timestamp(value: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp);
+    }
+    let value = value.trim_end_matches("UTC").trim();
+    match NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f") {
+        Ok(timestamp) => Ok(timezone::from_utc(timestamp)),
+        Err(err) => anyhow::bail!("invalid timestamp '{}': {}", value, err),
+    }
+}
+
+/// Returns the asset class of a currency traded on an exchange
+fn currency_class(asset: &Asset) -> AssetClass {
+    match asset {
+        Asset::Currency(Currency::Fiat(_)) => AssetClass::Fiat,
+        _ => AssetClass::Cryptocurrency,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_import_source() {
+        let source = ImportSource::from_str("kraken:exports/trades.csv").unwrap();
+        assert_eq!(source.format, ImportFormat::Venue(Venue::Kraken));
+        assert_eq!(source.path, PathBuf::from("exports/trades.csv"));
+        assert!(source.importer(&IsinSymbols::default()).is_ok());
+        assert_eq!(
+            ImportSource::from_str("trade-republic:transactions.csv")
+                .unwrap()
+                .importer(&IsinSymbols::default())
+                .unwrap()
+                .venue(),
+            Venue::TradeRepublic
+        );
+        assert!(ImportSource::from_str("trades.csv").is_err());
+        assert!(ImportSource::from_str("bitpanda:trades.csv")
+            .unwrap()
+            .importer(&IsinSymbols::default())
+            .is_err());
+        assert_eq!(
+            ImportSource::from_str("mappings/tr.json:export.csv")
+                .unwrap()
+                .format,
+            ImportFormat::Mapping(PathBuf::from("mappings/tr.json"))
+        );
+    }
+
+    #[test]
+    fn should_parse_amounts_and_timestamps() {
+        assert_eq!(parse_amount("€1,234.56", '.').unwrap(), dec!(1234.56));
+        assert_eq!(
+            parse_amount("-1.234.567,8 €", ',').unwrap(),
+            dec!(-1234567.8)
+        );
+        assert_eq!(parse_amount("-0.5", '.').unwrap(), dec!(-0.5));
+        assert_eq!(parse_amount("1,500", '.').unwrap(), dec!(1500));
+        assert_eq!(parse_amount("1,500", ',').unwrap(), dec!(1.5));
+        assert_eq!(parse_amount("", '.').unwrap(), Decimal::ZERO);
+        // the separators don't fit the locale
+        assert!(parse_amount("1,5", '.').is_err());
+        assert!(parse_amount("1.234.5", '.').is_err());
+        assert!(parse_amount("1.234,56", '.').is_err());
+        assert!(parse_amount("12,34.5", '.').is_err());
+        assert!(parse_amount("1.5", ';').is_err());
+        assert_eq!(
+            parse_utc_timestamp("2022-07-15 10:00:00 UTC")
+                .unwrap()
+                .to_rfc3339(),
+            "2022-07-15T12:00:00+02:00"
+        );
+        assert_eq!(
+            parse_utc_timestamp("2022-01-15 10:00:00.1234")
+                .unwrap()
+                .to_rfc3339(),
+            "2022-01-15T11:00:00.123400+01:00"
+        );
+        assert!(parse_utc_timestamp("15/01/2022").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! # Api
+//!
+//! This module exposes the fetcher of the trades from the Bitpanda API, as an alternative to the CSV export
+
+use bitpanda_api::model::crypto_wallet::CryptoWalletTransaction;
+use bitpanda_api::model::fiat_wallet::FiatWalletTransaction;
+use bitpanda_api::model::{
+    CryptoWallet, FiatWallet, InOrOut, Trade as ApiTrade, TradeStatus, TradeType,
+    TransactionStatus, TransactionType as ApiTransactionType,
+};
+use bitpanda_api::Client;
+use bitpanda_csv::{Asset, Currency, Fiat, InOut, Trade, TransactionType};
+use std::collections::HashMap;
+
+use super::{currency_class, parse_fiat};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+
+/// Fetches the history of the user from the Bitpanda API and converts it into the trade model of the CSV.
+///
+/// Trades come from `/trades`, deposits and withdrawals of FIAT from `/fiatwallets/transactions`, while deposits,
+/// withdrawals and transfers (e.g. staking rewards) of crypto from `/wallets/transactions`.
+/// Only the finished transactions are taken.
+pub struct BitpandaApi {
+    client: Client,
+}
+
+/// The history of the user, as returned by the API
+#[derive(Debug, Deserialize)]
+struct ApiHistory {
+    fiat_wallets: Vec<FiatWallet>,
+    crypto_wallets: Vec<CryptoWallet>,
+    trades: Vec<ApiTrade>,
+    fiat_transactions: Vec<FiatWalletTransaction>,
+    crypto_transactions: Vec<CryptoWalletTransaction>,
+}
+
+impl BitpandaApi {
+    /// Name of the source of the trades fetched, as reported when merging the sources
+    pub const SOURCE: &'static str = "api.bitpanda.com";
+
+    /// Setup the fetcher authenticated with the `api_key` of the user
+    pub fn new(api_key: impl ToString) -> Self {
+        Self {
+            client: Client::default().x_apikey(api_key),
+        }
+    }
+
+    /// Fetch the trades, the FIAT and the crypto wallet transactions of the user
+    pub async fn fetch_trades(&self) -> anyhow::Result<Vec<Trade>> {
+        let history = ApiHistory {
+            fiat_wallets: self.client.get_fiat_wallets().await?,
+            crypto_wallets: self.client.get_crypto_wallets().await?,
+            trades: self.client.get_trades().await?,
+            fiat_transactions: self
+                .client
+                .get_fiat_wallet_transactions_ex(None, Some(TransactionStatus::Finished), None)
+                .await?,
+            crypto_transactions: self
+                .client
+                .get_crypto_wallet_transactions_ex(None, Some(TransactionStatus::Finished), None)
+                .await?,
+        };
+        let trades = history.into_trades()?;
+        info!("fetched {} trades from {}", trades.len(), Self::SOURCE);
+        Ok(trades)
+    }
+}
+
+impl ApiHistory {
+    /// Convert the history into trades, sorted by timestamp
+    fn into_trades(self) -> anyhow::Result<Vec<Trade>> {
+        let fiats: HashMap<String, Fiat> = self
+            .fiat_wallets
+            .into_iter()
+            .map(|x| parse_fiat(&x.symbol).map(|fiat| (x.fiat_id, fiat)))
+            .collect::<anyhow::Result<_>>()?;
+        let cryptocoins: HashMap<String, String> = self
+            .crypto_wallets
+            .into_iter()
+            .map(|x| (x.cryptocoin_id, x.symbol))
+            .collect();
+        let fiat_of = |id: &str| match fiats.get(id) {
+            Some(fiat) => Ok(*fiat),
+            None => Err(anyhow::anyhow!("unknown FIAT id {}", id)),
+        };
+
+        let mut trades = Vec::new();
+        for trade in self.trades {
+            if trade.status != TradeStatus::Finished {
+                continue;
+            }
+            let (transaction_type, in_out) = match trade.r#type {
+                TradeType::Buy => (TransactionType::Buy, InOut::Outgoing),
+                TradeType::Sell => (TransactionType::Sell, InOut::Incoming),
+            };
+            let asset = parse_asset(&trade.symbol)?;
+            trades.push(
+                TradeBuilder::new(
+                    trade.id,
+                    trade.datetime,
+                    transaction_type,
+                    in_out,
+                    asset.clone(),
+                    currency_class(&asset),
+                )
+                .with_fiat(fiat_of(&trade.id_fiat)?)
+                .with_amount_fiat(trade.amount_fiat)
+                .with_amount_asset(trade.amount_asset)
+                .with_asset_market_price(trade.price)
+                .build()?,
+            );
+        }
+
+        for tx in self.fiat_transactions {
+            // buys and sells are already taken from the trades
+            let (transaction_type, in_out) = match tx.transaction_type {
+                ApiTransactionType::Deposit => (TransactionType::Deposit, InOut::Incoming),
+                ApiTransactionType::Withdrawal => (TransactionType::Withdrawal, InOut::Outgoing),
+                _ => continue,
+            };
+            if tx.status != TransactionStatus::Finished {
+                continue;
+            }
+            let fiat = fiat_of(&tx.fiat_id)?;
+            let mut builder = TradeBuilder::new(
+                tx.id,
+                tx.datetime,
+                transaction_type,
+                in_out,
+                Asset::Currency(Currency::Fiat(fiat)),
+                currency_class(&Asset::Currency(Currency::Fiat(fiat))),
+            )
+            .with_fiat(fiat)
+            .with_amount_fiat(tx.amount);
+            if !tx.fee.is_zero() {
+                builder = builder.with_fee(tx.fee, Currency::Fiat(fiat));
+            }
+            trades.push(builder.build()?);
+        }
+
+        for tx in self.crypto_transactions {
+            let in_out = match tx.in_or_out {
+                InOrOut::Incoming => InOut::Incoming,
+                InOrOut::Outgoing => InOut::Outgoing,
+            };
+            let transaction_type = match tx.transaction_type {
+                ApiTransactionType::Deposit => TransactionType::Deposit,
+                ApiTransactionType::Withdrawal => TransactionType::Withdrawal,
+                ApiTransactionType::Transfer => TransactionType::Transfer,
+                _ => continue,
+            };
+            if tx.status != TransactionStatus::Finished {
+                continue;
+            }
+            let Some(symbol) = cryptocoins.get(&tx.cryptocoin_id) else {
+                anyhow::bail!("unknown cryptocoin id {}", tx.cryptocoin_id);
+            };
+            let asset = parse_asset(symbol)?;
+            let mut builder = TradeBuilder::new(
+                tx.id,
+                tx.datetime,
+                transaction_type,
+                in_out,
+                asset.clone(),
+                currency_class(&asset),
+            )
+            .with_amount_fiat(tx.amount_eur)
+            .with_amount_asset(tx.amount);
+            if let (false, Asset::Currency(currency)) = (tx.fee.is_zero(), asset) {
+                builder = builder.with_fee(tx.fee, currency);
+            }
+            trades.push(builder.build()?);
+        }
+        trades.sort_by_key(|x| x.timestamp());
+        Ok(trades)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use bitpanda_csv::CryptoCurrency;
+    use pretty_assertions::assert_eq;
+
+    /// Recorded history of the user, as returned by the client
+    const HISTORY: &str = "./test/api/history.json";
+
+    #[test]
+    fn should_convert_api_history_into_trades() {
+        crate::mock::log();
+        let trades = history().into_trades().unwrap();
+        assert_eq!(
+            trades
+                .iter()
+                .map(|x| x.transaction_id())
+                .collect::<Vec<_>>(),
+            vec![
+                "fiat-deposit-1",
+                "trade-1",
+                "crypto-transfer-1",
+                "trade-2",
+                "crypto-withdrawal-1"
+            ]
+        );
+        // deposit
+        assert_eq!(trades[0].transaction_type(), TransactionType::Deposit);
+        assert_eq!(
+            trades[0].asset(),
+            Asset::Currency(Currency::Fiat(Fiat::Eur))
+        );
+        assert_eq!(trades[0].amount_fiat(), dec!(1000.00));
+        // buy (first page)
+        assert_eq!(trades[1].transaction_type(), TransactionType::Buy);
+        assert_eq!(trades[1].in_out(), InOut::Outgoing);
+        assert_eq!(
+            trades[1].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+        );
+        assert_eq!(trades[1].amount_fiat(), dec!(500.00));
+        assert_eq!(trades[1].amount_asset(), Some(dec!(0.01250000)));
+        assert_eq!(trades[1].asset_market_price(), Some(dec!(40000.00)));
+        // staking reward
+        assert_eq!(trades[2].transaction_type(), TransactionType::Transfer);
+        assert_eq!(trades[2].in_out(), InOut::Incoming);
+        assert_eq!(
+            trades[2].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Eth))
+        );
+        // sell (second page)
+        assert_eq!(trades[3].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[3].amount_fiat(), dec!(270.00));
+        // withdrawal
+        assert_eq!(trades[4].transaction_type(), TransactionType::Withdrawal);
+        assert_eq!(trades[4].fee(), Some(dec!(0.00010000)));
+    }
+
+    #[test]
+    fn should_fail_with_unknown_fiat() {
+        crate::mock::log();
+        let mut history = history();
+        history.fiat_wallets.clear();
+        assert!(history.into_trades().is_err());
+    }
+
+    fn history() -> ApiHistory {
+        serde_json::from_slice(&std::fs::read(HISTORY).unwrap()).unwrap()
+    }
+}
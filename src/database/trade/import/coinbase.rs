@@ -0,0 +1,301 @@
+//! # Coinbase
+//!
+//! This module exposes the importer of the Coinbase transaction history CSV
+
+use bitpanda_csv::{Asset, Currency, Fiat, InOut, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::io::Read;
+
+use super::{currency_class, parse_amount, parse_fiat, parse_utc_timestamp, Importer};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+use crate::database::trade::Venue;
+
+/// Decimal separator of the amounts of the export, which have the thousands separated by `,` (e.g. `€1,234.56`)
+const DECIMAL_SEPARATOR: char = '.';
+
+/// Importer of the Coinbase transaction history.
+///
+/// The rows before the column headers (title and user) are skipped. Older exports have no ID column, so the trades
+/// are identified by their line, and call the price columns "Spot Price Currency" and "Spot Price at Transaction".
+///
+/// Conversions between two crypto currencies are split into a sell and a buy, worth the subtotal of the conversion.
+pub struct CoinbaseImporter;
+
+/// Position of the columns in the CSV
+struct Columns {
+    id: Option<usize>,
+    timestamp: usize,
+    transaction_type: usize,
+    asset: usize,
+    quantity: usize,
+    price_currency: usize,
+    price: usize,
+    subtotal: usize,
+    total: usize,
+    fees: usize,
+    notes: usize,
+}
+
+impl Columns {
+    /// Get the columns from the header `record`; returns `None` if `record` is not the header
+    fn from_header(record: &StringRecord) -> Option<Self> {
+        let find = |names: &[&str]| record.iter().position(|x| names.contains(&x.trim()));
+        Some(Self {
+            id: find(&["ID"]),
+            timestamp: find(&["Timestamp"])?,
+            transaction_type: find(&["Transaction Type"])?,
+            asset: find(&["Asset"])?,
+            quantity: find(&["Quantity Transacted"])?,
+            price_currency: find(&["Price Currency", "Spot Price Currency"])?,
+            price: find(&["Price at Transaction", "Spot Price at Transaction"])?,
+            subtotal: find(&["Subtotal"])?,
+            total: find(&["Total (inclusive of fees and/or spread)"])?,
+            fees: find(&["Fees and/or Spread"])?,
+            notes: find(&["Notes"])?,
+        })
+    }
+}
+
+/// A row of the transaction history
+struct Row {
+    id: String,
+    timestamp: DateTime<FixedOffset>,
+    transaction_type: String,
+    asset: Asset,
+    quantity: Decimal,
+    fiat: Fiat,
+    price: Decimal,
+    subtotal: Decimal,
+    total: Decimal,
+    fees: Decimal,
+    notes: String,
+}
+
+impl Importer for CoinbaseImporter {
+    fn venue(&self) -> Venue {
+        Venue::Coinbase
+    }
+
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut columns = None;
+        let mut trades = Vec::new();
+        for (i, record) in reader.records().enumerate() {
+            let record = record?;
+            let line = i + 1;
+            match columns.as_ref() {
+                None => columns = Columns::from_header(&record),
+                Some(columns) => {
+                    let row = Row::parse(columns, &record, line)
+                        .map_err(|err| anyhow::anyhow!("line {}: {}", line, err))?;
+                    trades.extend(row.trades()?);
+                }
+            }
+        }
+        if columns.is_none() {
+            anyhow::bail!("could not find the column headers of the Coinbase CSV");
+        }
+        debug!("imported {} trades from Coinbase", trades.len());
+        Ok(trades)
+    }
+}
+
+impl Row {
+    fn parse(columns: &Columns, record: &StringRecord, line: usize) -> anyhow::Result<Self> {
+        let field = |i: usize| record.get(i).unwrap_or_default().trim();
+        Ok(Self {
+            id: columns
+                .id
+                .map(field)
+                .filter(|x| !x.is_empty())
+                .map(ToString::to_string)
+                .unwrap_or_else(|| format!("line-{}", line)),
+            timestamp: parse_utc_timestamp(field(columns.timestamp))?,
+            transaction_type: field(columns.transaction_type).to_string(),
+            asset: parse_asset(field(columns.asset))?,
+            quantity: parse_amount(field(columns.quantity), DECIMAL_SEPARATOR)?.abs(),
+            fiat: parse_fiat(field(columns.price_currency))?,
+            price: parse_amount(field(columns.price), DECIMAL_SEPARATOR)?,
+            subtotal: parse_amount(field(columns.subtotal), DECIMAL_SEPARATOR)?.abs(),
+            total: parse_amount(field(columns.total), DECIMAL_SEPARATOR)?.abs(),
+            fees: parse_amount(field(columns.fees), DECIMAL_SEPARATOR)?.abs(),
+            notes: field(columns.notes).to_string(),
+        })
+    }
+
+    /// Map the row into trades
+    fn trades(&self) -> anyhow::Result<Vec<Trade>> {
+        let trade = match self.transaction_type.as_str() {
+            "Buy" | "Advanced Trade Buy" => self
+                .trade(&self.id, TransactionType::Buy, InOut::Outgoing)
+                .with_amount_fiat(self.total)
+                .with_amount_asset(self.quantity),
+            "Sell" | "Advanced Trade Sell" => self
+                .trade(&self.id, TransactionType::Sell, InOut::Incoming)
+                .with_amount_fiat(self.total)
+                .with_amount_asset(self.quantity),
+            "Send" => self
+                .trade(&self.id, TransactionType::Withdrawal, InOut::Outgoing)
+                .with_amount_fiat(self.subtotal)
+                .with_amount_asset(self.quantity),
+            "Receive" => self
+                .trade(&self.id, TransactionType::Deposit, InOut::Incoming)
+                .with_amount_fiat(self.subtotal)
+                .with_amount_asset(self.quantity),
+            "Rewards Income" | "Staking Income" | "Inflation Reward" | "Learning Reward"
+            | "Coinbase Earn" => self
+                .trade(&self.id, TransactionType::Transfer, InOut::Incoming)
+                .with_amount_fiat(self.subtotal)
+                .with_amount_asset(self.quantity),
+            "Deposit" => self
+                .trade(&self.id, TransactionType::Deposit, InOut::Incoming)
+                .with_amount_fiat(self.quantity),
+            "Withdrawal" => self
+                .trade(&self.id, TransactionType::Withdrawal, InOut::Outgoing)
+                .with_amount_fiat(self.quantity),
+            "Convert" => return self.conversion(),
+            other => {
+                warn!(
+                    "ignoring Coinbase transaction {} of type '{}'",
+                    self.id, other
+                );
+                return Ok(Vec::new());
+            }
+        };
+        Ok(vec![trade.build()?])
+    }
+
+    /// Split a conversion (e.g. "Converted 0.1 ETH to 0.005 BTC") into a sell and a buy
+    fn conversion(&self) -> anyhow::Result<Vec<Trade>> {
+        let words: Vec<&str> = self.notes.split_whitespace().collect();
+        let [_, _, _, "to", bought_quantity, bought_asset] = words.as_slice() else {
+            anyhow::bail!(
+                "could not read the converted asset of {} from '{}'",
+                self.id,
+                self.notes
+            );
+        };
+        let bought_asset = parse_asset(bought_asset)?;
+        let sell = self
+            .trade(
+                &format!("{}-sell", self.id),
+                TransactionType::Sell,
+                InOut::Incoming,
+            )
+            .with_amount_fiat(self.subtotal)
+            .with_amount_asset(self.quantity)
+            .build()?;
+        let buy = TradeBuilder::new(
+            format!("{}-buy", self.id),
+            self.timestamp,
+            TransactionType::Buy,
+            InOut::Outgoing,
+            bought_asset.clone(),
+            currency_class(&bought_asset),
+        )
+        .with_fiat(self.fiat)
+        .with_amount_fiat(self.subtotal)
+        .with_amount_asset(parse_amount(bought_quantity, DECIMAL_SEPARATOR)?)
+        .build()?;
+        Ok(vec![sell, buy])
+    }
+
+    /// Get the builder for a trade of the row asset
+    fn trade(&self, id: &str, transaction_type: TransactionType, in_out: InOut) -> TradeBuilder {
+        let mut builder = TradeBuilder::new(
+            id,
+            self.timestamp,
+            transaction_type,
+            in_out,
+            self.asset.clone(),
+            currency_class(&self.asset),
+        )
+        .with_fiat(self.fiat);
+        if !self.price.is_zero() {
+            builder = builder.with_asset_market_price(self.price);
+        }
+        if !self.fees.is_zero() {
+            builder = builder.with_fee(self.fees, Currency::Fiat(self.fiat));
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use bitpanda_csv::{AssetClass, CryptoCurrency};
+    use pretty_assertions::assert_eq;
+
+    const COINBASE_CSV: &str = r#"Transactions
+User,Mario Rossi,5f1c0000
+ID,Timestamp,Transaction Type,Asset,Quantity Transacted,Price Currency,Price at Transaction,Subtotal,Total (inclusive of fees and/or spread),Fees and/or Spread,Notes
+6401,2022-01-10 10:00:00 UTC,Deposit,EUR,1000,EUR,€1.00,"€1,000.00","€1,000.00",€0.00,Deposited EUR
+6402,2022-01-10 11:00:00 UTC,Buy,BTC,0.02,EUR,"€40,000.00",€800.00,€810.00,€10.00,Bought 0.02 BTC
+6403,2022-03-01 09:00:00 UTC,Convert,BTC,-0.01,EUR,"€38,000.00",€380.00,€380.00,€0.00,Converted 0.01 BTC to 0.15 ETH
+6404,2022-03-02 09:00:00 UTC,Staking Income,ETH,0.001,EUR,"€2,500.00",€2.50,€2.50,€0.00,
+6405,2022-04-01 09:00:00 UTC,Sell,BTC,-0.01,EUR,"€42,000.00",€420.00,€415.00,€5.00,Sold 0.01 BTC
+6406,2022-04-02 09:00:00 UTC,Advanced Trade Fill,BTC,0.01,EUR,"€42,000.00",€420.00,€415.00,€5.00,
+"#;
+
+    #[test]
+    fn should_import_coinbase_transactions() {
+        crate::mock::log();
+        let trades = CoinbaseImporter
+            .import(&mut COINBASE_CSV.as_bytes())
+            .unwrap();
+        assert_eq!(trades.len(), 6);
+        assert_eq!(CoinbaseImporter.venue(), Venue::Coinbase);
+        // deposit
+        assert_eq!(trades[0].transaction_type(), TransactionType::Deposit);
+        assert_eq!(trades[0].asset_class(), AssetClass::Fiat);
+        assert_eq!(trades[0].amount_fiat(), dec!(1000));
+        // buy
+        assert_eq!(trades[1].transaction_id(), "6402");
+        assert_eq!(trades[1].transaction_type(), TransactionType::Buy);
+        assert_eq!(trades[1].in_out(), InOut::Outgoing);
+        assert_eq!(
+            trades[1].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+        );
+        assert_eq!(trades[1].amount_fiat(), dec!(810));
+        assert_eq!(trades[1].amount_asset(), Some(dec!(0.02)));
+        assert_eq!(trades[1].fee(), Some(dec!(10)));
+        assert_eq!(
+            trades[1].timestamp().to_rfc3339(),
+            "2022-01-10T12:00:00+01:00"
+        );
+        // conversion
+        assert_eq!(trades[2].transaction_id(), "6403-sell");
+        assert_eq!(trades[2].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[2].amount_asset(), Some(dec!(0.01)));
+        assert_eq!(trades[3].transaction_id(), "6403-buy");
+        assert_eq!(
+            trades[3].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Eth))
+        );
+        assert_eq!(trades[3].amount_asset(), Some(dec!(0.15)));
+        assert_eq!(trades[3].amount_fiat(), dec!(380));
+        // staking
+        assert_eq!(trades[4].transaction_type(), TransactionType::Transfer);
+        assert_eq!(trades[4].in_out(), InOut::Incoming);
+        // sell
+        assert_eq!(trades[5].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[5].amount_fiat(), dec!(415));
+    }
+
+    #[test]
+    fn should_not_import_csv_without_headers() {
+        crate::mock::log();
+        assert!(CoinbaseImporter
+            .import(&mut "Transactions\nfoo,bar\n".as_bytes())
+            .is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! # Kraken
+//!
+//! This module exposes the importer of the Kraken trades CSV
+
+use bitpanda_csv::{Asset, Currency, Fiat, InOut, Trade, TransactionType};
+use std::io::Read;
+
+use super::{currency_class, parse_amount, parse_fiat, parse_utc_timestamp, Importer};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+use crate::database::trade::Venue;
+
+/// Decimal separator of the amounts of the export
+const DECIMAL_SEPARATOR: char = '.';
+/// FIAT currencies pairs can be quoted in, with the Kraken symbol first
+const QUOTE_CURRENCIES: [&str; 8] = ["ZEUR", "ZUSD", "ZGBP", "ZCHF", "EUR", "USD", "GBP", "CHF"];
+
+/// Importer of the Kraken trades export (`trades.csv`).
+///
+/// Only the pairs quoted in a FIAT currency are supported; deposits and withdrawals are not part of this export.
+pub struct KrakenImporter;
+
+/// A row of the trades export
+#[derive(Debug, Deserialize)]
+struct Row {
+    txid: String,
+    pair: String,
+    time: String,
+    #[serde(rename = "type")]
+    side: String,
+    price: String,
+    cost: String,
+    fee: String,
+    vol: String,
+}
+
+impl Importer for KrakenImporter {
+    fn venue(&self) -> Venue {
+        Venue::Kraken
+    }
+
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let mut trades = Vec::new();
+        for row in reader.deserialize::<Row>() {
+            let row = row?;
+            trades.push(
+                row.trade()
+                    .map_err(|err| anyhow::anyhow!("trade {}: {}", row.txid, err))?,
+            );
+        }
+        debug!("imported {} trades from Kraken", trades.len());
+        Ok(trades)
+    }
+}
+
+impl Row {
+    /// Map the row into a trade
+    fn trade(&self) -> anyhow::Result<Trade> {
+        let (asset, fiat) = Self::split_pair(&self.pair)?;
+        let cost = parse_amount(&self.cost, DECIMAL_SEPARATOR)?;
+        let fee = parse_amount(&self.fee, DECIMAL_SEPARATOR)?;
+        let (transaction_type, in_out, amount_fiat) = match self.side.as_str() {
+            "buy" => (TransactionType::Buy, InOut::Outgoing, cost + fee),
+            "sell" => (TransactionType::Sell, InOut::Incoming, cost - fee),
+            side => anyhow::bail!("unknown trade type '{}'", side),
+        };
+        let mut builder = TradeBuilder::new(
+            &self.txid,
+            parse_utc_timestamp(&self.time)?,
+            transaction_type,
+            in_out,
+            asset.clone(),
+            currency_class(&asset),
+        )
+        .with_fiat(fiat)
+        .with_amount_fiat(amount_fiat)
+        .with_amount_asset(parse_amount(&self.vol, DECIMAL_SEPARATOR)?)
+        .with_asset_market_price(parse_amount(&self.price, DECIMAL_SEPARATOR)?);
+        if !fee.is_zero() {
+            builder = builder.with_fee(fee, Currency::Fiat(fiat));
+        }
+        builder.build()
+    }
+
+    /// Split a Kraken pair (e.g. `XXBTZEUR`) into the traded asset and the FIAT currency it is quoted in
+    fn split_pair(pair: &str) -> anyhow::Result<(Asset, Fiat)> {
+        let Some((base, quote)) = QUOTE_CURRENCIES.iter().find_map(|quote| {
+            pair.strip_suffix(quote)
+                .filter(|base| !base.is_empty())
+                .map(|base| (base, quote))
+        }) else {
+            anyhow::bail!("pair {} is not quoted in a FIAT currency", pair);
+        };
+        let fiat = parse_fiat(quote.strip_prefix('Z').unwrap_or(quote))?;
+        // legacy symbols have an X prefix (e.g. XETH)
+        let base = match base.strip_prefix('X') {
+            Some(symbol) if base.len() == 4 => symbol,
+            _ => base,
+        };
+        let symbol = match base {
+            "XBT" => "BTC",
+            "XDG" => "DOGE",
+            symbol => symbol,
+        };
+        Ok((parse_asset(symbol)?, fiat))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use bitpanda_csv::CryptoCurrency;
+    use pretty_assertions::assert_eq;
+
+    const KRAKEN_CSV: &str = r#""txid","ordertxid","pair","time","type","ordertype","price","cost","fee","vol","margin","misc","ledgers"
+"TQ1AAA-BBBBB-CCCCCC","OQ1AAA-BBBBB-CCCCCC","XXBTZEUR","2022-02-01 10:00:00.1234","buy","limit","35000.00000","350.00000","0.56000","0.01000000","0.00000","","L1,L2"
+"TQ2AAA-BBBBB-CCCCCC","OQ2AAA-BBBBB-CCCCCC","ADAEUR","2022-08-01 10:00:00.5678","sell","market","0.50000","50.00000","0.13000","100.00000000","0.00000","","L3,L4"
+"#;
+
+    #[test]
+    fn should_import_kraken_trades() {
+        crate::mock::log();
+        let trades = KrakenImporter.import(&mut KRAKEN_CSV.as_bytes()).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].transaction_id(), "TQ1AAA-BBBBB-CCCCCC");
+        assert_eq!(trades[0].transaction_type(), TransactionType::Buy);
+        assert_eq!(
+            trades[0].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+        );
+        assert_eq!(trades[0].fiat(), Fiat::Eur);
+        assert_eq!(trades[0].amount_fiat(), dec!(350.56));
+        assert_eq!(trades[0].amount_asset(), Some(dec!(0.01)));
+        assert_eq!(trades[0].fee(), Some(dec!(0.56)));
+        assert_eq!(trades[1].transaction_type(), TransactionType::Sell);
+        assert_eq!(
+            trades[1].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Ada))
+        );
+        assert_eq!(trades[1].amount_fiat(), dec!(49.87));
+    }
+
+    #[test]
+    fn should_split_kraken_pairs() {
+        assert_eq!(
+            Row::split_pair("XETHZUSD").unwrap(),
+            (
+                Asset::Currency(Currency::Crypto(CryptoCurrency::Eth)),
+                Fiat::Usd
+            )
+        );
+        assert_eq!(
+            Row::split_pair("XDGEUR").unwrap(),
+            (
+                Asset::Currency(Currency::Crypto(CryptoCurrency::Doge)),
+                Fiat::Eur
+            )
+        );
+        assert!(Row::split_pair("XETHXXBT").is_err());
+    }
+}
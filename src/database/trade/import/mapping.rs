@@ -0,0 +1,426 @@
+//! # Mapping
+//!
+//! This module exposes the generic importer of CSV exports, driven by a declarative column mapping
+
+use bitpanda_csv::{Asset, AssetClass, Currency, InOut, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::{currency_class, parse_amount, parse_fiat, parse_utc_timestamp, Importer};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+use crate::database::trade::Venue;
+use crate::timezone::Rome;
+
+/// The mapping of the columns and values of a CSV export into trades.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "venue": "trade-republic",
+///     "delimiter": ";",
+///     "skip_lines": 0,
+///     "columns": {
+///         "transaction_id": "ID",
+///         "timestamp": "Datum",
+///         "transaction_type": "Typ",
+///         "asset": "ISIN",
+///         "amount_asset": "Stück",
+///         "amount_fiat": "Betrag",
+///         "price": "Kurs",
+///         "fee": "Gebühr"
+///     },
+///     "date_format": "%d.%m.%Y %H:%M",
+///     "timezone": "Europe/Rome",
+///     "decimal_separator": ",",
+///     "fiat": "EUR",
+///     "asset_class": "Stock (derivative)",
+///     "transaction_types": { "Kauf": "buy", "Verkauf": "sell", "Einzahlung": "deposit", "Auszahlung": "withdrawal" },
+///     "assets": { "US0378331005": "AAPL" }
+/// }
+/// ```
+///
+/// Rows with a transaction type which is not mapped are skipped.
+/// If `date_format` is missing, timestamps must be RFC3339 or `YYYY-MM-DD hh:mm:ss`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvMapping {
+    venue: Venue,
+    #[serde(default = "CsvMapping::default_delimiter")]
+    delimiter: char,
+    /// Lines to skip before the column headers
+    #[serde(default)]
+    skip_lines: usize,
+    columns: MappedColumns,
+    /// Format of the timestamps, as a chrono format string; dates without time are accepted
+    #[serde(default)]
+    date_format: Option<String>,
+    #[serde(default)]
+    timezone: MappingTimezone,
+    #[serde(default = "CsvMapping::default_decimal_separator")]
+    decimal_separator: char,
+    /// FIAT currency of the trades, if there is no FIAT column
+    #[serde(default = "CsvMapping::default_fiat")]
+    fiat: String,
+    /// Asset class of the assets which are not currencies, if there is no asset class column
+    #[serde(default)]
+    asset_class: Option<AssetClass>,
+    /// Values of the transaction type column, mapped to the transaction type
+    transaction_types: HashMap<String, TransactionType>,
+    /// Values of the asset column, mapped to the asset name as written in the Bitpanda CSV
+    #[serde(default)]
+    assets: HashMap<String, String>,
+}
+
+/// Names of the columns in the CSV
+#[derive(Debug, Clone, Deserialize)]
+struct MappedColumns {
+    #[serde(default)]
+    transaction_id: Option<String>,
+    timestamp: String,
+    transaction_type: String,
+    /// If missing or empty, the asset is the FIAT currency
+    #[serde(default)]
+    asset: Option<String>,
+    #[serde(default)]
+    asset_class: Option<String>,
+    #[serde(default)]
+    amount_asset: Option<String>,
+    amount_fiat: String,
+    #[serde(default)]
+    fiat: Option<String>,
+    #[serde(default)]
+    price: Option<String>,
+    #[serde(default)]
+    fee: Option<String>,
+}
+
+/// Timezone of the timestamps in the CSV
+#[derive(Debug, Default, Copy, Clone, Deserialize)]
+enum MappingTimezone {
+    #[default]
+    #[serde(rename = "Europe/Rome")]
+    EuropeRome,
+    #[serde(rename = "UTC")]
+    Utc,
+}
+
+/// Position of the mapped columns in the CSV
+struct ColumnIndexes {
+    transaction_id: Option<usize>,
+    timestamp: usize,
+    transaction_type: usize,
+    asset: Option<usize>,
+    asset_class: Option<usize>,
+    amount_asset: Option<usize>,
+    amount_fiat: usize,
+    fiat: Option<usize>,
+    price: Option<usize>,
+    fee: Option<usize>,
+}
+
+/// Result of reading a CSV through a mapping
+#[derive(Debug, Default)]
+pub struct MappingReport {
+    pub trades: Vec<Trade>,
+    pub skipped: Vec<SkippedRow>,
+}
+
+/// A row which couldn't be mapped into a trade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRow {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl CsvMapping {
+    /// Load the mapping from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading CSV mapping from {}", path.display());
+        let file = File::open(path)?;
+        let mapping: Self = serde_json::from_reader(file)?;
+        info!(
+            "found mapping for {} with {} transaction types",
+            mapping.venue,
+            mapping.transaction_types.len()
+        );
+        Ok(mapping)
+    }
+
+    /// Read the CSV without failing on invalid rows, which are reported as skipped
+    pub fn dry_run(&self, reader: &mut dyn Read) -> anyhow::Result<MappingReport> {
+        self.read(reader, false)
+    }
+
+    /// Read the trades from the CSV; if `strict`, invalid rows make the import fail
+    fn read(&self, reader: &mut dyn Read, strict: bool) -> anyhow::Result<MappingReport> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(self.delimiter as u8)
+            .from_reader(reader);
+        let mut records = reader.records().enumerate().skip(self.skip_lines);
+        let Some((_, header)) = records.next() else {
+            anyhow::bail!("could not find the column headers");
+        };
+        let columns = self.columns.resolve(&header?)?;
+        let mut report = MappingReport::default();
+        for (i, record) in records {
+            let line = i + 1;
+            let record = record?;
+            let transaction_type = record
+                .get(columns.transaction_type)
+                .unwrap_or_default()
+                .trim();
+            let Some(transaction_type) = self.transaction_types.get(transaction_type).copied()
+            else {
+                debug!(
+                    "skipping line {}: type '{}' is not mapped",
+                    line, transaction_type
+                );
+                report.skipped.push(SkippedRow {
+                    line,
+                    reason: format!("transaction type '{}' is not mapped", transaction_type),
+                });
+                continue;
+            };
+            match self.trade(&columns, &record, line, transaction_type) {
+                Ok(trade) => report.trades.push(trade),
+                Err(err) if !strict => report.skipped.push(SkippedRow {
+                    line,
+                    reason: err.to_string(),
+                }),
+                Err(err) => anyhow::bail!("line {}: {}", line, err),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Map the `record` at `line` into a trade
+    fn trade(
+        &self,
+        columns: &ColumnIndexes,
+        record: &StringRecord,
+        line: usize,
+        transaction_type: TransactionType,
+    ) -> anyhow::Result<Trade> {
+        let field = |i: Option<usize>| {
+            i.and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+        };
+        let fiat = parse_fiat(field(columns.fiat).unwrap_or(&self.fiat))?;
+        let asset = match field(columns.asset) {
+            Some(name) => parse_asset(self.assets.get(name).map(String::as_str).unwrap_or(name))?,
+            None => Asset::Currency(Currency::Fiat(fiat)),
+        };
+        let asset_class = match (field(columns.asset_class), &asset, self.asset_class) {
+            (Some(class), _, _) => {
+                serde_json::from_value(serde_json::Value::String(class.to_string()))?
+            }
+            (None, Asset::Currency(_), _) | (None, _, None) => currency_class(&asset),
+            (None, _, Some(class)) => class,
+        };
+        let in_out = match transaction_type {
+            TransactionType::Buy | TransactionType::Withdrawal => InOut::Outgoing,
+            _ => InOut::Incoming,
+        };
+        let id = field(columns.transaction_id)
+            .map(ToString::to_string)
+            .unwrap_or_else(|| format!("line-{}", line));
+        let timestamp = self.parse_timestamp(field(Some(columns.timestamp)).unwrap_or_default())?;
+        let mut builder =
+            TradeBuilder::new(id, timestamp, transaction_type, in_out, asset, asset_class)
+                .with_fiat(fiat)
+                .with_amount_fiat(self.parse_amount(field(Some(columns.amount_fiat)))?.abs());
+        if let Some(amount_asset) = field(columns.amount_asset) {
+            builder = builder.with_amount_asset(self.parse_amount(Some(amount_asset))?.abs());
+        }
+        if let Some(price) = field(columns.price) {
+            builder = builder.with_asset_market_price(self.parse_amount(Some(price))?.abs());
+        }
+        let fee = self.parse_amount(field(columns.fee))?.abs();
+        if !fee.is_zero() {
+            builder = builder.with_fee(fee, Currency::Fiat(fiat));
+        }
+        builder.build()
+    }
+
+    /// Parse an amount written with the decimal separator of the mapping
+    fn parse_amount(&self, value: Option<&str>) -> anyhow::Result<Decimal> {
+        parse_amount(value.unwrap_or_default(), self.decimal_separator)
+    }
+
+    /// Parse a timestamp with the date format and the timezone of the mapping
+    fn parse_timestamp(&self, value: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+        let Some(format) = self.date_format.as_deref() else {
+            if let MappingTimezone::Utc = self.timezone {
+                return parse_utc_timestamp(value);
+            }
+            if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+                return Ok(timestamp);
+            }
+            let timestamp = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .map_err(|err| anyhow::anyhow!("invalid timestamp '{}': {}", value, err))?;
+            return self.local_timestamp(timestamp);
+        };
+        let timestamp = NaiveDateTime::parse_from_str(value, format)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(value, format).map(|date| date.and_time(NaiveTime::MIN))
+            })
+            .map_err(|err| anyhow::anyhow!("invalid timestamp '{}': {}", value, err))?;
+        self.local_timestamp(timestamp)
+    }
+
+    /// Returns the timestamp of the `local` time in the timezone of the mapping
+    fn local_timestamp(&self, local: NaiveDateTime) -> anyhow::Result<DateTime<FixedOffset>> {
+        match self.timezone {
+            MappingTimezone::Utc => Ok(crate::timezone::from_utc(local)),
+            MappingTimezone::EuropeRome => match Rome.from_local_datetime(&local).earliest() {
+                Some(timestamp) => Ok(timestamp.fixed_offset()),
+                None => anyhow::bail!("{} doesn't exist in Europe/Rome", local),
+            },
+        }
+    }
+
+    fn default_delimiter() -> char {
+        ','
+    }
+
+    fn default_decimal_separator() -> char {
+        '.'
+    }
+
+    fn default_fiat() -> String {
+        String::from("EUR")
+    }
+}
+
+impl Importer for CsvMapping {
+    fn venue(&self) -> Venue {
+        self.venue
+    }
+
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>> {
+        let report = self.read(reader, true)?;
+        for row in report.skipped.iter() {
+            warn!("skipped line {}: {}", row.line, row.reason);
+        }
+        Ok(report.trades)
+    }
+}
+
+impl MappedColumns {
+    /// Find the position of the columns in the `header`
+    fn resolve(&self, header: &StringRecord) -> anyhow::Result<ColumnIndexes> {
+        let find = |name: &str| header.iter().position(|x| x.trim() == name);
+        let required = |name: &str| match find(name) {
+            Some(i) => Ok(i),
+            None => Err(anyhow::anyhow!("column '{}' not found", name)),
+        };
+        let optional = |name: &Option<String>| match name {
+            Some(name) => required(name).map(Some),
+            None => Ok(None),
+        };
+        Ok(ColumnIndexes {
+            transaction_id: optional(&self.transaction_id)?,
+            timestamp: required(&self.timestamp)?,
+            transaction_type: required(&self.transaction_type)?,
+            asset: optional(&self.asset)?,
+            asset_class: optional(&self.asset_class)?,
+            amount_asset: optional(&self.amount_asset)?,
+            amount_fiat: required(&self.amount_fiat)?,
+            fiat: optional(&self.fiat)?,
+            price: optional(&self.price)?,
+            fee: optional(&self.fee)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const MAPPING: &str = r#"{
+        "venue": "trade-republic",
+        "delimiter": ";",
+        "skip_lines": 1,
+        "columns": {
+            "transaction_id": "ID",
+            "timestamp": "Datum",
+            "transaction_type": "Typ",
+            "asset": "ISIN",
+            "amount_asset": "Stück",
+            "amount_fiat": "Betrag",
+            "price": "Kurs",
+            "fee": "Gebühr"
+        },
+        "date_format": "%d.%m.%Y %H:%M",
+        "decimal_separator": ",",
+        "asset_class": "Stock (derivative)",
+        "transaction_types": { "Kauf": "buy", "Verkauf": "sell", "Einzahlung": "deposit" },
+        "assets": { "US0378331005": "AAPL" }
+    }"#;
+
+    const EXPORT: &str = "Trade Republic export
+ID;Datum;Typ;ISIN;Stück;Kurs;Betrag;Gebühr
+TR1;01.07.2022 09:00;Einzahlung;;;;1.000,00;
+TR2;01.07.2022 10:30;Kauf;US0378331005;2;140,50;-282,00;1,00
+TR3;02.07.2022 10:30;Dividende;US0378331005;;;0,46;
+TR4;03.13.2022 10:30;Verkauf;US0378331005;1;150,00;150,00;1,00
+";
+
+    #[test]
+    fn should_import_mapped_csv() {
+        crate::mock::log();
+        let mapping: CsvMapping = serde_json::from_str(MAPPING).unwrap();
+        assert_eq!(mapping.venue(), Venue::TradeRepublic);
+        // strict import fails on the invalid date
+        assert!(mapping.import(&mut EXPORT.as_bytes()).is_err());
+        let report = mapping.dry_run(&mut EXPORT.as_bytes()).unwrap();
+        assert_eq!(report.trades.len(), 2);
+        let deposit = &report.trades[0];
+        assert_eq!(deposit.transaction_id(), "TR1");
+        assert_eq!(deposit.transaction_type(), TransactionType::Deposit);
+        assert_eq!(deposit.asset_class(), AssetClass::Fiat);
+        assert_eq!(deposit.amount_fiat(), dec!(1000));
+        assert_eq!(
+            deposit.timestamp().to_rfc3339(),
+            "2022-07-01T09:00:00+02:00"
+        );
+        let buy = &report.trades[1];
+        assert_eq!(buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(buy.in_out(), InOut::Outgoing);
+        assert_eq!(buy.asset(), Asset::Ticker(String::from("AAPL")));
+        assert_eq!(buy.asset_class(), AssetClass::Stock);
+        assert_eq!(buy.amount_asset(), Some(dec!(2)));
+        assert_eq!(buy.amount_fiat(), dec!(282));
+        assert_eq!(buy.asset_market_price(), Some(dec!(140.50)));
+        assert_eq!(buy.fee(), Some(dec!(1)));
+        // skipped
+        assert_eq!(
+            report.skipped.iter().map(|x| x.line).collect::<Vec<_>>(),
+            vec![5, 6]
+        );
+        assert_eq!(
+            report.skipped[0].reason.as_str(),
+            "transaction type 'Dividende' is not mapped"
+        );
+    }
+
+    #[test]
+    fn should_fail_on_missing_column() {
+        crate::mock::log();
+        let mapping: CsvMapping = serde_json::from_str(MAPPING).unwrap();
+        assert!(mapping
+            .dry_run(&mut "title\nID;Datum;Typ\n".as_bytes())
+            .is_err());
+    }
+}
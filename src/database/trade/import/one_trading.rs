@@ -0,0 +1,284 @@
+//! # One Trading
+//!
+//! This module exposes the importer of the One Trading (ex Bitpanda Pro) trade history CSV
+
+use bitpanda_csv::{Asset, CryptoCurrency, Currency, Fiat, InOut, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::io::Read;
+
+use super::{currency_class, parse_amount, parse_fiat, parse_utc_timestamp, Importer};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+use crate::database::trade::Venue;
+
+/// Separator between the base and the quote asset of a market (e.g. `BTC_EUR`)
+const MARKET_SEPARATOR: char = '_';
+/// Decimal separator of the amounts of the export
+const DECIMAL_SEPARATOR: char = '.';
+
+/// Importer of the One Trading trade history.
+///
+/// Each fill is mapped into a buy or a sell; only the markets quoted in a FIAT currency are supported.
+/// Maker and taker fees are charged either in the asset received or in BEST:
+///
+/// - fees in the quote currency are added to the cost of a buy and subtracted from the proceeds of a sell
+/// - fees in the base asset reduce the quantity bought
+/// - fees in BEST are withdrawals of BEST, with the fill ID suffixed by `-fee`
+///
+/// The rows of type `DEPOSIT` and `WITHDRAWAL` of the account history, whose market is the currency moved,
+/// can be appended to the export, so that the transfers from and to Bitpanda are matched.
+pub struct OneTradingImporter;
+
+/// Position of the columns in the CSV
+struct Columns {
+    id: usize,
+    transaction_type: usize,
+    market: usize,
+    amount: usize,
+    price: usize,
+    fee: usize,
+    fee_currency: usize,
+    timestamp: usize,
+}
+
+impl Columns {
+    fn from_header(record: &StringRecord) -> anyhow::Result<Self> {
+        let find = |names: &[&str]| {
+            record
+                .iter()
+                .position(|x| names.contains(&x.trim()))
+                .ok_or_else(|| anyhow::anyhow!("column '{}' not found", names[0]))
+        };
+        Ok(Self {
+            id: find(&["Trade ID", "Transaction ID"])?,
+            transaction_type: find(&["Type"])?,
+            market: find(&["Market", "Instrument"])?,
+            amount: find(&["Amount"])?,
+            price: find(&["Price"])?,
+            fee: find(&["Fee"])?,
+            fee_currency: find(&["Fee Currency"])?,
+            timestamp: find(&["Time (UTC)", "Time"])?,
+        })
+    }
+}
+
+/// A row of the trade history
+struct Row {
+    id: String,
+    timestamp: DateTime<FixedOffset>,
+    transaction_type: String,
+    market: String,
+    amount: Decimal,
+    price: Decimal,
+    fee: Decimal,
+    fee_currency: String,
+}
+
+impl Importer for OneTradingImporter {
+    fn venue(&self) -> Venue {
+        Venue::OneTrading
+    }
+
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let columns = Columns::from_header(reader.headers()?)?;
+        let mut trades = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let row = Row::parse(&columns, &record)?;
+            trades.extend(
+                row.trades()
+                    .map_err(|err| anyhow::anyhow!("trade {}: {}", row.id, err))?,
+            );
+        }
+        debug!("imported {} trades from One Trading", trades.len());
+        Ok(trades)
+    }
+}
+
+impl Row {
+    fn parse(columns: &Columns, record: &StringRecord) -> anyhow::Result<Self> {
+        let field = |i: usize| record.get(i).unwrap_or_default().trim();
+        Ok(Self {
+            id: field(columns.id).to_string(),
+            timestamp: parse_utc_timestamp(field(columns.timestamp))?,
+            transaction_type: field(columns.transaction_type).to_uppercase(),
+            market: field(columns.market).to_string(),
+            amount: parse_amount(field(columns.amount), DECIMAL_SEPARATOR)?.abs(),
+            price: parse_amount(field(columns.price), DECIMAL_SEPARATOR)?.abs(),
+            fee: parse_amount(field(columns.fee), DECIMAL_SEPARATOR)?.abs(),
+            fee_currency: field(columns.fee_currency).to_string(),
+        })
+    }
+
+    /// Map the row into trades
+    fn trades(&self) -> anyhow::Result<Vec<Trade>> {
+        match self.transaction_type.as_str() {
+            "BUY" => self.fill(TransactionType::Buy, InOut::Outgoing),
+            "SELL" => self.fill(TransactionType::Sell, InOut::Incoming),
+            "DEPOSIT" => self.funding(TransactionType::Deposit, InOut::Incoming),
+            "WITHDRAWAL" => self.funding(TransactionType::Withdrawal, InOut::Outgoing),
+            other => {
+                warn!(
+                    "ignoring One Trading transaction {} of type '{}'",
+                    self.id, other
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Map a fill into a buy or a sell, with the fee accounted for
+    fn fill(&self, transaction_type: TransactionType, in_out: InOut) -> anyhow::Result<Vec<Trade>> {
+        let Some((base, quote)) = self.market.split_once(MARKET_SEPARATOR) else {
+            anyhow::bail!("invalid market '{}'", self.market);
+        };
+        let asset = parse_asset(base)?;
+        let Ok(fiat) = parse_fiat(quote) else {
+            anyhow::bail!("market {} is not quoted in a FIAT currency", self.market);
+        };
+        let gross = self.amount * self.price;
+        let mut amount_asset = self.amount;
+        let mut amount_fiat = gross;
+        let mut fee_trade = None;
+        if !self.fee.is_zero() {
+            let fee_asset = parse_asset(&self.fee_currency)?;
+            let best = Asset::Currency(Currency::Crypto(CryptoCurrency::Best));
+            match (&fee_asset, transaction_type) {
+                (Asset::Currency(Currency::Fiat(_)), TransactionType::Buy) => {
+                    amount_fiat += self.fee
+                }
+                (Asset::Currency(Currency::Fiat(_)), _) => amount_fiat -= self.fee,
+                (fee_asset, TransactionType::Buy) if fee_asset == &asset => {
+                    amount_asset -= self.fee
+                }
+                (fee_asset, _) if fee_asset == &asset => amount_asset += self.fee,
+                (fee_asset, _) if fee_asset == &best => {
+                    fee_trade = Some(
+                        self.trade(
+                            &format!("{}-fee", self.id),
+                            TransactionType::Withdrawal,
+                            InOut::Outgoing,
+                            best,
+                            fiat,
+                        )
+                        .with_amount_asset(self.fee)
+                        .build()?,
+                    );
+                }
+                (fee_asset, _) => anyhow::bail!("unexpected fee currency {}", fee_asset),
+            }
+        }
+        let mut builder = self
+            .trade(&self.id, transaction_type, in_out, asset.clone(), fiat)
+            .with_amount_asset(amount_asset)
+            .with_amount_fiat(amount_fiat)
+            .with_asset_market_price(self.price);
+        if !self.fee.is_zero() {
+            let Asset::Currency(fee_currency) = parse_asset(&self.fee_currency)? else {
+                anyhow::bail!("unexpected fee currency {}", self.fee_currency);
+            };
+            builder = builder.with_fee(self.fee, fee_currency);
+        }
+        Ok(std::iter::once(builder.build()?).chain(fee_trade).collect())
+    }
+
+    /// Map a deposit or withdrawal of the account history
+    fn funding(
+        &self,
+        transaction_type: TransactionType,
+        in_out: InOut,
+    ) -> anyhow::Result<Vec<Trade>> {
+        let asset = parse_asset(&self.market)?;
+        let builder = self.trade(&self.id, transaction_type, in_out, asset.clone(), Fiat::Eur);
+        let trade = match asset {
+            Asset::Currency(Currency::Fiat(fiat)) => {
+                builder.with_fiat(fiat).with_amount_fiat(self.amount)
+            }
+            _ => builder
+                .with_amount_asset(self.amount)
+                .with_amount_fiat(self.amount * self.price),
+        };
+        Ok(vec![trade.build()?])
+    }
+
+    /// Get the builder for a trade of the row
+    fn trade(
+        &self,
+        id: &str,
+        transaction_type: TransactionType,
+        in_out: InOut,
+        asset: Asset,
+        fiat: Fiat,
+    ) -> TradeBuilder {
+        TradeBuilder::new(
+            id,
+            self.timestamp,
+            transaction_type,
+            in_out,
+            asset.clone(),
+            currency_class(&asset),
+        )
+        .with_fiat(fiat)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const ONE_TRADING_CSV: &str = r#"Order ID,Trade ID,Type,Market,Amount,Amount Currency,Price,Price Currency,Fee,Fee Currency,Time (UTC)
+,D-1,DEPOSIT,BTC,0.1,BTC,,,,,2022-03-01 10:00:00
+O-1,F-1,SELL,BTC_EUR,0.05,BTC,40000,EUR,2.00,EUR,2022-03-02 10:00:00
+O-2,F-2,BUY,ETH_EUR,1,ETH,2500,EUR,0.001,ETH,2022-03-03 10:00:00
+O-3,F-3,BUY,BTC_EUR,0.01,BTC,41000,EUR,1.5,BEST,2022-03-04 10:00:00
+O-4,F-4,SELL,ETH_BTC,0.1,ETH,0.07,BTC,0,BTC,2022-03-05 10:00:00
+"#;
+
+    #[test]
+    fn should_import_one_trading_fills() {
+        crate::mock::log();
+        let csv = ONE_TRADING_CSV
+            .lines()
+            .take(5)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let trades = OneTradingImporter.import(&mut csv.as_bytes()).unwrap();
+        assert_eq!(trades.len(), 5);
+        assert_eq!(OneTradingImporter.venue(), Venue::OneTrading);
+        // deposit
+        assert_eq!(trades[0].transaction_id(), "D-1");
+        assert_eq!(trades[0].transaction_type(), TransactionType::Deposit);
+        assert_eq!(trades[0].amount_asset(), Some(dec!(0.1)));
+        // sell with fee in EUR
+        assert_eq!(trades[1].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[1].amount_fiat(), dec!(1998));
+        assert_eq!(trades[1].amount_asset(), Some(dec!(0.05)));
+        // buy with fee in the asset bought
+        assert_eq!(trades[2].transaction_type(), TransactionType::Buy);
+        assert_eq!(trades[2].amount_fiat(), dec!(2500));
+        assert_eq!(trades[2].amount_asset(), Some(dec!(0.999)));
+        // buy with fee in BEST
+        assert_eq!(trades[3].amount_fiat(), dec!(410));
+        assert_eq!(trades[3].amount_asset(), Some(dec!(0.01)));
+        assert_eq!(trades[4].transaction_id(), "F-3-fee");
+        assert_eq!(trades[4].transaction_type(), TransactionType::Withdrawal);
+        assert_eq!(
+            trades[4].asset(),
+            Asset::Currency(Currency::Crypto(CryptoCurrency::Best))
+        );
+        assert_eq!(trades[4].amount_asset(), Some(dec!(1.5)));
+    }
+
+    #[test]
+    fn should_not_import_markets_not_quoted_in_fiat() {
+        crate::mock::log();
+        assert!(OneTradingImporter
+            .import(&mut ONE_TRADING_CSV.as_bytes())
+            .is_err());
+    }
+}
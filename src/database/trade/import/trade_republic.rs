@@ -0,0 +1,316 @@
+//! # Trade Republic
+//!
+//! This module exposes the importer of the Trade Republic transactions, as exported by pytr
+
+use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, InOut, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::{parse_amount, parse_utc_timestamp, Importer};
+use crate::database::trade::builder::{parse_asset, TradeBuilder};
+use crate::database::trade::Venue;
+use crate::timezone;
+
+/// Delimiter of the columns of the export
+const DELIMITER: u8 = b';';
+/// Decimal separator of the amounts of the export, which have the thousands separated by `.` (e.g. `1.234,56`)
+const DECIMAL_SEPARATOR: char = ',';
+
+/// Importer of the Trade Republic transactions, as exported by `pytr export_transactions --lang de --decimal-localization`
+/// (columns `Datum;Typ;Wert;Notiz;ISIN;Stück;Gebühren;Steuern`).
+///
+/// Deposits and withdrawals of EUR, buys and sells are imported, while the other events (e.g. dividends and interests)
+/// are ignored. The value of buys and sells includes the fees. Securities are identified by their ISIN, which is
+/// mapped to the symbol of the asset on Bitpanda through the `IsinSymbols`, so that it can be quoted, and imported as
+/// stocks; the asset class of the ETFs can be fixed with an adjustment.
+///
+/// The export has no transaction ID, so the trades are identified by their day, type, ISIN and value, followed by a
+/// counter if the same trade is repeated.
+pub struct TradeRepublicImporter {
+    symbols: IsinSymbols,
+}
+
+/// The symbols of the securities on Bitpanda, by ISIN.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// { "US0378331005": "AAPL", "IE00B4L5Y983": "MSCI World" }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct IsinSymbols(HashMap<String, String>);
+
+/// A row of the export
+#[derive(Debug, Deserialize)]
+struct Row {
+    #[serde(rename = "Datum")]
+    date: String,
+    #[serde(rename = "Typ")]
+    transaction_type: String,
+    #[serde(rename = "Wert")]
+    value: String,
+    #[serde(rename = "ISIN")]
+    isin: String,
+    #[serde(rename = "Stück")]
+    shares: String,
+    #[serde(rename = "Gebühren")]
+    fees: String,
+}
+
+impl IsinSymbols {
+    /// Load the symbols from the JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading ISIN symbols from {}", path.display());
+        let file = File::open(path)?;
+        let symbols: Self = serde_json::from_reader(file)?;
+        info!("found symbols for {} ISINs", symbols.0.len());
+        Ok(symbols)
+    }
+
+    /// Returns the asset on Bitpanda of the security identified by `isin`
+    fn asset(&self, isin: &str) -> anyhow::Result<Asset> {
+        match self.0.get(isin) {
+            Some(symbol) => parse_asset(symbol),
+            None => anyhow::bail!(
+                "there is no symbol for the ISIN {}, so it couldn't be quoted; map it to its symbol on Bitpanda with --isin-symbols",
+                isin
+            ),
+        }
+    }
+}
+
+impl TradeRepublicImporter {
+    /// Instantiate the importer, mapping the securities to their asset on Bitpanda through `symbols`
+    pub fn new(symbols: IsinSymbols) -> Self {
+        Self { symbols }
+    }
+}
+
+impl Importer for TradeRepublicImporter {
+    fn venue(&self) -> Venue {
+        Venue::TradeRepublic
+    }
+
+    fn import(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<Trade>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(DELIMITER)
+            .from_reader(reader);
+        let mut rows = Vec::new();
+        for (i, row) in reader.deserialize::<Row>().enumerate() {
+            let line = i + 2;
+            let row = row.map_err(|err| anyhow::anyhow!("line {}: {}", line, err))?;
+            let timestamp =
+                parse_date(&row.date).map_err(|err| anyhow::anyhow!("line {}: {}", line, err))?;
+            rows.push((timestamp, row));
+        }
+        // NOTE: pytr lists the events from the newest one; the events of the same day must keep their order
+        if rows.first().map(|(timestamp, _)| timestamp)
+            > rows.last().map(|(timestamp, _)| timestamp)
+        {
+            rows.reverse();
+        }
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        let mut trades = Vec::new();
+        for (timestamp, row) in rows.iter() {
+            let key = format!(
+                "{}/{}/{}/{}",
+                row.date, row.transaction_type, row.isin, row.value
+            );
+            let occurrence = occurrences.entry(key.clone()).or_default();
+            *occurrence += 1;
+            let id = match *occurrence {
+                1 => key,
+                n => format!("{}/{}", key, n),
+            };
+            if let Some(trade) = row
+                .trade(&id, *timestamp, &self.symbols)
+                .map_err(|err| anyhow::anyhow!("trade {}: {}", id, err))?
+            {
+                trades.push(trade);
+            }
+        }
+        debug!("imported {} trades from Trade Republic", trades.len());
+        Ok(trades)
+    }
+}
+
+impl Row {
+    /// Map the row into a trade, if it is a deposit, a withdrawal, a buy or a sell
+    fn trade(
+        &self,
+        id: &str,
+        timestamp: DateTime<FixedOffset>,
+        symbols: &IsinSymbols,
+    ) -> anyhow::Result<Option<Trade>> {
+        let value = parse_amount(&self.value, DECIMAL_SEPARATOR)?.abs();
+        let fee = parse_amount(&self.fees, DECIMAL_SEPARATOR)?.abs();
+        let builder = match self.transaction_type.as_str() {
+            "Einzahlung" => self
+                .cash(id, timestamp, TransactionType::Deposit, InOut::Incoming)
+                .with_amount_fiat(value),
+            "Auszahlung" => self
+                .cash(id, timestamp, TransactionType::Withdrawal, InOut::Outgoing)
+                .with_amount_fiat(value),
+            "Kauf" => self.security(id, timestamp, TransactionType::Buy, value, fee, symbols)?,
+            "Verkauf" => {
+                self.security(id, timestamp, TransactionType::Sell, value, fee, symbols)?
+            }
+            other => {
+                warn!("ignoring Trade Republic event {} of type '{}'", id, other);
+                return Ok(None);
+            }
+        };
+        builder.build().map(Some)
+    }
+
+    /// Get the builder for a deposit or a withdrawal of EUR
+    fn cash(
+        &self,
+        id: &str,
+        timestamp: DateTime<FixedOffset>,
+        transaction_type: TransactionType,
+        in_out: InOut,
+    ) -> TradeBuilder {
+        TradeBuilder::new(
+            id,
+            timestamp,
+            transaction_type,
+            in_out,
+            Asset::Currency(Currency::Fiat(Fiat::Eur)),
+            AssetClass::Fiat,
+        )
+        .with_fiat(Fiat::Eur)
+    }
+
+    /// Get the builder for a buy or a sell of the security of the row, worth `value` including the `fee`
+    fn security(
+        &self,
+        id: &str,
+        timestamp: DateTime<FixedOffset>,
+        transaction_type: TransactionType,
+        value: Decimal,
+        fee: Decimal,
+        symbols: &IsinSymbols,
+    ) -> anyhow::Result<TradeBuilder> {
+        if self.isin.is_empty() {
+            anyhow::bail!("missing ISIN");
+        }
+        let asset = symbols.asset(&self.isin)?;
+        let shares = parse_amount(&self.shares, DECIMAL_SEPARATOR)?.abs();
+        if shares.is_zero() {
+            anyhow::bail!("missing shares");
+        }
+        // NOTE: the fees are paid on top of the price of a buy and taken from the proceeds of a sell
+        let (in_out, gross) = match transaction_type {
+            TransactionType::Buy => (InOut::Outgoing, value - fee),
+            _ => (InOut::Incoming, value + fee),
+        };
+        let mut builder = TradeBuilder::new(
+            id,
+            timestamp,
+            transaction_type,
+            in_out,
+            asset,
+            AssetClass::Stock,
+        )
+        .with_fiat(Fiat::Eur)
+        .with_amount_fiat(value)
+        .with_amount_asset(shares)
+        .with_asset_market_price(gross / shares);
+        if !fee.is_zero() {
+            builder = builder.with_fee(fee, Currency::Fiat(Fiat::Eur));
+        }
+        Ok(builder)
+    }
+}
+
+/// Parse the date of an event, written as `YYYY-MM-DD` or, if exported with the time, as RFC3339
+fn parse_date(value: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(timezone::start_of_day(date)),
+        Err(_) => parse_utc_timestamp(value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const TRADE_REPUBLIC_CSV: &str = "Datum;Typ;Wert;Notiz;ISIN;Stück;Gebühren;Steuern
+2022-08-01;Verkauf;149,00;Apple;US0378331005;-1;-1,00;
+2022-07-15;Dividende;0,46;Apple;US0378331005;;;
+2022-07-01;Kauf;-141,00;Apple;US0378331005;1;-1,00;
+2022-07-01;Kauf;-141,00;Apple;US0378331005;1;-1,00;
+2022-07-01;Einzahlung;1.000,00;;;;;
+";
+
+    #[test]
+    fn should_import_trade_republic_transactions() {
+        crate::mock::log();
+        let importer = importer();
+        let trades = importer.import(&mut TRADE_REPUBLIC_CSV.as_bytes()).unwrap();
+        assert_eq!(importer.venue(), Venue::TradeRepublic);
+        assert_eq!(trades.len(), 4);
+        // deposit, from the oldest event
+        assert_eq!(trades[0].transaction_type(), TransactionType::Deposit);
+        assert_eq!(trades[0].asset_class(), AssetClass::Fiat);
+        assert_eq!(trades[0].amount_fiat(), dec!(1000));
+        assert_eq!(
+            trades[0].timestamp().to_rfc3339(),
+            "2022-07-01T00:00:00+02:00"
+        );
+        // buys
+        assert_eq!(
+            trades[1].transaction_id(),
+            "2022-07-01/Kauf/US0378331005/-141,00"
+        );
+        assert_eq!(
+            trades[2].transaction_id(),
+            "2022-07-01/Kauf/US0378331005/-141,00/2"
+        );
+        assert_eq!(trades[1].transaction_type(), TransactionType::Buy);
+        assert_eq!(trades[1].in_out(), InOut::Outgoing);
+        assert_eq!(trades[1].asset(), Asset::Ticker(String::from("AAPL")));
+        assert_eq!(trades[1].asset_class(), AssetClass::Stock);
+        assert_eq!(trades[1].amount_fiat(), dec!(141));
+        assert_eq!(trades[1].amount_asset(), Some(dec!(1)));
+        assert_eq!(trades[1].asset_market_price(), Some(dec!(140)));
+        assert_eq!(trades[1].fee(), Some(dec!(1)));
+        // sell; the dividend is ignored
+        assert_eq!(trades[3].transaction_type(), TransactionType::Sell);
+        assert_eq!(trades[3].amount_fiat(), dec!(149));
+        assert_eq!(trades[3].asset_market_price(), Some(dec!(150)));
+    }
+
+    #[test]
+    fn should_fail_with_ambiguous_amount() {
+        crate::mock::log();
+        let csv = "Datum;Typ;Wert;Notiz;ISIN;Stück;Gebühren;Steuern
+2022-07-01;Einzahlung;1,000.00;;;;;
+";
+        assert!(importer().import(&mut csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn should_fail_with_isin_without_symbol() {
+        crate::mock::log();
+        let err = TradeRepublicImporter::new(IsinSymbols::default())
+            .import(&mut TRADE_REPUBLIC_CSV.as_bytes())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no symbol for the ISIN US0378331005"));
+    }
+
+    fn importer() -> TradeRepublicImporter {
+        TradeRepublicImporter::new(serde_json::from_str(r#"{ "US0378331005": "AAPL" }"#).unwrap())
+    }
+}
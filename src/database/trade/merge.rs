@@ -0,0 +1,189 @@
+//! # Merge
+//!
+//! This module exposes the merge of the trades of several exports, which may overlap.
+//! Trades are deduplicated by venue and transaction ID.
+
+use bitpanda_csv::Trade;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::Venue;
+
+/// Merges the trades of several sources, keeping the first occurrence of each transaction ID of a venue
+#[derive(Debug, Default)]
+pub struct TradeMerge {
+    trades: Vec<(Venue, Trade)>,
+    /// Source of each trade in `trades`
+    trade_sources: Vec<usize>,
+    /// Index in `trades` by venue and transaction ID
+    index: HashMap<(Venue, String), usize>,
+    report: MergeReport,
+}
+
+/// Report of a merge
+#[derive(Debug, Default, Clone)]
+pub struct MergeReport {
+    pub sources: Vec<MergeSource>,
+    /// Trades with the same transaction ID of another trade, but with a different content
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A merged source
+#[derive(Debug, Clone)]
+pub struct MergeSource {
+    pub path: PathBuf,
+    /// Trades read from the source
+    pub trades: usize,
+    /// Trades already found in a previous source (or earlier in the same source)
+    pub duplicates: usize,
+}
+
+/// A trade discarded because another one with the same transaction ID but a different content has been found before
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub venue: Venue,
+    pub transaction_id: String,
+    /// Source of the trade which has been kept
+    pub kept: PathBuf,
+    /// Source of the trade which has been discarded
+    pub discarded: PathBuf,
+}
+
+impl TradeMerge {
+    /// Add the trades made on `venue`, read from `path`
+    pub fn add(&mut self, path: &Path, venue: Venue, trades: Vec<Trade>) {
+        let source = self.report.sources.len();
+        let mut merge_source = MergeSource {
+            path: path.to_path_buf(),
+            trades: trades.len(),
+            duplicates: 0,
+        };
+        for trade in trades.into_iter() {
+            let key = (venue, trade.transaction_id().to_string());
+            match self.index.get(&key) {
+                Some(&i) if self.trades[i].1 == trade => {
+                    merge_source.duplicates += 1;
+                }
+                Some(&i) => {
+                    warn!(
+                        "transaction {} in {} conflicts with the one already found; discarded",
+                        trade.transaction_id(),
+                        path.display()
+                    );
+                    merge_source.duplicates += 1;
+                    let kept = self.trade_sources[i];
+                    self.report.conflicts.push(MergeConflict {
+                        venue,
+                        transaction_id: trade.transaction_id().to_string(),
+                        kept: self.source_path(kept, &merge_source),
+                        discarded: path.to_path_buf(),
+                    });
+                }
+                None => {
+                    self.index.insert(key, self.trades.len());
+                    self.trades.push((venue, trade));
+                    self.trade_sources.push(source);
+                }
+            }
+        }
+        debug!(
+            "merged {} trades from {} ({} duplicates)",
+            merge_source.trades,
+            path.display(),
+            merge_source.duplicates
+        );
+        self.report.sources.push(merge_source);
+    }
+
+    /// Returns the merged trades sorted by timestamp and the merge report
+    pub fn finish(self) -> (Vec<(Venue, Trade)>, MergeReport) {
+        let mut trades = self.trades;
+        trades.sort_by_key(|(_, x)| x.timestamp());
+        (trades, self.report)
+    }
+
+    /// Returns the path of the source at `index`, which may be the source being added
+    fn source_path(&self, index: usize, current: &MergeSource) -> PathBuf {
+        self.report
+            .sources
+            .get(index)
+            .unwrap_or(current)
+            .path
+            .clone()
+    }
+}
+
+impl MergeReport {
+    /// Returns the total amount of duplicated trades
+    pub fn duplicates(&self) -> usize {
+        self.sources.iter().map(|x| x.duplicates).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::super::builder::TradeBuilder;
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{Asset, AssetClass, InOut, TransactionType};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_merge_trades() {
+        crate::mock::log();
+        let trades: Vec<Trade> = DatabaseTradeMock::mock()
+            .trades
+            .into_iter()
+            .map(|(_, trade)| trade)
+            .collect();
+        let first = &trades[..8];
+        // second file overlaps with the first one
+        let mut second = trades[6..].to_vec();
+        second.reverse();
+        let conflicting = TradeBuilder::new(
+            first[0].transaction_id(),
+            first[0].timestamp(),
+            TransactionType::Deposit,
+            InOut::Incoming,
+            Asset::Ticker(String::from("AMZN")),
+            AssetClass::Stock,
+        )
+        .build()
+        .unwrap();
+        second.push(conflicting);
+        let mut merge = TradeMerge::default();
+        merge.add(Path::new("first.csv"), Venue::Bitpanda, first.to_vec());
+        merge.add(Path::new("second.csv"), Venue::Bitpanda, second);
+        // the same transaction ID on another venue is another trade
+        merge.add(
+            Path::new("kraken.csv"),
+            Venue::Kraken,
+            vec![first[0].clone()],
+        );
+        let (merged, report) = merge.finish();
+        assert_eq!(merged.len(), trades.len() + 1);
+        assert!(merged
+            .windows(2)
+            .all(|x| x[0].1.timestamp() <= x[1].1.timestamp()));
+        assert_eq!(
+            merged
+                .iter()
+                .filter(|(venue, _)| *venue == Venue::Kraken)
+                .count(),
+            1
+        );
+        assert_eq!(report.sources.len(), 3);
+        assert_eq!(report.sources[1].duplicates, 3);
+        assert_eq!(report.duplicates(), 3);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].venue, Venue::Bitpanda);
+        assert_eq!(
+            report.conflicts[0].transaction_id.as_str(),
+            first[0].transaction_id()
+        );
+        assert_eq!(report.conflicts[0].kept, PathBuf::from("first.csv"));
+        assert_eq!(report.conflicts[0].discarded, PathBuf::from("second.csv"));
+    }
+}
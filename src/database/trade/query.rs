@@ -1,21 +1,83 @@
-///! # Query
-///
-/// This module exposes the query which can be performed to select trades
-use bitpanda_csv::Asset;
+//! # Query
+//!
+//! This module exposes the query which can be performed to select trades
+
+use bitpanda_csv::{Asset, AssetClass, Fiat, InOut, TransactionType};
 use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
 
-use super::{Trade, TradeSet};
+use super::{Trade, TradeSet, Venue};
 
-/// Query statement for trade
-#[derive(Default, Debug)]
+/// Query statement for trade.
+///
+/// Filters are in AND; `or` and `not` combine them with the filters of another query, e.g.:
+///
+/// ```ignore
+/// // crypto deposits, but the ones in the set
+/// Query::default()
+///     .transaction_type(TransactionType::Deposit)
+///     .not(Query::default().asset_class(AssetClass::Fiat))
+///     .not(Query::default().transaction_ids(["C2cbcc5dd-67c1-4ded-8020-000000000000"]))
+/// ```
+#[derive(Default, Debug, Clone)]
 pub struct Query {
     filters: Vec<QueryFilter>,
+    order: Option<Order>,
+}
+
+/// How the trades selected by a query are sorted.
+/// If not set, trades are kept in the order of the database, which is by timestamp
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    TimestampAsc,
+    TimestampDesc,
+    AmountFiatAsc,
+    AmountFiatDesc,
+}
+
+impl FromStr for Order {
+    type Err = anyhow::Error;
+
+    /// Parse the order from the name of the field, prefixed by `-` for the descending order (e.g. `-amount`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "date" | "timestamp" => Ok(Self::TimestampAsc),
+            "-date" | "-timestamp" => Ok(Self::TimestampDesc),
+            "amount" => Ok(Self::AmountFiatAsc),
+            "-amount" => Ok(Self::AmountFiatDesc),
+            other => anyhow::bail!(
+                "invalid order '{}'; expected date, -date, amount or -amount",
+                other
+            ),
+        }
+    }
 }
 
 impl Query {
-    pub(super) fn select(self, trades: &[Trade]) -> TradeSet {
+    pub(super) fn select<'a>(
+        self,
+        trades: impl IntoIterator<Item = &'a (Venue, Trade)>,
+    ) -> TradeSet<'a> {
         debug!("selecting trades which satisfy query {:?}", self);
-        TradeSet::from_iter(trades.iter().filter(|trade| self.filter(trade)))
+        let mut selected: Vec<&(Venue, Trade)> = trades
+            .into_iter()
+            .filter(|(_, trade)| self.matches(trade))
+            .collect();
+        match self.order {
+            None => {}
+            Some(Order::TimestampAsc) => selected.sort_by_key(|(_, x)| x.timestamp()),
+            Some(Order::TimestampDesc) => {
+                selected.sort_by_key(|(_, x)| std::cmp::Reverse(x.timestamp()))
+            }
+            Some(Order::AmountFiatAsc) => selected.sort_by_key(|(_, x)| x.amount_fiat()),
+            Some(Order::AmountFiatDesc) => {
+                selected.sort_by_key(|(_, x)| std::cmp::Reverse(x.amount_fiat()))
+            }
+        }
+        TradeSet::from_iter(selected)
     }
 
     /// Select only trades after `date`
@@ -30,29 +92,156 @@ impl Query {
         self
     }
 
+    /// Select only trades which asset is equal to `asset`
+    pub fn asset_eq(mut self, asset: Asset) -> Self {
+        self.filters.push(QueryFilter::AssetEq(asset));
+        self
+    }
+
     /// Select only trades which asset is NOT equal to `asset`
     pub fn asset_neq(mut self, asset: Asset) -> Self {
         self.filters.push(QueryFilter::AssetNeq(asset));
         self
     }
 
-    /// apply filters for trade
-    fn filter(&self, trade: &Trade) -> bool {
+    /// Select only trades of the asset class `class`
+    pub fn asset_class(mut self, class: AssetClass) -> Self {
+        self.filters.push(QueryFilter::AssetClass(class));
+        self
+    }
+
+    /// Select only trades of type `transaction_type`
+    pub fn transaction_type(mut self, transaction_type: TransactionType) -> Self {
+        self.filters
+            .push(QueryFilter::TransactionType(transaction_type));
+        self
+    }
+
+    /// Select only trades with direction `in_out`
+    pub fn in_out(mut self, in_out: InOut) -> Self {
+        self.filters.push(QueryFilter::InOut(in_out));
+        self
+    }
+
+    /// Select only trades in the FIAT currency `fiat`
+    pub fn fiat(mut self, fiat: Fiat) -> Self {
+        self.filters.push(QueryFilter::Fiat(fiat));
+        self
+    }
+
+    /// Select only trades which FIAT amount is in `range` (e.g. `dec!(100)..`)
+    pub fn amount_fiat(mut self, range: impl RangeBounds<Decimal>) -> Self {
+        self.filters.push(QueryFilter::AmountFiat(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ));
+        self
+    }
+
+    /// Select only trades which asset amount is in `range`; trades without an asset amount are excluded
+    pub fn amount_asset(mut self, range: impl RangeBounds<Decimal>) -> Self {
+        self.filters.push(QueryFilter::AmountAsset(
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        ));
+        self
+    }
+
+    /// Select only trades which transaction ID is one of `ids`
+    pub fn transaction_ids<S: ToString>(mut self, ids: impl IntoIterator<Item = S>) -> Self {
+        self.filters.push(QueryFilter::TransactionIds(
+            ids.into_iter().map(|x| x.to_string()).collect(),
+        ));
+        self
+    }
+
+    /// Select only trades which satisfy also the filters of `query`.
+    /// The order of this query is kept; if not set, the order of `query` is used
+    pub fn and(mut self, query: Query) -> Self {
+        self.filters.extend(query.filters);
+        self.order = self.order.or(query.order);
+        self
+    }
+
+    /// Select the trades which satisfy either the filters of this query or the ones of `query`.
+    /// The order of this query is kept; if not set, the order of `query` is used
+    pub fn or(self, query: Query) -> Self {
+        Self {
+            filters: vec![QueryFilter::Any(vec![
+                QueryFilter::All(self.filters),
+                QueryFilter::All(query.filters),
+            ])],
+            order: self.order.or(query.order),
+        }
+    }
+
+    /// Select only trades which do NOT satisfy the filters of `query`
+    pub fn not(mut self, query: Query) -> Self {
+        self.filters
+            .push(QueryFilter::Not(Box::new(QueryFilter::All(query.filters))));
+        self
+    }
+
+    /// Sort the selected trades by `order`
+    pub fn order_by(mut self, order: Order) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// Returns the time range which the trades must be in, according to the filters in AND
+    pub(super) fn time_range(
+        &self,
+    ) -> (Option<DateTime<FixedOffset>>, Option<DateTime<FixedOffset>>) {
+        let mut range = (None, None);
         for filter in self.filters.iter() {
-            if !filter.includes(trade) {
-                return false;
+            match filter {
+                QueryFilter::DateTimeAfter(date) => range.0 = range.0.max(Some(*date)),
+                QueryFilter::DateTimeBefore(date) => {
+                    range.1 = Some(
+                        range
+                            .1
+                            .map_or(*date, |x: DateTime<FixedOffset>| x.min(*date)),
+                    )
+                }
+                _ => {}
             }
         }
-        true
+        range
+    }
+
+    /// Returns the asset which the trades must be of, according to the filters in AND
+    pub(super) fn asset(&self) -> Option<&Asset> {
+        self.filters.iter().find_map(|filter| match filter {
+            QueryFilter::AssetEq(asset) => Some(asset),
+            _ => None,
+        })
+    }
+
+    /// Returns whether `trade` satisfies the filters of the query
+    pub fn matches(&self, trade: &Trade) -> bool {
+        self.filters.iter().all(|filter| filter.includes(trade))
     }
 }
 
 /// A single filter to apply to trades
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum QueryFilter {
+    AssetEq(Asset),
     AssetNeq(Asset),
+    AssetClass(AssetClass),
+    TransactionType(TransactionType),
+    InOut(InOut),
+    Fiat(Fiat),
+    AmountFiat(Bound<Decimal>, Bound<Decimal>),
+    AmountAsset(Bound<Decimal>, Bound<Decimal>),
+    TransactionIds(HashSet<String>),
     DateTimeAfter(DateTime<FixedOffset>),
     DateTimeBefore(DateTime<FixedOffset>),
+    /// All the filters are satisfied
+    All(Vec<QueryFilter>),
+    /// At least one of the filters is satisfied
+    Any(Vec<QueryFilter>),
+    Not(Box<QueryFilter>),
 }
 
 impl QueryFilter {
@@ -60,7 +249,23 @@ impl QueryFilter {
         match self {
             Self::DateTimeAfter(date) => trade.timestamp() >= *date,
             Self::DateTimeBefore(date) => trade.timestamp() <= *date,
+            Self::AssetEq(asset) => trade.asset() == *asset,
             Self::AssetNeq(asset) => trade.asset() != *asset,
+            Self::AssetClass(class) => trade.asset_class() == *class,
+            Self::TransactionType(transaction_type) => {
+                trade.transaction_type() == *transaction_type
+            }
+            Self::InOut(in_out) => trade.in_out() == *in_out,
+            Self::Fiat(fiat) => trade.fiat() == *fiat,
+            Self::AmountFiat(start, end) => (*start, *end).contains(&trade.amount_fiat()),
+            Self::AmountAsset(start, end) => trade
+                .amount_asset()
+                .map(|amount| (*start, *end).contains(&amount))
+                .unwrap_or_default(),
+            Self::TransactionIds(ids) => ids.contains(trade.transaction_id()),
+            Self::All(filters) => filters.iter().all(|filter| filter.includes(trade)),
+            Self::Any(filters) => filters.iter().any(|filter| filter.includes(trade)),
+            Self::Not(filter) => !filter.includes(trade),
         }
     }
 }
@@ -70,7 +275,7 @@ mod test {
 
     use super::*;
     use crate::mock::database::DatabaseTradeMock;
-    use bitpanda_csv::{Currency, Fiat};
+    use bitpanda_csv::{CryptoCurrency, Currency};
 
     use chrono::TimeZone;
     use pretty_assertions::assert_eq;
@@ -104,4 +309,126 @@ mod test {
         let query = Query::default().asset_neq(Asset::Currency(Currency::Fiat(Fiat::Eur)));
         assert_eq!(query.select(&db.trades).trades().len(), 13);
     }
+
+    #[test]
+    fn should_query_by_asset_class_and_type() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+
+        let query = Query::default()
+            .asset_eq(Asset::Currency(Currency::Crypto(CryptoCurrency::Btc)))
+            .transaction_type(TransactionType::Buy)
+            .in_out(InOut::Outgoing)
+            .asset_class(AssetClass::Cryptocurrency)
+            .fiat(Fiat::Eur);
+        let set = query.select(&db.trades);
+        assert!(!set.trades().is_empty());
+        assert!(set.trades().iter().all(|x| {
+            x.asset() == Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))
+                && x.transaction_type() == TransactionType::Buy
+        }));
+    }
+
+    #[test]
+    fn should_query_by_amount_and_ids() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+
+        let set = Query::default()
+            .amount_fiat(dec!(100)..=dec!(500))
+            .select(&db.trades);
+        assert!(set
+            .trades()
+            .iter()
+            .all(|x| x.amount_fiat() >= dec!(100) && x.amount_fiat() <= dec!(500)));
+        let set = Query::default().amount_asset(dec!(1)..).select(&db.trades);
+        assert!(set
+            .trades()
+            .iter()
+            .all(|x| x.amount_asset().unwrap() >= dec!(1)));
+        let ids = [
+            db.trades[0].1.transaction_id().to_string(),
+            db.trades[3].1.transaction_id().to_string(),
+        ];
+        let set = Query::default().transaction_ids(&ids).select(&db.trades);
+        assert_eq!(set.trades().len(), 2);
+        let set = Query::default()
+            .not(Query::default().transaction_ids(&ids))
+            .select(&db.trades);
+        assert_eq!(set.trades().len(), db.trades.len() - 2);
+    }
+
+    #[test]
+    fn should_combine_queries() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+
+        let buys = Query::default().transaction_type(TransactionType::Buy);
+        let sells = Query::default().transaction_type(TransactionType::Sell);
+        let count = |query: Query| query.select(&db.trades).trades().len();
+        assert_eq!(
+            count(buys.clone().or(sells.clone())),
+            count(buys.clone()) + count(sells.clone())
+        );
+        assert_eq!(count(buys.clone().and(sells.clone())), 0);
+        assert_eq!(
+            count(Query::default().not(buys.clone())),
+            db.trades.len() - count(buys)
+        );
+    }
+
+    #[test]
+    fn should_order_selected_trades() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+
+        let set = Query::default()
+            .order_by(Order::TimestampDesc)
+            .select(&db.trades);
+        assert!(set
+            .trades()
+            .windows(2)
+            .all(|x| x[0].timestamp() >= x[1].timestamp()));
+        let set = Query::default()
+            .order_by(Order::from_str("amount").unwrap())
+            .select(&db.trades);
+        assert!(set
+            .trades()
+            .windows(2)
+            .all(|x| x[0].amount_fiat() <= x[1].amount_fiat()));
+    }
+
+    #[test]
+    fn should_keep_order_of_combined_queries() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+
+        let buys = Query::default().transaction_type(TransactionType::Buy);
+        let by_amount = Query::default()
+            .asset_class(AssetClass::Stock)
+            .order_by(Order::AmountFiatDesc);
+        let set = buys.clone().and(by_amount.clone()).select(&db.trades);
+        assert!(!set.trades().is_empty());
+        assert!(set
+            .trades()
+            .windows(2)
+            .all(|x| x[0].amount_fiat() >= x[1].amount_fiat()));
+        // the order of self wins
+        let set = buys
+            .order_by(Order::TimestampDesc)
+            .and(by_amount)
+            .select(&db.trades);
+        assert!(set
+            .trades()
+            .windows(2)
+            .all(|x| x[0].timestamp() >= x[1].timestamp()));
+    }
+
+    #[test]
+    fn should_parse_order() {
+        assert_eq!(Order::from_str("date").unwrap(), Order::TimestampAsc);
+        assert_eq!(Order::from_str("-date").unwrap(), Order::TimestampDesc);
+        assert_eq!(Order::from_str("-amount").unwrap(), Order::AmountFiatDesc);
+        assert!(Order::from_str("asset").is_err());
+    }
 }
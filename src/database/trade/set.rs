@@ -2,7 +2,7 @@
 //!
 //! This module expose a select result on the trade database
 
-use super::Trade;
+use super::{Trade, TradeQuery, Venue};
 use bitpanda_csv::{Asset, AssetClass, Currency, Fiat, InOut, TransactionType};
 
 use rust_decimal::Decimal;
@@ -11,15 +11,19 @@ use std::iter::FromIterator;
 
 pub struct Set<'a> {
     trades: Vec<&'a Trade>,
+    /// Venue of each trade in `trades`
+    venues: Vec<Venue>,
 }
 
-impl<'a> FromIterator<&'a Trade> for Set<'a> {
-    fn from_iter<T: IntoIterator<Item = &'a Trade>>(iter: T) -> Self {
+impl<'a> FromIterator<&'a (Venue, Trade)> for Set<'a> {
+    fn from_iter<T: IntoIterator<Item = &'a (Venue, Trade)>>(iter: T) -> Self {
         let mut trades = Vec::new();
-        for t in iter {
+        let mut venues = Vec::new();
+        for (venue, t) in iter {
             trades.push(t);
+            venues.push(*venue);
         }
-        Self { trades }
+        Self { trades, venues }
     }
 }
 
@@ -28,6 +32,11 @@ impl<'a> Set<'a> {
         &self.trades
     }
 
+    /// Iterate over the trades along with the venue where they have been made
+    pub fn with_venues(&self) -> impl Iterator<Item = (Venue, &'a Trade)> + '_ {
+        self.venues.iter().copied().zip(self.trades.iter().copied())
+    }
+
     /// Group trades by asset
     pub fn group_by_asset(&self) -> HashMap<Asset, Vec<&Trade>> {
         let mut grouped: HashMap<Asset, Vec<&Trade>> = HashMap::new();
@@ -53,10 +62,11 @@ impl<'a> Set<'a> {
 
     /// Get current FIAT balance in the bitpanda wallet
     pub fn fiat_balance(&self, fiat: Fiat) -> Decimal {
+        let in_fiat = TradeQuery::default().fiat(fiat);
         let incoming_fiat = self
             .trades
             .iter()
-            .filter(|t| t.fiat() == fiat)
+            .filter(|t| in_fiat.matches(t))
             .filter(|t| Self::is_fiat_incoming(t))
             .map(|t| t.amount_fiat() - t.fee().unwrap_or_default()) // NOTE: for incoming operations fee must be subtracted, since is kept by Bitpanda
             .sum::<Decimal>();
@@ -64,7 +74,7 @@ impl<'a> Set<'a> {
         let outgoing_fiat = self
             .trades
             .iter()
-            .filter(|t| t.fiat() == fiat)
+            .filter(|t| in_fiat.matches(t))
             .filter(|t| Self::is_fiat_outgoing(t))
             .map(|t| t.amount_fiat())
             .sum::<Decimal>();
@@ -72,6 +82,17 @@ impl<'a> Set<'a> {
         (incoming_fiat - outgoing_fiat).round_dp(2)
     }
 
+    /// Returns how much the FIAT balance changes with `trade`, according to the rules of `fiat_balance`
+    pub(crate) fn fiat_change(trade: &Trade) -> Decimal {
+        if Self::is_fiat_incoming(trade) {
+            trade.amount_fiat() - trade.fee().unwrap_or_default()
+        } else if Self::is_fiat_outgoing(trade) {
+            -trade.amount_fiat()
+        } else {
+            Decimal::ZERO
+        }
+    }
+
     // -- private
 
     /// Returns whether trade is FIAT incoming
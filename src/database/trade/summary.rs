@@ -0,0 +1,151 @@
+//! # Summary
+//!
+//! This module exposes the summary of the trades selected by a query, with the totals for each asset and asset class
+
+use bitpanda_csv::Trade;
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+use super::builder::{asset_class_name, asset_name};
+use super::{TradeSet, Venue};
+
+/// The trades selected by a query and their totals
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub trades: Vec<SummaryTrade>,
+    /// Totals of each asset, sorted by asset name
+    pub assets: Vec<AssetTotal>,
+    /// Totals of each asset class, sorted by class name
+    pub classes: Vec<ClassTotal>,
+}
+
+/// A trade of the summary
+#[derive(Debug, Serialize)]
+pub struct SummaryTrade {
+    pub transaction_id: String,
+    pub timestamp: DateTime<FixedOffset>,
+    pub venue: Venue,
+    pub transaction_type: String,
+    pub in_out: String,
+    pub asset: String,
+    pub asset_class: String,
+    pub amount_asset: Option<Decimal>,
+    pub amount_fiat: Decimal,
+    pub fiat: String,
+    pub asset_market_price: Option<Decimal>,
+    pub fee: Option<Decimal>,
+}
+
+/// Totals of the trades of an asset
+#[derive(Debug, Serialize)]
+pub struct AssetTotal {
+    pub asset: String,
+    pub asset_class: String,
+    pub trades: usize,
+    /// Quantity of the asset moved by the trades
+    pub amount_asset: Decimal,
+    /// FIAT amount of the trades
+    pub amount_fiat: Decimal,
+}
+
+/// Totals of the trades of an asset class
+#[derive(Debug, Serialize)]
+pub struct ClassTotal {
+    pub asset_class: String,
+    pub trades: usize,
+    /// FIAT amount of the trades
+    pub amount_fiat: Decimal,
+}
+
+impl From<&TradeSet<'_>> for Summary {
+    fn from(set: &TradeSet<'_>) -> Self {
+        let trades = set
+            .with_venues()
+            .map(|(venue, trade)| SummaryTrade::new(venue, trade))
+            .collect();
+        let mut assets: Vec<AssetTotal> = set
+            .group_by_asset()
+            .into_iter()
+            .map(|(asset, trades)| AssetTotal {
+                asset: asset_name(&asset),
+                asset_class: asset_class_name(trades[0].asset_class()).to_string(),
+                trades: trades.len(),
+                amount_asset: trades.iter().filter_map(|x| x.amount_asset()).sum(),
+                amount_fiat: trades.iter().map(|x| x.amount_fiat()).sum(),
+            })
+            .collect();
+        assets.sort_by(|a, b| a.asset.cmp(&b.asset));
+        let mut classes: BTreeMap<&'static str, ClassTotal> = BTreeMap::new();
+        for trade in set.trades().iter() {
+            let class = classes
+                .entry(asset_class_name(trade.asset_class()))
+                .or_insert_with(|| ClassTotal {
+                    asset_class: asset_class_name(trade.asset_class()).to_string(),
+                    trades: 0,
+                    amount_fiat: Decimal::ZERO,
+                });
+            class.trades += 1;
+            class.amount_fiat += trade.amount_fiat();
+        }
+        Self {
+            trades,
+            assets,
+            classes: classes.into_values().collect(),
+        }
+    }
+}
+
+impl SummaryTrade {
+    /// Summarize `trade`, made on `venue`
+    fn new(venue: Venue, trade: &Trade) -> Self {
+        Self {
+            transaction_id: trade.transaction_id().to_string(),
+            timestamp: trade.timestamp(),
+            venue,
+            transaction_type: format!("{:?}", trade.transaction_type()),
+            in_out: format!("{:?}", trade.in_out()),
+            asset: asset_name(&trade.asset()),
+            asset_class: asset_class_name(trade.asset_class()).to_string(),
+            amount_asset: trade.amount_asset(),
+            amount_fiat: trade.amount_fiat(),
+            fiat: format!("{:?}", trade.fiat()).to_uppercase(),
+            asset_market_price: trade.asset_market_price(),
+            fee: trade.fee(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::database::TradeQuery;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{Asset, CryptoCurrency, Currency};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_summarize_trades() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let btc = Asset::Currency(Currency::Crypto(CryptoCurrency::Btc));
+        let set = db.select(TradeQuery::default().asset_eq(btc.clone()));
+        let summary = Summary::from(&set);
+        assert_eq!(summary.trades.len(), set.trades().len());
+        assert_eq!(summary.assets.len(), 1);
+        assert_eq!(summary.assets[0].asset, "BTC");
+        assert_eq!(summary.assets[0].trades, set.trades().len());
+        assert_eq!(
+            summary.assets[0].amount_fiat,
+            set.trades()
+                .iter()
+                .map(|x| x.amount_fiat())
+                .sum::<Decimal>()
+        );
+        assert_eq!(summary.classes.len(), 1);
+        assert_eq!(summary.classes[0].asset_class, "Cryptocurrency");
+        assert!(serde_json::to_string(&summary).is_ok());
+    }
+}
@@ -0,0 +1,87 @@
+//! # Venue
+//!
+//! This module exposes the venues (brokers and exchanges) where the trades have been made
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A broker or exchange where the investor holds assets
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Venue {
+    #[default]
+    Bitpanda,
+    Coinbase,
+    Kraken,
+    OneTrading,
+    TradeRepublic,
+}
+
+impl Venue {
+    /// Returns the "codice stato" of the country where the venue is located, as required by the quadro RW
+    pub fn country_code(&self) -> &'static str {
+        match self {
+            Self::Bitpanda | Self::OneTrading => "008",
+            Self::Coinbase | Self::Kraken => "040",
+            Self::TradeRepublic => "094",
+        }
+    }
+
+    /// Returns the name of the country where the venue is located
+    pub fn country(&self) -> &'static str {
+        match self {
+            Self::Bitpanda | Self::OneTrading => "Austria",
+            Self::Coinbase | Self::Kraken => "Irlanda",
+            Self::TradeRepublic => "Germania",
+        }
+    }
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Bitpanda => "bitpanda",
+            Self::Coinbase => "coinbase",
+            Self::Kraken => "kraken",
+            Self::OneTrading => "one-trading",
+            Self::TradeRepublic => "trade-republic",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Venue {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bitpanda" => Ok(Self::Bitpanda),
+            "coinbase" => Ok(Self::Coinbase),
+            "kraken" => Ok(Self::Kraken),
+            "one-trading" => Ok(Self::OneTrading),
+            "trade-republic" => Ok(Self::TradeRepublic),
+            _ => anyhow::bail!("unknown venue '{}'", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_venue() {
+        assert_eq!(
+            Venue::from_str(&Venue::TradeRepublic.to_string()).unwrap(),
+            Venue::TradeRepublic
+        );
+        assert_eq!(Venue::from_str("Coinbase").unwrap(), Venue::Coinbase);
+        assert!(Venue::from_str("binance").is_err());
+        assert_eq!(Venue::Coinbase.country_code(), "040");
+    }
+}
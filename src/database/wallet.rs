@@ -3,14 +3,30 @@
 //! The wallet database contains all the assets detained by your wallet
 
 use super::TradeSet;
-use bitpanda_csv::{Asset, AssetClass, InOut, Trade, TransactionType};
+use bitpanda_csv::{Asset, AssetClass, Fiat, InOut, Trade, TransactionType};
 
+use chrono::{DateTime, FixedOffset};
 use rust_decimal::Decimal;
 use std::collections::{hash_map::Iter, HashMap};
 
-/// Contains all the assets detained by the user
+/// Contains all the assets detained by the user and how their quantity changed along the time
 pub struct WalletDatabase {
     assets: HashMap<Asset, Decimal>,
+    /// Changes of the quantity of each asset, sorted by timestamp
+    history: HashMap<Asset, Vec<HoldingChange>>,
+    /// Changes of the balance of each FIAT currency, sorted by timestamp
+    fiat_history: HashMap<Fiat, Vec<HoldingChange>>,
+}
+
+/// A change of the quantity held of an asset, or of the balance of a FIAT currency.
+/// The changes of the trades at the same timestamp are merged
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HoldingChange {
+    pub timestamp: DateTime<FixedOffset>,
+    /// Quantity added (or removed, if negative)
+    pub change: Decimal,
+    /// Quantity held after the change
+    pub quantity: Decimal,
 }
 
 impl WalletDatabase {
@@ -20,16 +36,41 @@ impl WalletDatabase {
         let grouped_trades = trades.group_by_asset();
         debug!("loading {} assets", grouped_trades.len());
         let mut assets = HashMap::with_capacity(grouped_trades.len());
+        let mut history = HashMap::with_capacity(grouped_trades.len());
         for (asset, trades) in grouped_trades.into_iter() {
             debug!("counting assets amount for {}", asset);
-            assets.insert(asset, Self::count(&trades));
+            let changes = Self::changes_of(trades);
+            assets.insert(
+                asset.clone(),
+                changes.last().map(|x| x.quantity).unwrap_or_default(),
+            );
+            history.insert(asset, changes);
+        }
+        let mut trades: Vec<&Trade> = trades.trades().to_vec();
+        trades.sort_by_key(|x| x.timestamp());
+        let mut fiat_changes: HashMap<Fiat, Vec<(DateTime<FixedOffset>, Decimal)>> = HashMap::new();
+        for trade in trades.into_iter() {
+            let change = TradeSet::fiat_change(trade);
+            if !change.is_zero() {
+                fiat_changes
+                    .entry(trade.fiat())
+                    .or_default()
+                    .push((trade.timestamp(), change));
+            }
         }
+        let fiat_history = fiat_changes
+            .into_iter()
+            .map(|(fiat, changes)| (fiat, Self::time_series(changes)))
+            .collect();
 
-        Self { assets }
+        Self {
+            assets,
+            history,
+            fiat_history,
+        }
     }
 
     /// Get balance for provided asset
-    #[allow(dead_code)]
     pub fn balance(&self, asset: &Asset) -> Option<Decimal> {
         self.assets.get(asset).cloned()
     }
@@ -39,23 +80,95 @@ impl WalletDatabase {
         self.assets.iter()
     }
 
+    /// Get the quantity of `asset` held at `date`, with the trades made until `date` (included)
+    pub fn quantity_at(&self, asset: &Asset, date: DateTime<FixedOffset>) -> Decimal {
+        Self::changes_until(self.changes(asset), date)
+            .last()
+            .map(|x| x.quantity)
+            .unwrap_or_default()
+    }
+
+    /// Get the balance of `fiat` at `date`, with the trades made until `date` (included).
+    /// The balance follows the rules of `TradeSet::fiat_balance`
+    pub fn fiat_balance_at(&self, fiat: Fiat, date: DateTime<FixedOffset>) -> Decimal {
+        Self::changes_until(self.fiat_changes(fiat), date)
+            .last()
+            .map(|x| x.quantity.round_dp(2))
+            .unwrap_or_default()
+    }
+
+    /// Get the time series of the changes of the quantity held of `asset`, sorted by timestamp
+    pub fn changes(&self, asset: &Asset) -> &[HoldingChange] {
+        self.history
+            .get(asset)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Get the time series of the changes of the balance of `fiat`, sorted by timestamp
+    pub fn fiat_changes(&self, fiat: Fiat) -> &[HoldingChange] {
+        self.fiat_history
+            .get(&fiat)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
     // -- private
 
-    /// Get the amount of assets detained from these trades
-    fn count(trades: &[&Trade]) -> Decimal {
-        let incoming_assets = trades
-            .iter()
-            .filter(|t| Self::has_asset_increased(t))
-            .map(|t| Self::asset_amount(t)) // NOTE: for incoming operations fee must be subtracted, since is kept by Bitpanda
-            .sum::<Decimal>();
-        debug!("found {} incoming assets", incoming_assets);
-        let outgoing_assets = trades
-            .iter()
-            .filter(|t| Self::has_asset_decreased(t))
-            .map(|t| Self::asset_amount(t))
-            .sum::<Decimal>();
-        debug!("found {} outgoing assets", outgoing_assets);
-        incoming_assets - outgoing_assets
+    /// Returns how much the quantity of the asset of `trade` changes with it, according to the rules of `load`
+    fn quantity_change(trade: &Trade) -> Decimal {
+        let mut change = Decimal::ZERO;
+        if Self::has_asset_increased(trade) {
+            change += Self::asset_amount(trade);
+        }
+        if Self::has_asset_decreased(trade) {
+            change -= Self::asset_amount(trade);
+        }
+        change
+    }
+
+    /// Get the changes of the quantity of an asset made by its `trades`
+    fn changes_of(mut trades: Vec<&Trade>) -> Vec<HoldingChange> {
+        trades.sort_by_key(|x| x.timestamp());
+        let changes = Self::time_series(
+            trades
+                .into_iter()
+                .map(|trade| (trade.timestamp(), Self::quantity_change(trade))),
+        );
+        debug!(
+            "quantity held after {} changes: {}",
+            changes.len(),
+            changes.last().map(|x| x.quantity).unwrap_or_default()
+        );
+        changes
+    }
+
+    /// Get the time series of the quantity held, from its changes sorted by timestamp
+    fn time_series(
+        changes: impl IntoIterator<Item = (DateTime<FixedOffset>, Decimal)>,
+    ) -> Vec<HoldingChange> {
+        let mut series: Vec<HoldingChange> = Vec::new();
+        let mut quantity = Decimal::ZERO;
+        for (timestamp, change) in changes.into_iter() {
+            quantity += change;
+            match series.last_mut() {
+                Some(last) if last.timestamp == timestamp => {
+                    last.change += change;
+                    last.quantity = quantity;
+                }
+                _ => series.push(HoldingChange {
+                    timestamp,
+                    change,
+                    quantity,
+                }),
+            }
+        }
+        series
+    }
+
+    /// Get the `changes` made until `date` (included)
+    fn changes_until(changes: &[HoldingChange], date: DateTime<FixedOffset>) -> &[HoldingChange] {
+        &changes[..changes.partition_point(|x| x.timestamp <= date)]
     }
 
     /// Check whether asset in trade has increased in quantity, according to these rules:
@@ -104,7 +217,7 @@ mod test {
 
     use super::*;
     use crate::mock::database::DatabaseTradeMock;
-    use bitpanda_csv::{CryptoCurrency, Currency, Fiat};
+    use bitpanda_csv::{CryptoCurrency, Currency};
 
     use pretty_assertions::assert_eq;
 
@@ -150,4 +263,59 @@ mod test {
             dec!(100.0)
         );
     }
+
+    #[test]
+    fn should_get_wallet_at_date() {
+        crate::mock::log();
+        use chrono::TimeZone;
+        let trades = DatabaseTradeMock::mock();
+        let db = WalletDatabase::load(&trades.all());
+        let ada = Asset::Currency(Currency::Crypto(CryptoCurrency::Ada));
+        let changes = db.changes(&ada);
+        assert!(!changes.is_empty());
+        assert_eq!(changes.last().unwrap().quantity, dec!(100.0));
+        assert_eq!(
+            changes.iter().map(|x| x.change).sum::<Decimal>(),
+            db.balance(&ada).unwrap()
+        );
+        // before the first trade of ADA
+        let first = changes[0].timestamp;
+        assert_eq!(
+            db.quantity_at(&ada, first - chrono::Duration::seconds(1)),
+            Decimal::ZERO
+        );
+        assert_eq!(db.quantity_at(&ada, first), changes[0].quantity);
+        // after all the trades
+        let end = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2030, 1, 1, 0, 0, 0)
+            .unwrap();
+        assert_eq!(db.quantity_at(&ada, end), db.balance(&ada).unwrap());
+    }
+
+    #[test]
+    fn should_get_holdings_at_date() {
+        crate::mock::log();
+        use chrono::TimeZone;
+        let db = WalletDatabase::load(&DatabaseTradeMock::mock().all());
+        let august = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 8, 15, 0, 0, 0)
+            .unwrap();
+        let end = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2030, 1, 1, 0, 0, 0)
+            .unwrap();
+        assert_eq!(db.fiat_balance_at(Fiat::Eur, august), dec!(7934.88));
+        assert_eq!(db.fiat_balance_at(Fiat::Eur, end), dec!(7377.54));
+        assert_eq!(db.fiat_balance_at(Fiat::Usd, end), dec!(1000.0));
+        assert_eq!(
+            db.quantity_at(&Asset::Ticker(String::from("AMZN")), end),
+            dec!(1.0)
+        );
+        assert_eq!(
+            db.quantity_at(&Asset::Currency(Currency::Crypto(CryptoCurrency::Ada)), end),
+            dec!(100.0)
+        );
+    }
 }
@@ -114,28 +114,16 @@ mod test {
         // fetch
         let quotes = bitpanda.get_symbols_quotes(&assets).await.unwrap();
         assert_eq!(quotes.len(), 8);
-        assert!(quotes
-            .get(&CsvAsset::Ticker(String::from("NASDAQ100")))
-            .is_some());
-        assert!(quotes
-            .get(&CsvAsset::Ticker(String::from("S&P500")))
-            .is_some());
-        assert!(quotes
-            .get(&CsvAsset::Ticker(String::from("NATGAS")))
-            .is_some());
-        assert!(quotes.get(&CsvAsset::Metal(Metal::Gold)).is_some());
-        assert!(quotes
-            .get(&CsvAsset::Ticker(String::from("AMZN")))
-            .is_some());
-        assert!(quotes.get(&CsvAsset::HongKong(1177)).is_some());
-        assert!(quotes
-            .get(&CsvAsset::Currency(Currency::Crypto(
-                CryptoCurrency::OneInch
-            )))
-            .is_some());
-        assert!(quotes
-            .get(&CsvAsset::Currency(Currency::Crypto(CryptoCurrency::Sushi)))
-            .is_some());
+        assert!(quotes.contains_key(&CsvAsset::Ticker(String::from("NASDAQ100"))));
+        assert!(quotes.contains_key(&CsvAsset::Ticker(String::from("S&P500"))));
+        assert!(quotes.contains_key(&CsvAsset::Ticker(String::from("NATGAS"))));
+        assert!(quotes.contains_key(&CsvAsset::Metal(Metal::Gold)));
+        assert!(quotes.contains_key(&CsvAsset::Ticker(String::from("AMZN"))));
+        assert!(quotes.contains_key(&CsvAsset::HongKong(1177)));
+        assert!(quotes.contains_key(&CsvAsset::Currency(Currency::Crypto(
+            CryptoCurrency::OneInch
+        ))));
+        assert!(quotes.contains_key(&CsvAsset::Currency(Currency::Crypto(CryptoCurrency::Sushi))));
     }
 
     #[tokio::test]
@@ -51,8 +51,7 @@ impl Quotes {
         // get last
         self.quotes
             .iter()
-            .filter(|x| x.date <= date)
-            .last()
+            .rfind(|x| x.date <= date)
             .map(|x| x.price)
             .unwrap_or_else(|| {
                 self.quotes
@@ -63,6 +62,26 @@ impl Quotes {
             })
     }
 
+    /// Get the price quoted at or before `date`.
+    /// Unlike `price_at`, returns `None` if `date` precedes all the quotations
+    pub fn quoted_price_at(&self, date: DateTime<Utc>) -> Option<Decimal> {
+        self.quotes
+            .iter()
+            .rfind(|x| x.date <= date)
+            .map(|x| x.price)
+    }
+
+    /// Iterate over the quotes, sorted by date
+    #[cfg(feature = "sqlite")]
+    pub fn iter(&self) -> impl Iterator<Item = &Quote> {
+        self.quotes.iter()
+    }
+
+    /// Returns whether there are no quotations
+    pub fn is_empty(&self) -> bool {
+        self.quotes.is_empty()
+    }
+
     /// Convert quotes prices to EUR from USD
     pub fn usd_to_eur(&mut self, conversion: &Quotes) -> anyhow::Result<()> {
         debug!("converting quotes to EUR");
@@ -74,6 +93,12 @@ impl Quotes {
 }
 
 impl Quote {
+    /// Create a new Quote with EUR price
+    #[cfg(any(test, feature = "sqlite"))]
+    pub fn eur(date: DateTime<Utc>, price: Decimal) -> Self {
+        Self::new(date, price, Currency::Eur)
+    }
+
     /// Create a new Quote with USD price
     pub fn usd(date: DateTime<Utc>, price: Decimal) -> Self {
         Self::new(date, price, Currency::Usd)
@@ -2,6 +2,8 @@
 extern crate log;
 #[macro_use]
 extern crate rust_decimal_macros;
+#[macro_use]
+extern crate serde;
 
 use env_logger::Builder as LogBuilder;
 use log::LevelFilter;
@@ -11,16 +13,25 @@ mod args;
 mod database;
 mod finance;
 mod module730;
+mod report;
 mod tax;
+mod timezone;
 
 #[cfg(test)]
 mod mock;
 
-use app::App;
-use args::Args;
+use app::{App, Batch, Validation};
+use args::{Args, Command};
+#[cfg(feature = "sqlite")]
+use database::Store;
+use database::{Adjustments, BitpandaApi, HypotheticalTrades, IsinSymbols, Profile};
+#[cfg(feature = "sqlite")]
+use std::rc::Rc;
+use tax::{CarriedLosses, CostBasis, Inventory, SelfTransfers};
 
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 const APP_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
+const BITPANDA_API_KEY_ENV: &str = "BITPANDA_API_KEY";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -40,6 +51,176 @@ async fn main() -> anyhow::Result<()> {
     if args.version {
         anyhow::bail!("bitpanda730 {} - developed by {}", APP_VERSION, APP_AUTHORS)
     }
+    // dry-run a CSV mapping
+    if let Some(Command::Mapping(mapping)) = args.command.as_ref() {
+        App::dry_run_mapping(&mapping.mapping, &mapping.csv_file).await?;
+        return Ok(());
+    }
+    // anonymize a CSV
+    if let Some(Command::Anonymize(anonymize)) = args.command.as_ref() {
+        App::anonymize(&anonymize.csv_file, &anonymize.output, anonymize.scale)?;
+        return Ok(());
+    }
+    // open the local store
+    let history = matches!(args.command, Some(Command::History(_)));
+    #[cfg(feature = "sqlite")]
+    let store = match args.store_file.clone() {
+        Some(path) => Some(path),
+        None if args.store || history => Some(Store::default_path()?),
+        None => None,
+    }
+    .map(|path| Store::open(&path).map(Rc::new))
+    .transpose()?;
+    #[cfg(not(feature = "sqlite"))]
+    if args.store || args.store_file.is_some() || history {
+        anyhow::bail!("the local store requires bitpanda730 to be built with the sqlite feature");
+    }
+    // print the summaries kept in the store
+    #[cfg(feature = "sqlite")]
+    if let (Some(Command::History(_)), Some(store)) = (args.command.as_ref(), store.as_deref()) {
+        return App::history(store);
+    }
+    #[cfg(feature = "sqlite")]
+    let stored = store.is_some();
+    #[cfg(not(feature = "sqlite"))]
+    let stored = false;
+    let mut csv_files = args.csv_files;
+    match args.command.as_ref() {
+        Some(Command::Validate(validate)) => csv_files.extend(validate.csv_files.iter().cloned()),
+        Some(Command::Query(query)) => csv_files.extend(query.csv_files.iter().cloned()),
+        _ => {}
+    }
+    if csv_files.is_empty() && args.import.is_empty() && !args.bitpanda_api && !stored {
+        anyhow::bail!("at least a csv file, an import, --bitpanda-api or --store must be provided");
+    }
+    // setup Bitpanda API
+    let api = match args.bitpanda_api {
+        true => match std::env::var(BITPANDA_API_KEY_ENV) {
+            Ok(api_key) => Some(BitpandaApi::new(api_key)),
+            Err(_) => anyhow::bail!("{} is not set", BITPANDA_API_KEY_ENV),
+        },
+        false => None,
+    };
+    // load self transfers
+    let self_transfers = match args.self_transfers.as_deref() {
+        Some(path) => SelfTransfers::load(path)?,
+        None => SelfTransfers::default(),
+    };
+    // load cost basis
+    let cost_basis = match args.cost_basis.as_deref() {
+        Some(path) => CostBasis::load(path)?,
+        None => CostBasis::default(),
+    };
+    // load carried losses
+    let carried_losses = match args.carried_losses.as_deref() {
+        Some(path) => CarriedLosses::load(path)?,
+        None => CarriedLosses::default(),
+    };
+    // load hypothetical trades
+    let mut what_if = HypotheticalTrades::from(args.what_if);
+    if let Some(path) = args.what_if_file.as_deref() {
+        what_if.extend(HypotheticalTrades::load(path)?);
+    }
+    // load opening inventory
+    let opening_inventory = match args.opening_inventory.as_deref() {
+        Some(path) => Some(Inventory::load(path)?),
+        None => None,
+    };
+    // load ISIN symbols
+    let isin_symbols = match args.isin_symbols.as_deref() {
+        Some(path) => IsinSymbols::load(path)?,
+        None => IsinSymbols::default(),
+    };
+    // load adjustments
+    let adjustments = match args.adjustments.as_deref() {
+        Some(path) => Adjustments::load(path)?,
+        None => Adjustments::default(),
+    };
+    // load profile
+    let mut profile = App::read_profile(&csv_files)?;
+    if let Some(path) = args.profile.as_deref() {
+        profile = Profile::load(path)?.or(profile);
+    }
+    // parse trades
+    let trades = App::parse_trades(&csv_files, &args.import, &isin_symbols, api.as_ref()).await?;
+    #[cfg(feature = "sqlite")]
+    let trades = App::store_trades(trades, store.as_deref())?;
+    let trades = App::adjust_trades(trades, &adjustments)?;
+    // query trades
+    if let Some(Command::Query(query)) = args.command.as_ref() {
+        return App::query(trades, query.query(), query.json);
+    }
+    // validate trades
+    if let Some(Command::Validate(_)) = args.command {
+        let report = Validation::new(trades)
+            .with_profile(profile)
+            .with_self_transfers(self_transfers)
+            .with_cost_basis(cost_basis)
+            .with_opening_inventory(opening_inventory)
+            .run()?;
+        if report.is_blocking() {
+            anyhow::bail!("found {} problems in the trades", report.issues.len());
+        }
+        return Ok(());
+    }
+    // run for several years
+    if let Some(years) = args.years {
+        if args.year.is_some() || args.from.is_some() || args.to.is_some() || args.ytd {
+            anyhow::bail!("--years can't be used with --year, --from/--to or --ytd");
+        }
+        if !what_if.is_empty() {
+            anyhow::bail!("--what-if can't be used with --years");
+        }
+        let batch = Batch::new(years, trades);
+        #[cfg(feature = "sqlite")]
+        let batch = batch.with_store(store);
+        return batch
+            .run(|year, app| {
+                app.with_self_transfers(self_transfers.clone())
+                    .with_cost_basis(cost_basis.clone())
+                    .with_export_dir(args.export_dir.as_ref().map(|x| x.join(year.to_string())))
+                    .with_explain(args.explain)
+                    .with_opening_inventory(opening_inventory.clone())
+                    .with_closing_inventory(args.closing_inventory.clone())
+                    .with_carried_losses(carried_losses.clone())
+                    .with_harvest(args.harvest)
+                    .with_profile(profile.clone())
+            })
+            .await
+            .map(|_| ());
+    }
+    // setup app
+    let app = match (args.year, args.from, args.to, args.ytd) {
+        (Some(year), None, None, false) => App::setup(year, trades)?,
+        (None, Some(from), Some(to), false) => App::setup_range(
+            trades,
+            timezone::start_of_day(from),
+            timezone::end_of_day(to),
+        )?,
+        (None, None, None, true) => App::setup_year_to_date(trades)?,
+        (None, Some(_), None, false) | (None, None, Some(_), false) => {
+            anyhow::bail!("--from and --to must be provided together")
+        }
+        (None, None, None, false) => {
+            anyhow::bail!("either --year, --years, --from and --to or --ytd must be provided")
+        }
+        _ => anyhow::bail!("--year, --from/--to and --ytd can't be used together"),
+    };
     // run app
-    App::setup(args.year, &args.csv_file).await?.run().await
+    #[cfg(feature = "sqlite")]
+    let app = app.with_store(store);
+    app.with_self_transfers(self_transfers)
+        .with_cost_basis(cost_basis)
+        .with_export_dir(args.export_dir)
+        .with_explain(args.explain)
+        .with_opening_inventory(opening_inventory)
+        .with_closing_inventory(args.closing_inventory)
+        .with_carried_losses(carried_losses)
+        .with_harvest(args.harvest)
+        .with_profile(profile)
+        .with_what_if(what_if)
+        .run()
+        .await?;
+
+    Ok(())
 }
@@ -2,29 +2,46 @@
 //!
 //! Quote mock
 
-use super::DatabaseTradeMock;
 use crate::database::QuoteDatabase;
+use crate::finance::{Quote, Quotes};
 
+use bitpanda_csv::{Asset, CryptoCurrency, Currency, Fiat};
 use chrono::prelude::*;
-use chrono::FixedOffset;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
 
 pub struct DatabaseQuoteMock;
 
 impl DatabaseQuoteMock {
-    pub async fn mock() -> QuoteDatabase {
-        let db = DatabaseTradeMock::mock();
-        QuoteDatabase::load(
-            &db,
-            FixedOffset::east_opt(3600)
-                .unwrap()
-                .with_ymd_and_hms(2022, 1, 1, 0, 0, 0)
-                .unwrap(),
+    /// The same quote along the whole 2022 for each asset of `DatabaseTradeMock::mock`
+    pub fn mock() -> QuoteDatabase {
+        let quote = |price: Decimal| {
+            Quotes::from(vec![Quote::eur(
+                Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+                price,
+            )])
+        };
+        QuoteDatabase::from_history(
+            HashMap::from([
+                (Asset::Ticker(String::from("TSLA")), quote(dec!(200))),
+                (Asset::Ticker(String::from("AMZN")), quote(dec!(100))),
+                (Asset::Ticker(String::from("ADBE")), quote(dec!(400))),
+                (Asset::Ticker(String::from("PYPL")), quote(dec!(100))),
+                (Asset::HongKong(1177), quote(dec!(5))),
+                (
+                    Asset::Currency(Currency::Crypto(CryptoCurrency::Btc)),
+                    quote(dec!(20000)),
+                ),
+                (
+                    Asset::Currency(Currency::Crypto(CryptoCurrency::Ada)),
+                    quote(dec!(1)),
+                ),
+                (Asset::Currency(Currency::Fiat(Fiat::Usd)), quote(dec!(0.9))),
+            ]),
             FixedOffset::east_opt(3600)
                 .unwrap()
                 .with_ymd_and_hms(2022, 12, 31, 23, 59, 59)
                 .unwrap(),
         )
-        .await
-        .unwrap()
     }
 }
@@ -1,9 +1,12 @@
-use crate::database::TradeDatabase;
+use crate::database::{TradeDatabase, Venue};
 use bitpanda_csv::{Asset, AssetClass, CryptoCurrency, Currency, Fiat, InOut, TradeGenerator};
 
+use bitpanda_csv::Trade;
 use chrono::DateTime;
 use std::str::FromStr;
 
+const BITPANDA_CSV_COL_HEADER: &str = r#""Transaction ID",Timestamp,"Transaction Type",In/Out,"Amount Fiat",Fiat,"Amount Asset",Asset,"Asset market price","Asset market price currency","Asset class","Product ID",Fee,"Fee asset",Spread,"Spread Currency""#;
+
 pub struct DatabaseTradeMock;
 
 impl DatabaseTradeMock {
@@ -205,4 +208,97 @@ impl DatabaseTradeMock {
             ),
         ])
     }
+
+    /// BTC bought, withdrawn to a private wallet, deposited back and then sold
+    pub fn self_transfer_mock() -> TradeDatabase {
+        TradeDatabase::from(Self::from_csv(
+            r#"T00000000-0000-0000-0000-000000000001,2022-03-01T10:00:00+01:00,buy,outgoing,2000.00,EUR,0.10000000,BTC,20000.00,EUR,Cryptocurrency,1,-,-,-,-
+C00000000-0000-0000-0000-000000000002,2022-04-01T10:00:00+02:00,withdrawal,outgoing,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,0.00010000,BTC,-,-
+C00000000-0000-0000-0000-000000000003,2022-04-05T10:00:00+02:00,deposit,incoming,0,EUR,0.09990000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+T00000000-0000-0000-0000-000000000004,2022-05-01T10:00:00+02:00,sell,incoming,2997.00,EUR,0.09990000,BTC,30000.00,EUR,Cryptocurrency,1,-,-,-,-
+"#,
+        ))
+    }
+
+    /// BTC bought, partially withdrawn to a wallet of the investor and deposited back with more than the withdrawn
+    pub fn excess_self_transfer_mock() -> TradeDatabase {
+        TradeDatabase::from(Self::from_csv(
+            r#"T00000000-0000-0000-0000-000000000005,2022-03-01T10:00:00+01:00,buy,outgoing,2000.00,EUR,0.10000000,BTC,20000.00,EUR,Cryptocurrency,1,-,-,-,-
+C00000000-0000-0000-0000-000000000006,2022-04-01T10:00:00+02:00,withdrawal,outgoing,0,EUR,0.05000000,BTC,0.00,-,Cryptocurrency,1,0.00010000,BTC,-,-
+C00000000-0000-0000-0000-000000000007,2022-04-05T10:00:00+02:00,deposit,incoming,0,EUR,0.09990000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+"#,
+        ))
+    }
+
+    /// BTC deposited from an external wallet and then sold
+    pub fn external_deposit_mock() -> TradeDatabase {
+        TradeDatabase::from(Self::from_csv(
+            r#"C00000000-0000-0000-0000-000000000011,2022-04-05T10:00:00+02:00,deposit,incoming,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+T00000000-0000-0000-0000-000000000012,2022-05-01T10:00:00+02:00,sell,incoming,4000.00,EUR,0.10000000,BTC,40000.00,EUR,Cryptocurrency,1,-,-,-,-
+"#,
+        ))
+    }
+
+    /// BTC bought on Bitpanda, transferred to One Trading and sold there
+    pub fn venue_transfer_mock() -> TradeDatabase {
+        let mut trades = Self::from_venue_csv(
+            Venue::Bitpanda,
+            r#"T00000000-0000-0000-0000-000000000021,2022-03-01T10:00:00+01:00,buy,outgoing,2000.00,EUR,0.10000000,BTC,20000.00,EUR,Cryptocurrency,1,-,-,-,-
+C00000000-0000-0000-0000-000000000022,2022-04-01T10:00:00+02:00,withdrawal,outgoing,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+"#,
+        );
+        trades.extend(Self::from_venue_csv(
+            Venue::OneTrading,
+            r#"D-1,2022-04-01T10:01:00+02:00,deposit,incoming,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+F-1,2022-05-01T10:00:00+02:00,sell,incoming,3000.00,EUR,0.10000000,BTC,30000.00,EUR,Cryptocurrency,1,-,-,-,-
+"#,
+        ));
+        TradeDatabase::from(trades)
+    }
+
+    /// Two equal withdrawals from Bitpanda, but only one deposit into One Trading
+    pub fn double_venue_withdrawal_mock() -> TradeDatabase {
+        let mut trades = Self::from_venue_csv(
+            Venue::Bitpanda,
+            r#"T00000000-0000-0000-0000-000000000021,2022-03-01T10:00:00+01:00,buy,outgoing,4000.00,EUR,0.20000000,BTC,20000.00,EUR,Cryptocurrency,1,-,-,-,-
+C00000000-0000-0000-0000-000000000022,2022-04-01T10:00:00+02:00,withdrawal,outgoing,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+C00000000-0000-0000-0000-000000000023,2022-04-01T10:00:30+02:00,withdrawal,outgoing,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+"#,
+        );
+        trades.extend(Self::from_venue_csv(
+            Venue::OneTrading,
+            r#"D-1,2022-04-01T10:01:00+02:00,deposit,incoming,0,EUR,0.10000000,BTC,0.00,-,Cryptocurrency,1,-,-,-,-
+"#,
+        ));
+        TradeDatabase::from(trades)
+    }
+
+    /// A loss on BTC in 2022, then a gain on BTC in 2023
+    pub fn carried_loss_mock() -> TradeDatabase {
+        TradeDatabase::from(Self::from_csv(
+            r#"F00000000-0000-0000-0000-000000000031,2022-01-10T10:00:00+01:00,deposit,incoming,5000.00,EUR,-,EUR,-,-,Fiat,-,0.00000000,EUR,-,-
+T00000000-0000-0000-0000-000000000032,2022-02-01T10:00:00+01:00,buy,outgoing,2000.00,EUR,0.10000000,BTC,20000.00,EUR,Cryptocurrency,1,-,-,-,-
+T00000000-0000-0000-0000-000000000033,2022-06-01T10:00:00+02:00,sell,incoming,1500.00,EUR,0.10000000,BTC,15000.00,EUR,Cryptocurrency,1,-,-,-,-
+T00000000-0000-0000-0000-000000000034,2023-02-01T10:00:00+01:00,buy,outgoing,1500.00,EUR,0.05000000,BTC,30000.00,EUR,Cryptocurrency,1,-,-,-,-
+T00000000-0000-0000-0000-000000000035,2023-06-01T10:00:00+02:00,sell,incoming,2300.00,EUR,0.05000000,BTC,46000.00,EUR,Cryptocurrency,1,-,-,-,-
+"#,
+        ))
+    }
+
+    /// Parse trades from Bitpanda CSV rows (without column headers)
+    fn from_csv(rows: &str) -> Vec<Trade> {
+        let csv = format!("{BITPANDA_CSV_COL_HEADER}\n{rows}");
+        csv::Reader::from_reader(csv.as_bytes())
+            .deserialize::<Trade>()
+            .map(|trade| trade.expect("invalid mock trade"))
+            .collect()
+    }
+
+    /// Parse trades made on `venue` from Bitpanda CSV rows (without column headers)
+    fn from_venue_csv(venue: Venue, rows: &str) -> Vec<(Venue, Trade)> {
+        Self::from_csv(rows)
+            .into_iter()
+            .map(|trade| (venue, trade))
+            .collect()
+    }
 }
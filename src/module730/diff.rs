@@ -0,0 +1,114 @@
+//! # Diff
+//!
+//! This module exposes the comparison between two 730 modules, used to show the effects of hypothetical trades
+
+use rust_decimal::Decimal;
+
+use super::Module730;
+
+/// The change of the value of a 730 field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    /// The name of the field (e.g. "RT23 - Col. 2")
+    pub field: String,
+    /// The value of the field in the original module
+    pub before: Decimal,
+    /// The value of the field in the compared module
+    pub after: Decimal,
+}
+
+impl FieldChange {
+    /// Returns the difference between the new value and the original one
+    pub fn delta(&self) -> Decimal {
+        self.after - self.before
+    }
+}
+
+impl Module730 {
+    /// Compare each field of this module with the same field of `other`.
+    /// Fields which are missing in one of the two modules are considered zero
+    pub fn compare(&self, other: &Module730) -> Vec<FieldChange> {
+        let mut changes: Vec<FieldChange> = self
+            .derivations()
+            .map(|derivation| FieldChange {
+                field: derivation.field.clone(),
+                before: derivation.value,
+                after: other
+                    .derivations()
+                    .find(|x| x.field == derivation.field)
+                    .map(|x| x.value)
+                    .unwrap_or_default(),
+            })
+            .collect();
+        for derivation in other.derivations() {
+            if !changes.iter().any(|x| x.field == derivation.field) {
+                changes.push(FieldChange {
+                    field: derivation.field.clone(),
+                    before: Decimal::ZERO,
+                    after: derivation.value,
+                });
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::database::Venue;
+    use crate::tax::{CapitalDiff, CarriedLosses, GainsAndLosses, LossNetting};
+
+    use bitpanda_csv::{Asset, AssetClass};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_compare_modules() {
+        crate::mock::log();
+        let before_gains = GainsAndLosses::from(vec![CapitalDiff::gain(
+            Asset::Ticker(String::from("AMZN")),
+            AssetClass::Stock,
+            dec!(26.0),
+            dec!(100.0),
+        )]);
+        let before = Module730::prepare(
+            &[(Venue::Bitpanda, dec!(6000.0))],
+            365,
+            dec!(12.0),
+            &before_gains,
+            &LossNetting::new(&before_gains, &CarriedLosses::default(), 2022),
+        )
+        .unwrap();
+        let after_gains = GainsAndLosses::from(vec![
+            CapitalDiff::gain(
+                Asset::Ticker(String::from("AMZN")),
+                AssetClass::Stock,
+                dec!(26.0),
+                dec!(100.0),
+            ),
+            CapitalDiff::loss(
+                Asset::Ticker(String::from("TSLA")),
+                AssetClass::Stock,
+                dec!(26.0),
+                dec!(-300.0),
+            ),
+        ]);
+        let after = Module730::prepare(
+            &[(Venue::Bitpanda, dec!(6000.0))],
+            365,
+            dec!(12.0),
+            &after_gains,
+            &LossNetting::new(&after_gains, &CarriedLosses::default(), 2022),
+        )
+        .unwrap();
+        let changes = before.compare(&after);
+        let change = |field: &str| changes.iter().find(|x| x.field == field).unwrap();
+        assert_eq!(change("RT21").delta(), dec!(300.0));
+        assert_eq!(change("RT22 - Col. 3").after, dec!(300.0));
+        assert_eq!(change("RT23 - Col. 2").after, Decimal::ZERO);
+        assert_eq!(change("RT23 - Col. 1").before, Decimal::ZERO);
+        assert_eq!(change("RT23 - Col. 1").after, dec!(200.0));
+        assert_eq!(change("RW1 - Col. 11").delta(), Decimal::ZERO);
+    }
+}
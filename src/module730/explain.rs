@@ -0,0 +1,83 @@
+//! # Explain
+//!
+//! This module exposes the derivation of the 730 fields, which tells how each value has been calculated
+
+use rust_decimal::Decimal;
+
+use crate::tax::CapitalDiff;
+
+/// Describes how the value of a 730 field has been calculated
+#[derive(Debug, Clone)]
+pub struct Derivation {
+    /// The name of the field (e.g. "RT23 - Col. 2")
+    pub field: String,
+    /// The value of the field
+    pub value: Decimal,
+    /// The formula used to calculate the value
+    pub formula: String,
+    /// The capital diffs which contributed to the value; each of them refers to its matched lots and so to the source trades
+    pub capitals: Vec<CapitalDiff>,
+}
+
+impl Derivation {
+    /// Instantiate a new `Derivation` without contributing capital diffs
+    pub fn new(field: impl ToString, value: Decimal, formula: impl ToString) -> Self {
+        Self {
+            field: field.to_string(),
+            value,
+            formula: formula.to_string(),
+            capitals: Vec::new(),
+        }
+    }
+
+    /// Set the capital diffs which contributed to the value
+    pub fn with_capitals<'a>(mut self, capitals: impl Iterator<Item = &'a CapitalDiff>) -> Self {
+        self.capitals = capitals.cloned().collect();
+        self
+    }
+
+    /// Returns the transaction IDs of the trades which contributed to the value
+    pub fn transaction_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self
+            .capitals
+            .iter()
+            .flat_map(|x| x.lots())
+            .flat_map(|x| {
+                [
+                    x.buy_transaction_id.as_str(),
+                    x.sell_transaction_id.as_str(),
+                ]
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+    use crate::tax::GainsAndLossesCalculator;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_collect_transaction_ids_of_derivation() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::external_deposit_mock();
+        let gains_and_losses = GainsAndLossesCalculator::default().calculate(&db).unwrap();
+        let derivation = Derivation::new("RT23 - Col. 2", dec!(4000.0), "somma")
+            .with_capitals(gains_and_losses.iter());
+        assert_eq!(derivation.capitals.len(), 1);
+        assert_eq!(
+            derivation.transaction_ids(),
+            vec![
+                "C00000000-0000-0000-0000-000000000011",
+                "T00000000-0000-0000-0000-000000000012"
+            ]
+        );
+    }
+}
@@ -4,15 +4,22 @@
 
 use rust_decimal::Decimal;
 
-use crate::tax::GainsAndLosses;
+use crate::database::Venue;
+use crate::tax::{GainsAndLosses, LossNetting};
 
+mod diff;
+mod explain;
 mod paginate;
 mod quadro_rt;
 mod quadro_rw;
+mod summary;
 
+pub use diff::FieldChange;
+pub use explain::Derivation;
 pub use paginate::{Paginate, Stdout};
 use quadro_rt::QuadroRt;
 use quadro_rw::QuadroRw;
+pub use summary::YearSummary;
 
 /// Module 730 data for investments gains
 #[derive(Debug)]
@@ -22,18 +29,28 @@ pub struct Module730 {
 }
 
 impl Module730 {
-    /// Instantiate a new `Module730`
+    /// Instantiate a new `Module730` from the average balance of each venue over `days` days, the gains and losses
+    /// of the year and their netting with the losses of the previous years
     pub fn prepare(
-        average_balance: Decimal,
+        average_balances: &[(Venue, Decimal)],
+        days: i64,
         ivafe: Decimal,
         gains_and_losses: &GainsAndLosses,
+        netting: &LossNetting,
     ) -> anyhow::Result<Self> {
         Ok(Self {
-            quadro_rt: QuadroRt::prepare(gains_and_losses),
-            quadro_rw: QuadroRw::prepare(average_balance, ivafe),
+            quadro_rt: QuadroRt::prepare(gains_and_losses, netting.carried_losses_used()),
+            quadro_rw: QuadroRw::prepare(average_balances, days, ivafe),
         })
     }
 
+    /// Iterate over the derivations of all the fields of the module
+    pub fn derivations(&self) -> impl Iterator<Item = &Derivation> {
+        self.quadro_rt
+            .derivations()
+            .chain(self.quadro_rw.derivations())
+    }
+
     /// Output the 730 columns using the provided paginator
     pub fn output(
         &self,
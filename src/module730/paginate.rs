@@ -2,7 +2,13 @@
 //!
 //! Paginate provides a trait and types to paginate the 730 data
 
-use super::{GainsAndLosses, Module730};
+use rust_decimal::Decimal;
+
+use super::{FieldChange, GainsAndLosses, Module730, YearSummary};
+use crate::tax::{
+    CostBasisReport, HarvestingAdvice, InventoryMismatch, OpenPositionsReport, SelfTransfersReport,
+    YearToDateEstimate,
+};
 
 mod stdout;
 
@@ -12,4 +18,40 @@ pub trait Paginate {
     /// Paginate module 730 to some kind of output
     fn paginate(&self, module: &Module730, gains_and_losses: &GainsAndLosses)
         -> anyhow::Result<()>;
+
+    /// Paginate the derivation of each field of module 730
+    fn paginate_explain(&self, module: &Module730) -> anyhow::Result<()>;
+
+    /// Paginate the withdrawals to the investor's own wallets and their deposits
+    fn paginate_self_transfers(&self, report: &SelfTransfersReport) -> anyhow::Result<()>;
+
+    /// Paginate the cost basis assigned to the crypto deposited from outside Bitpanda
+    fn paginate_cost_basis(&self, report: &CostBasisReport) -> anyhow::Result<()>;
+
+    /// Paginate the positions still open at the end of the year, with their unrealized gains and losses
+    fn paginate_open_positions(&self, report: &OpenPositionsReport) -> anyhow::Result<()>;
+
+    /// Paginate the differences between the lots held at the end of the year and the balances reported by the CSV
+    fn paginate_inventory_mismatches(&self, mismatches: &[InventoryMismatch])
+        -> anyhow::Result<()>;
+
+    /// Paginate the tax-loss harvesting advice
+    fn paginate_harvesting(&self, advice: &HarvestingAdvice) -> anyhow::Result<()>;
+
+    /// Paginate the changes to the 730 fields and to the total tax caused by hypothetical trades
+    fn paginate_what_if(
+        &self,
+        changes: &[FieldChange],
+        tax_before: Decimal,
+        tax_after: Decimal,
+    ) -> anyhow::Result<()>;
+
+    /// Paginate the tax estimate for the current, unfinished year
+    fn paginate_year_to_date(&self, estimate: &YearToDateEstimate) -> anyhow::Result<()>;
+
+    /// Paginate the header of the output of a year, when several years are processed
+    fn paginate_year_header(&self, year: i32) -> anyhow::Result<()>;
+
+    /// Paginate the summary of several years
+    fn paginate_summary(&self, summaries: &[YearSummary]) -> anyhow::Result<()>;
 }
@@ -2,7 +2,13 @@
 //!
 //! This module exposes the stdout paginator for 730
 
-use super::{GainsAndLosses, Module730, Paginate};
+use super::{
+    CostBasisReport, FieldChange, GainsAndLosses, HarvestingAdvice, InventoryMismatch, Module730,
+    OpenPositionsReport, Paginate, SelfTransfersReport, YearSummary,
+};
+use crate::tax::{CostBasisSource, YearToDateEstimate};
+
+use rust_decimal::Decimal;
 
 /// Stdout paginator
 #[derive(Default)]
@@ -19,6 +25,385 @@ impl Paginate for Stdout {
         self.print_quadro_rw(module);
         Ok(())
     }
+
+    fn paginate_explain(&self, module: &Module730) -> anyhow::Result<()> {
+        println!("DERIVAZIONE DEI CAMPI:");
+        println!();
+        for derivation in module.derivations() {
+            println!("{}: € {}", derivation.field, derivation.value);
+            println!("  formula: {}", derivation.formula);
+            for diff in derivation.capitals.iter() {
+                println!(
+                    "  {} {}: € {} ({} %)",
+                    if diff.is_gain() {
+                        "guadagno"
+                    } else {
+                        "perdita"
+                    },
+                    diff.asset(),
+                    diff.value().round_dp(2),
+                    diff.tax_percentage()
+                );
+                for lot in diff.lots() {
+                    println!(
+                        "    {} {} acquistati con {} il {} e venduti con {} il {}: costo € {}, ricavo € {}",
+                        lot.quantity,
+                        lot.asset,
+                        lot.buy_transaction_id,
+                        lot.acquired_at.date_naive(),
+                        lot.sell_transaction_id,
+                        lot.disposed_at.date_naive(),
+                        lot.cost.round_dp(2),
+                        lot.proceeds.round_dp(2)
+                    );
+                }
+            }
+            let transaction_ids = derivation.transaction_ids();
+            if !transaction_ids.is_empty() {
+                println!("  operazioni: {}", transaction_ids.join(", "));
+            }
+            println!();
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_self_transfers(&self, report: &SelfTransfersReport) -> anyhow::Result<()> {
+        if report.matched.is_empty()
+            && report.unmatched_withdrawals.is_empty()
+            && report.unmatched_deposits.is_empty()
+            && report.rejected.is_empty()
+        {
+            return Ok(());
+        }
+        println!("TRASFERIMENTI VERSO WALLET PERSONALI:");
+        println!();
+        for transfer in report.matched.iter() {
+            println!(
+                "{} {} prelevati con {} e ridepositati con {} ({} {}; costo € {})",
+                transfer.parked.amount_asset(),
+                transfer.parked.asset,
+                transfer.parked.withdrawal,
+                transfer.deposit,
+                transfer.amount_asset,
+                transfer.parked.asset,
+                transfer.parked.amount_fiat().round_dp(2)
+            );
+        }
+        for parked in report.unmatched_withdrawals.iter() {
+            println!(
+                "ATTENZIONE: {} {} prelevati con {} il {} non sono mai stati ridepositati (costo € {})",
+                parked.amount_asset(),
+                parked.asset,
+                parked.withdrawal,
+                parked.timestamp.date_naive(),
+                parked.amount_fiat().round_dp(2)
+            );
+        }
+        for deposit in report.unmatched_deposits.iter() {
+            println!(
+                "ATTENZIONE: il deposito {} non corrisponde a nessun prelievo",
+                deposit
+            );
+        }
+        for rejected in report.rejected.iter() {
+            println!(
+                "ATTENZIONE: il deposito {} riporta {} {}, ma con {} ne sono stati prelevati {}; non è stato associato al prelievo",
+                rejected.deposit,
+                rejected.deposited,
+                rejected.asset,
+                rejected.withdrawal,
+                rejected.withdrawn
+            );
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_cost_basis(&self, report: &CostBasisReport) -> anyhow::Result<()> {
+        if report.deposits.is_empty() {
+            return Ok(());
+        }
+        println!("DEPOSITI DA WALLET ESTERNI:");
+        println!();
+        for deposit in report.deposits.iter() {
+            let source = match deposit.source {
+                CostBasisSource::Declared => "dichiarato",
+                CostBasisSource::MarketValue => "valore di mercato al deposito",
+                CostBasisSource::Missing => "non disponibile",
+            };
+            println!(
+                "{} {} depositati con {}: costo € {} ({}), acquistati il {}",
+                deposit.amount_asset,
+                deposit.asset,
+                deposit.deposit,
+                deposit.cost.round_dp(2),
+                source,
+                deposit.acquired_at.date_naive()
+            );
+        }
+        if report.undeclared().next().is_some() {
+            println!();
+            println!("############################################");
+            println!("# ATTENZIONE: COSTO DI ACQUISTO NON DICHIARATO");
+            println!("############################################");
+            for deposit in report.undeclared() {
+                println!(
+                    "# il deposito {} ({} {}) non ha un costo dichiarato; usati € {}",
+                    deposit.deposit,
+                    deposit.amount_asset,
+                    deposit.asset,
+                    deposit.cost.round_dp(2)
+                );
+            }
+            println!("# dichiara il costo con l'opzione --cost-basis per evitare di pagare tasse non dovute");
+            println!("############################################");
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_open_positions(&self, report: &OpenPositionsReport) -> anyhow::Result<()> {
+        if report.positions.is_empty() {
+            return Ok(());
+        }
+        println!("POSIZIONI APERTE A FINE ANNO:");
+        println!();
+        for position in report.positions.iter() {
+            match (
+                position.market_value(),
+                position.unrealized(),
+                position.latent_tax(),
+            ) {
+                (Some(value), Some(diff), Some(tax)) => println!(
+                    "{} {}: costo € {}, valore di mercato € {}, {} latente € {}, tasse latenti € {} ({} %)",
+                    position.quantity,
+                    position.asset,
+                    position.cost.round_dp(2),
+                    value.round_dp(2),
+                    if diff.is_sign_negative() { "perdita" } else { "guadagno" },
+                    diff.round_dp(2),
+                    tax.round_dp(2),
+                    position.tax_percentage
+                ),
+                _ => println!(
+                    "{} {}: costo € {}, valore di mercato non disponibile",
+                    position.quantity,
+                    position.asset,
+                    position.cost.round_dp(2)
+                ),
+            }
+        }
+        println!();
+        println!(
+            "Costo totale: € {}; guadagno/perdita latente: € {}; tasse latenti: € {}",
+            report.cost().round_dp(2),
+            report.unrealized().round_dp(2),
+            report.latent_tax().round_dp(2)
+        );
+        if report.unpriced().next().is_some() {
+            println!("ATTENZIONE: i totali escludono le posizioni senza valore di mercato");
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_inventory_mismatches(
+        &self,
+        mismatches: &[InventoryMismatch],
+    ) -> anyhow::Result<()> {
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        println!("############################################");
+        println!("# ATTENZIONE: INVENTARIO NON CORRISPONDENTE AI SALDI DEL CSV");
+        println!("############################################");
+        for mismatch in mismatches.iter() {
+            println!(
+                "# {}: {} secondo l'inventario, {} secondo il CSV",
+                mismatch.asset, mismatch.inventory, mismatch.csv
+            );
+        }
+        println!("# verifica che l'inventario di apertura sia l'inventario di chiusura dell'anno precedente");
+        println!("############################################");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_harvesting(&self, advice: &HarvestingAdvice) -> anyhow::Result<()> {
+        println!("OTTIMIZZAZIONE DELLE MINUSVALENZE:");
+        println!();
+        println!(
+            "Plusvalenze compensabili (26%): € {}",
+            advice.offsettable_gains.round_dp(2)
+        );
+        println!(
+            "Plusvalenze non compensabili (ETF): € {}",
+            advice.non_offsettable_gains.round_dp(2)
+        );
+        println!(
+            "Minusvalenze realizzate: € {}",
+            advice.realized_losses.round_dp(2)
+        );
+        println!(
+            "Minusvalenze pregresse utilizzate: € {} (disponibili: € {})",
+            advice.carried_losses_used.round_dp(2),
+            advice.carried_losses_available.round_dp(2)
+        );
+        println!(
+            "Plusvalenze ancora tassabili: € {}",
+            advice.taxable_gains.round_dp(2)
+        );
+        println!();
+        if advice.suggestions.is_empty() {
+            println!("nessuna posizione in perdita può ridurre le plusvalenze tassabili");
+        }
+        for suggestion in advice.suggestions.iter() {
+            println!(
+                "vendendo {} {} realizzeresti una perdita di € {}, compensando € {} di plusvalenze e risparmiando € {} di tasse",
+                suggestion.quantity,
+                suggestion.asset,
+                suggestion.unrealized_loss.round_dp(2),
+                suggestion.offset.round_dp(2),
+                suggestion.tax_saving.round_dp(2)
+            );
+        }
+        if !advice.suggestions.is_empty() {
+            println!("Risparmio totale: € {}", advice.tax_saving().round_dp(2));
+        }
+        for expiring in advice.expiring.iter() {
+            println!(
+                "ATTENZIONE: le minusvalenze del {} scadono il 31/12/{}; € {} non ancora utilizzati",
+                expiring.loss.year,
+                expiring.loss.expires_at(),
+                expiring.unused.round_dp(2)
+            );
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_what_if(
+        &self,
+        changes: &[FieldChange],
+        tax_before: Decimal,
+        tax_after: Decimal,
+    ) -> anyhow::Result<()> {
+        println!("SIMULAZIONE CON LE OPERAZIONI IPOTETICHE:");
+        println!();
+        for change in changes.iter() {
+            println!(
+                "{}: € {} => € {} ({:+})",
+                change.field,
+                change.before,
+                change.after,
+                change.delta().round_dp(2)
+            );
+        }
+        println!();
+        println!(
+            "Totale tasse: € {} => € {} ({:+})",
+            tax_before.round_dp(2),
+            tax_after.round_dp(2),
+            (tax_after - tax_before).round_dp(2)
+        );
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_year_to_date(&self, estimate: &YearToDateEstimate) -> anyhow::Result<()> {
+        println!(
+            "STIMA DELLE TASSE AL {} (giorno {} di {}):",
+            estimate.date.format("%d/%m/%Y"),
+            estimate.elapsed_days,
+            estimate.year_days
+        );
+        println!();
+        println!(
+            "Giacenza media ad oggi: € {}; saldo attuale: € {}",
+            estimate.average_balance.round_dp(2),
+            estimate.current_balance.round_dp(2)
+        );
+        println!(
+            "IVAFE maturata ad oggi: € {}",
+            estimate.ivafe_to_date.round_dp(2)
+        );
+        println!(
+            "Tasse sulle plusvalenze realizzate ad oggi: € {}",
+            estimate.realized_tax.round_dp(2)
+        );
+        println!();
+        println!("Proiezione a fine anno, ai prezzi attuali:");
+        println!(
+            "Giacenza media: € {}; IVAFE: € {}",
+            estimate.projected_average_balance.round_dp(2),
+            estimate.projected_ivafe.round_dp(2)
+        );
+        println!(
+            "Tasse da accantonare mantenendo le posizioni aperte: € {}",
+            estimate.projected_liabilities().round_dp(2)
+        );
+        println!(
+            "Tasse da accantonare vendendo tutte le posizioni aperte: € {}",
+            estimate.projected_liabilities_if_sold().round_dp(2)
+        );
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_year_header(&self, year: i32) -> anyhow::Result<()> {
+        println!("============================================");
+        println!("ANNO {}", year);
+        println!("============================================");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_summary(&self, summaries: &[YearSummary]) -> anyhow::Result<()> {
+        println!("RIEPILOGO:");
+        println!();
+        println!(
+            "{:<6} {:>14} {:>14} {:>14} {:>10} {:>14} {:>20}",
+            "Anno",
+            "Plusvalenze",
+            "Minusvalenze",
+            "Imposta",
+            "IVAFE",
+            "Totale tasse",
+            "Minusvalenze residue"
+        );
+        for summary in summaries.iter() {
+            println!(
+                "{:<6} {:>14} {:>14} {:>14} {:>10} {:>14} {:>20}",
+                summary.year,
+                summary.gains.round_dp(2),
+                summary.losses.round_dp(2),
+                summary.capital_gains_tax.round_dp(2),
+                summary.ivafe.round_dp(2),
+                summary.total_tax().round_dp(2),
+                summary.carried_losses.round_dp(2)
+            );
+        }
+        println!();
+        println!(
+            "Totale tasse: € {}",
+            summaries
+                .iter()
+                .map(|x| x.total_tax())
+                .sum::<Decimal>()
+                .round_dp(2)
+        );
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
 }
 
 impl Stdout {
@@ -51,23 +436,13 @@ impl Stdout {
         println!("QUADRO RT:");
         println!();
         println!("Sezione I:");
-        println!("RT1: € {}", module.quadro_rt.sezione_1.rt1);
-        println!("RT2 - Col. 3: € {}", module.quadro_rt.sezione_1.rt2_col3);
-        if let Some(col) = module.quadro_rt.sezione_1.rt3_col1 {
-            println!("RT3 - Col. 1: € {}", col);
-        }
-        if let Some(col) = module.quadro_rt.sezione_1.rt3_col2 {
-            println!("RT3 - Col. 2: € {}", col);
+        for field in module.quadro_rt.sezione_1.derivations() {
+            println!("{}: € {}", field.field, field.value);
         }
         println!();
         println!("Sezione II:");
-        println!("RT21: € {}", module.quadro_rt.sezione_2.rt21);
-        println!("RT22 - Col. 3: € {}", module.quadro_rt.sezione_2.rt22_col3);
-        if let Some(col) = module.quadro_rt.sezione_2.rt23_col1 {
-            println!("RT23 - Col. 1: € {}", col);
-        }
-        if let Some(col) = module.quadro_rt.sezione_2.rt23_col2 {
-            println!("RT23 - Col. 2: € {}", col);
+        for field in module.quadro_rt.sezione_2.derivations() {
+            println!("{}: € {}", field.field, field.value);
         }
         println!("--------------------------------------------");
         println!();
@@ -76,11 +451,21 @@ impl Stdout {
     fn print_quadro_rw(&self, module: &Module730) {
         println!("QUADRO RW:");
         println!();
-        println!(
-            "RW1 - Col.8: € {} (giacenza media)",
-            module.quadro_rw.rw1_column8
-        );
-        println!("RW1 - Col.11: € {} (IVAFE)", module.quadro_rw.rw1_column11);
+        for (i, row) in module.quadro_rw.rows.iter().enumerate() {
+            let line = i + 1;
+            println!(
+                "RW{} - Col.4: {} ({}, {})",
+                line,
+                row.column4,
+                row.venue.country(),
+                row.venue
+            );
+            println!(
+                "RW{} - Col.8: € {} (giacenza media)",
+                line, row.column8.value
+            );
+            println!("RW{} - Col.11: € {} (IVAFE)", line, row.column11.value);
+        }
         println!("--------------------------------------------");
         println!();
     }
@@ -4,6 +4,7 @@
 
 use rust_decimal::Decimal;
 
+use super::Derivation;
 use crate::tax::{CapitalDiff, GainsAndLosses};
 
 /// Quadro RT - Plusvalenze di natura finanziaria
@@ -15,34 +16,39 @@ pub struct QuadroRt {
     pub sezione_2: Sezione2,
 }
 
-/// Sezione I - Plusvalenze assoggettate ad imposta sostitutiva del 12.5%
+/// Sezione I - Plusvalenze assoggettate ad imposta sostitutiva del 12.5%.
+/// Each field comes with its derivation
 #[derive(Debug)]
 pub struct Sezione1 {
     /// Corrispettivo incassato (gain + loss)
-    pub rt1: Decimal,
+    pub rt1: Derivation,
     /// Valore fiscale riconosciuto alla partecipazione; total loss
-    pub rt2_col3: Decimal,
-    /// Minusvalenza (RT1 - RT2); only if < 0
-    pub rt3_col1: Option<Decimal>,
-    /// Plusvalenza (RT1 - RT2); only if > 0
-    pub rt3_col2: Option<Decimal>,
+    pub rt2_col3: Derivation,
+    /// Minusvalenza (sum of the gains and of the losses); only if < 0
+    pub rt3_col1: Option<Derivation>,
+    /// Plusvalenza (sum of the gains and of the losses); only if > 0
+    pub rt3_col2: Option<Derivation>,
 }
 
-/// Sezione II - Plusvalenze assoggettate ad imposta sostitutiva del 26%
+/// Sezione II - Plusvalenze assoggettate ad imposta sostitutiva del 26%.
+/// Each field comes with its derivation
 #[derive(Debug)]
 pub struct Sezione2 {
     /// Corrispettivo incassato (gain + loss)
-    pub rt21: Decimal,
+    pub rt21: Derivation,
     /// Valore fiscale riconosciuto alla partecipazione; loss
-    pub rt22_col3: Decimal,
-    /// Minusvalenza (RT21 - RT22); only if < 0
-    pub rt23_col1: Option<Decimal>,
-    /// Plusvalenza (RT21 - RT22); only if > 0
-    pub rt23_col2: Option<Decimal>,
+    pub rt22_col3: Derivation,
+    /// Minusvalenza (sum of the gains and of the losses); only if < 0
+    pub rt23_col1: Option<Derivation>,
+    /// Plusvalenza (sum of the gains and of the losses); only if > 0
+    pub rt23_col2: Option<Derivation>,
+    /// Minusvalenze degli anni precedenti, used to offset the plusvalenza; only if > 0
+    pub rt24: Option<Derivation>,
 }
 
 impl QuadroRt {
-    pub fn prepare(gains_and_losses: &GainsAndLosses) -> Self {
+    /// Prepare the quadro from the gains and losses of the year and the losses of the previous years used to offset them
+    pub fn prepare(gains_and_losses: &GainsAndLosses, carried_losses_used: Decimal) -> Self {
         Self {
             sezione_1: Sezione1::prepare(
                 gains_and_losses
@@ -59,71 +65,145 @@ impl QuadroRt {
                     .cloned()
                     .collect::<Vec<CapitalDiff>>()
                     .into(),
+                carried_losses_used,
             ),
         }
     }
+
+    /// Iterate over the derivations of the populated fields
+    pub fn derivations(&self) -> impl Iterator<Item = &Derivation> {
+        self.sezione_1
+            .derivations()
+            .chain(self.sezione_2.derivations())
+    }
 }
 
 impl Sezione1 {
     pub fn prepare(gains_and_losses_12_percent: GainsAndLosses) -> Self {
-        let total_sold = gains_and_losses_12_percent
-            .iter()
-            .map(|x| x.value().abs())
-            .sum::<Decimal>()
-            .round_dp(2);
-        let loss = gains_and_losses_12_percent
-            .iter()
-            .filter(|x| x.is_loss())
-            .map(|x| x.value().abs())
-            .sum::<Decimal>()
-            .round_dp(2);
-        let diff = gains_and_losses_12_percent
-            .iter()
-            .map(|x| x.value())
-            .sum::<Decimal>()
-            .round_dp(2);
-        let rt3 = if diff.is_sign_negative() {
-            (Some(diff.abs()), None)
+        let fields = SectionFields::prepare(
+            &gains_and_losses_12_percent,
+            ["RT1", "RT2 - Col. 3", "RT3 - Col. 1", "RT3 - Col. 2"],
+        );
+        Self {
+            rt1: fields.sold,
+            rt2_col3: fields.loss,
+            rt3_col1: fields.minus,
+            rt3_col2: fields.plus,
+        }
+    }
+
+    /// Iterate over the derivations of the populated fields
+    pub fn derivations(&self) -> impl Iterator<Item = &Derivation> {
+        [
+            Some(&self.rt1),
+            Some(&self.rt2_col3),
+            self.rt3_col1.as_ref(),
+            self.rt3_col2.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl Sezione2 {
+    pub fn prepare(
+        gains_and_losses_26_percent: GainsAndLosses,
+        carried_losses_used: Decimal,
+    ) -> Self {
+        let fields = SectionFields::prepare(
+            &gains_and_losses_26_percent,
+            ["RT21", "RT22 - Col. 3", "RT23 - Col. 1", "RT23 - Col. 2"],
+        );
+        let carried_losses_used = carried_losses_used.round_dp(2);
+        let rt24 = if carried_losses_used.is_zero() {
+            None
         } else {
-            (None, Some(diff))
+            Some(Derivation::new(
+                "RT24",
+                carried_losses_used,
+                "minusvalenze degli anni precedenti ancora utilizzabili, fino alla plusvalenza residua",
+            ))
         };
         Self {
-            rt1: total_sold,
-            rt2_col3: loss,
-            rt3_col1: rt3.0,
-            rt3_col2: rt3.1,
+            rt21: fields.sold,
+            rt22_col3: fields.loss,
+            rt23_col1: fields.minus,
+            rt23_col2: fields.plus,
+            rt24,
         }
     }
+
+    /// Iterate over the derivations of the populated fields
+    pub fn derivations(&self) -> impl Iterator<Item = &Derivation> {
+        [
+            Some(&self.rt21),
+            Some(&self.rt22_col3),
+            self.rt23_col1.as_ref(),
+            self.rt23_col2.as_ref(),
+            self.rt24.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
 }
 
-impl Sezione2 {
-    pub fn prepare(gains_and_losses_26_percent: GainsAndLosses) -> Self {
-        let total_sold = gains_and_losses_26_percent
+/// The "corrispettivo", "valore fiscale", "minusvalenza" and "plusvalenza" fields of a section
+struct SectionFields {
+    sold: Derivation,
+    loss: Derivation,
+    minus: Option<Derivation>,
+    plus: Option<Derivation>,
+}
+
+impl SectionFields {
+    /// Calculate the fields of a section, named after `fields`, from its gains and losses.
+    /// Only one of "minusvalenza" and "plusvalenza" is populated, depending on the sign of the sum
+    fn prepare(gains_and_losses: &GainsAndLosses, fields: [&str; 4]) -> Self {
+        let [sold_field, loss_field, minus_field, plus_field] = fields;
+        let total_sold = gains_and_losses
             .iter()
             .map(|x| x.value().abs())
             .sum::<Decimal>()
             .round_dp(2);
-        let loss = gains_and_losses_26_percent
+        let loss = gains_and_losses
             .iter()
             .filter(|x| x.is_loss())
             .map(|x| x.value().abs())
             .sum::<Decimal>()
             .round_dp(2);
-        let diff = gains_and_losses_26_percent
+        let diff = gains_and_losses
             .iter()
             .map(|x| x.value())
             .sum::<Decimal>()
             .round_dp(2);
-        let rt23 = if diff.is_sign_negative() {
-            (Some(diff.abs()), None)
+        let (minus, plus) = if diff.is_sign_negative() {
+            let minus = Derivation::new(
+                minus_field,
+                diff.abs(),
+                "somma algebrica di guadagni e perdite (valore assoluto, se negativa)",
+            )
+            .with_capitals(gains_and_losses.iter());
+            (Some(minus), None)
         } else {
-            (None, Some(diff))
+            let plus = Derivation::new(
+                plus_field,
+                diff,
+                "somma algebrica di guadagni e perdite (se positiva)",
+            )
+            .with_capitals(gains_and_losses.iter());
+            (None, Some(plus))
         };
         Self {
-            rt21: total_sold,
-            rt22_col3: loss,
-            rt23_col1: rt23.0,
-            rt23_col2: rt23.1,
+            sold: Derivation::new(
+                sold_field,
+                total_sold,
+                "somma dei valori assoluti di guadagni e perdite",
+            )
+            .with_capitals(gains_and_losses.iter()),
+            loss: Derivation::new(loss_field, loss, "somma dei valori assoluti delle perdite")
+                .with_capitals(gains_and_losses.iter().filter(|x| x.is_loss())),
+            minus,
+            plus,
         }
     }
 }
@@ -139,16 +219,65 @@ mod test {
     #[test]
     fn should_prepare_quadro_rt() {
         crate::mock::log();
-        let quadro_rt = QuadroRt::prepare(&gains_and_losses());
-        assert_eq!(quadro_rt.sezione_1.rt1, dec!(680.0));
-        assert_eq!(quadro_rt.sezione_1.rt2_col3, dec!(80.0));
-        assert_eq!(quadro_rt.sezione_1.rt3_col1, None);
-        assert_eq!(quadro_rt.sezione_1.rt3_col2, Some(dec!(520.0)));
-
-        assert_eq!(quadro_rt.sezione_2.rt21, dec!(632.0));
-        assert_eq!(quadro_rt.sezione_2.rt22_col3, dec!(32.0));
-        assert_eq!(quadro_rt.sezione_2.rt23_col1, None);
-        assert_eq!(quadro_rt.sezione_2.rt23_col2, Some(dec!(568.0)));
+        let quadro_rt = QuadroRt::prepare(&gains_and_losses(), Decimal::ZERO);
+        let value = |field: Option<&Derivation>| field.map(|x| x.value);
+        assert_eq!(quadro_rt.sezione_1.rt1.value, dec!(680.0));
+        assert_eq!(quadro_rt.sezione_1.rt2_col3.value, dec!(80.0));
+        assert_eq!(value(quadro_rt.sezione_1.rt3_col1.as_ref()), None);
+        assert_eq!(
+            value(quadro_rt.sezione_1.rt3_col2.as_ref()),
+            Some(dec!(520.0))
+        );
+
+        assert_eq!(quadro_rt.sezione_2.rt21.value, dec!(632.0));
+        assert_eq!(quadro_rt.sezione_2.rt22_col3.value, dec!(32.0));
+        assert_eq!(value(quadro_rt.sezione_2.rt23_col1.as_ref()), None);
+        assert_eq!(
+            value(quadro_rt.sezione_2.rt23_col2.as_ref()),
+            Some(dec!(568.0))
+        );
+        assert!(quadro_rt.sezione_2.rt24.is_none());
+    }
+
+    #[test]
+    fn should_report_carried_losses_used() {
+        crate::mock::log();
+        let quadro_rt = QuadroRt::prepare(&gains_and_losses(), dec!(150.0));
+        let rt24 = quadro_rt.sezione_2.rt24.as_ref().unwrap();
+        assert_eq!(rt24.field.as_str(), "RT24");
+        assert_eq!(rt24.value, dec!(150.0));
+        assert_eq!(
+            quadro_rt.derivations().last().unwrap().field.as_str(),
+            "RT24"
+        );
+    }
+
+    #[test]
+    fn should_explain_quadro_rt() {
+        crate::mock::log();
+        let quadro_rt = QuadroRt::prepare(&gains_and_losses(), Decimal::ZERO);
+        // a derivation for each populated field
+        let fields: Vec<&str> = quadro_rt.derivations().map(|x| x.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "RT1",
+                "RT2 - Col. 3",
+                "RT3 - Col. 2",
+                "RT21",
+                "RT22 - Col. 3",
+                "RT23 - Col. 2"
+            ]
+        );
+        let sezione_2 = &quadro_rt.sezione_2;
+        assert_eq!(sezione_2.rt21.capitals.len(), 3);
+        assert_eq!(sezione_2.rt22_col3.capitals.len(), 1);
+        let rt23_col2 = sezione_2.rt23_col2.as_ref().unwrap();
+        assert_eq!(rt23_col2.value, dec!(568.0));
+        assert_eq!(
+            rt23_col2.formula,
+            "somma algebrica di guadagni e perdite (se positiva)"
+        );
     }
 
     fn gains_and_losses() -> GainsAndLosses {
@@ -4,26 +4,78 @@
 
 use rust_decimal::Decimal;
 
+use super::Derivation;
+use crate::database::Venue;
+
 /// According to the 730:
 ///
 /// > il quadro RW è quello dedicato al monitoraggio degli investimenti patrimoniali e
-/// delle attività finanziarie detenuti all’estero da persone fisiche, enti non commerciali
-/// con sede in Italia e società semplici.
+/// > delle attività finanziarie detenuti all’estero da persone fisiche, enti non commerciali
+/// > con sede in Italia e società semplici.
 ///
 /// Ref: <https://il730.online/come-compilare-il-quadro-rw-del-modello-redditi-pf-2022/>
 #[derive(Debug)]
 pub struct QuadroRw {
-    pub rw1_column8: Decimal,
+    /// A row for each venue where the investor holds assets
+    pub rows: Vec<QuadroRwRow>,
+}
+
+/// A row of the quadro RW, which refers to the assets held at a venue.
+/// Each calculated column comes with its derivation
+#[derive(Debug)]
+pub struct QuadroRwRow {
+    /// The venue where the assets are held
+    pub venue: Venue,
+    /// codice dello Stato estero in cui sono detenute le attività
+    pub column4: &'static str,
+    /// valore medio delle attività detenute nell'anno
+    pub column8: Derivation,
     /// indicare il valore dell’IVAFE calcolata dal rapporto tra valore inserito nella colonna 8 alla quota e al periodo di detenzione.
-    pub rw1_column11: Decimal,
+    pub column11: Derivation,
 }
 
 impl QuadroRw {
-    pub fn prepare(avg_balance: Decimal, ivafe: Decimal) -> Self {
-        Self {
-            rw1_column8: avg_balance.round_dp(2),
-            rw1_column11: ivafe.round_dp(2),
+    /// Prepare the quadro RW from the average balance of each venue, calculated over `days` days.
+    /// The IVAFE is split among the venues proportionally to their average balance
+    pub fn prepare(balances: &[(Venue, Decimal)], days: i64, ivafe: Decimal) -> Self {
+        let total_balance: Decimal = balances.iter().map(|(_, balance)| *balance).sum();
+        let mut remaining_ivafe = ivafe;
+        let last = balances.len().saturating_sub(1);
+        let mut rows = Vec::with_capacity(balances.len());
+        for (i, (venue, balance)) in balances.iter().enumerate() {
+            let venue_ivafe = if i == last || total_balance.is_zero() {
+                remaining_ivafe
+            } else {
+                (ivafe * balance / total_balance).round_dp(2)
+            };
+            remaining_ivafe -= venue_ivafe;
+            let line = i + 1;
+            rows.push(QuadroRwRow {
+                venue: *venue,
+                column4: venue.country_code(),
+                column8: Derivation::new(
+                    format!("RW{} - Col. 8", line),
+                    balance.round_dp(2),
+                    format!(
+                        "somma delle giacenze giornaliere (FIAT e asset) su {} diviso {}",
+                        venue, days
+                    ),
+                ),
+                column11: Derivation::new(
+                    format!("RW{} - Col. 11", line),
+                    venue_ivafe.round_dp(2),
+                    "IVAFE totale (giacenza media totale x 0.2%, 0 se inferiore a € 5000) in proporzione a RW - Col. 8",
+                ),
+            });
         }
+        Self { rows }
+    }
+
+    /// Iterate over the derivations of the populated fields
+    pub fn derivations(&self) -> impl Iterator<Item = &Derivation> {
+        self.rows
+            .iter()
+            .flat_map(|row| [&row.column8, &row.column11])
     }
 }
 
@@ -37,8 +89,44 @@ mod test {
     #[test]
     fn should_prepare_quadro_rw() {
         crate::mock::log();
-        let quadro = QuadroRw::prepare(dec!(13171.0), dec!(26.342));
-        assert_eq!(quadro.rw1_column8, dec!(13171.0));
-        assert_eq!(quadro.rw1_column11, dec!(26.34));
+        let quadro = QuadroRw::prepare(&[(Venue::Bitpanda, dec!(13171.0))], 365, dec!(26.342));
+        assert_eq!(quadro.rows.len(), 1);
+        assert_eq!(quadro.rows[0].column4, "008");
+        assert_eq!(quadro.rows[0].column8.value, dec!(13171.0));
+        assert_eq!(quadro.rows[0].column11.value, dec!(26.34));
+        assert_eq!(
+            quadro.rows[0].column8.formula,
+            "somma delle giacenze giornaliere (FIAT e asset) su bitpanda diviso 365"
+        );
+    }
+
+    #[test]
+    fn should_split_ivafe_among_venues() {
+        crate::mock::log();
+        let quadro = QuadroRw::prepare(
+            &[
+                (Venue::Bitpanda, dec!(7500.0)),
+                (Venue::Kraken, dec!(2500.0)),
+            ],
+            366,
+            dec!(20.0),
+        );
+        assert_eq!(quadro.rows.len(), 2);
+        assert_eq!(quadro.rows[0].column11.value, dec!(15.0));
+        assert_eq!(quadro.rows[1].venue, Venue::Kraken);
+        assert_eq!(quadro.rows[1].column4, "040");
+        assert_eq!(quadro.rows[1].column8.value, dec!(2500.0));
+        assert_eq!(quadro.rows[1].column11.value, dec!(5.0));
+        let fields: Vec<&str> = quadro.derivations().map(|x| x.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "RW1 - Col. 8",
+                "RW1 - Col. 11",
+                "RW2 - Col. 8",
+                "RW2 - Col. 11"
+            ]
+        );
+        assert!(quadro.rows[1].column8.formula.ends_with("diviso 366"));
     }
 }
@@ -0,0 +1,46 @@
+//! # Summary
+//!
+//! This module exposes the summary of the taxes of a year, used to compare several years
+
+use rust_decimal::Decimal;
+
+use crate::tax::GainsAndLosses;
+
+/// The summary of the taxes of a year
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YearSummary {
+    pub year: i32,
+    /// Realized gains
+    pub gains: Decimal,
+    /// Realized losses (negative)
+    pub losses: Decimal,
+    /// Tax on the capital gains, net of the carried losses used
+    pub capital_gains_tax: Decimal,
+    pub ivafe: Decimal,
+    /// Losses which can be carried to the next year
+    pub carried_losses: Decimal,
+}
+
+impl YearSummary {
+    pub fn new(
+        year: i32,
+        gains_and_losses: &GainsAndLosses,
+        capital_gains_tax: Decimal,
+        ivafe: Decimal,
+        carried_losses: Decimal,
+    ) -> Self {
+        Self {
+            year,
+            gains: gains_and_losses.gains_value(),
+            losses: gains_and_losses.losses_value(),
+            capital_gains_tax,
+            ivafe,
+            carried_losses,
+        }
+    }
+
+    /// Returns the total amount of taxes to pay
+    pub fn total_tax(&self) -> Decimal {
+        self.capital_gains_tax + self.ivafe
+    }
+}
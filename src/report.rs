@@ -0,0 +1,45 @@
+//! # Report
+//!
+//! This module exposes the traits to paginate the reports which are not part of module 730:
+//! the profile of the taxpayer, the reports on the trades and the validation of the trades
+
+use std::path::Path;
+
+use crate::database::{
+    AdjustmentsReport, AnonymizeReport, MappingReport, MergeReport, Profile, TradeSummary,
+};
+use crate::tax::ValidationReport;
+
+mod stdout;
+
+pub use stdout::Stdout;
+
+/// Paginate the profile of the taxpayer
+pub trait PaginateProfile {
+    /// Paginate the profile of the taxpayer, which heads the reports
+    fn paginate_profile(&self, profile: &Profile) -> anyhow::Result<()>;
+}
+
+/// Paginate the reports on the trades read, merged, adjusted or selected
+pub trait PaginateTrades {
+    /// Paginate the report of the merge of several CSV files
+    fn paginate_merge_report(&self, report: &MergeReport) -> anyhow::Result<()>;
+
+    /// Paginate the manual adjustments applied to the trades
+    fn paginate_adjustments(&self, report: &AdjustmentsReport) -> anyhow::Result<()>;
+
+    /// Paginate the trades read through a CSV mapping and the rows which couldn't be mapped
+    fn paginate_mapping(&self, report: &MappingReport) -> anyhow::Result<()>;
+
+    /// Paginate the trades selected by a query and their totals
+    fn paginate_query(&self, summary: &TradeSummary) -> anyhow::Result<()>;
+
+    /// Paginate the outcome of the anonymization of a CSV written to `output`
+    fn paginate_anonymized(&self, report: &AnonymizeReport, output: &Path) -> anyhow::Result<()>;
+}
+
+/// Paginate the validation of the trades
+pub trait PaginateValidation {
+    /// Paginate the problems found validating the trades
+    fn paginate_validation(&self, report: &ValidationReport) -> anyhow::Result<()>;
+}
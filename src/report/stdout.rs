@@ -0,0 +1,272 @@
+//! # Stdout
+//!
+//! This module exposes the stdout paginator for the reports
+
+use super::{PaginateProfile, PaginateTrades, PaginateValidation};
+use crate::database::{
+    AdjustmentKind, AdjustmentsReport, AnonymizeReport, MappingReport, MergeReport, Profile,
+    TradeSummary,
+};
+use crate::tax::{ValidationIssueKind, ValidationReport};
+use crate::timezone;
+
+use std::path::Path;
+
+/// Stdout paginator
+#[derive(Default)]
+pub struct Stdout;
+
+impl PaginateProfile for Stdout {
+    fn paginate_profile(&self, profile: &Profile) -> anyhow::Result<()> {
+        println!("CONTRIBUENTE:");
+        println!();
+        if let Some(name) = profile.name.as_deref() {
+            println!("Nome: {}", name);
+        }
+        if let Some(birth_date) = profile.birth_date {
+            println!("Data di nascita: {}", birth_date.format("%d/%m/%Y"));
+        }
+        if let Some(codice_fiscale) = profile.codice_fiscale.as_deref() {
+            println!("Codice fiscale: {}", codice_fiscale);
+        }
+        if let Some(email) = profile.email.as_deref() {
+            println!("Email: {}", email);
+        }
+        if let Some(opened_at) = profile.account_opened_at {
+            println!("Conto Bitpanda aperto il: {}", opened_at.format("%d/%m/%Y"));
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+}
+
+impl PaginateTrades for Stdout {
+    fn paginate_merge_report(&self, report: &MergeReport) -> anyhow::Result<()> {
+        println!("FILE CSV UNITI:");
+        println!();
+        for source in report.sources.iter() {
+            println!(
+                "{}: {} operazioni, di cui {} già presenti",
+                source.path.display(),
+                source.trades,
+                source.duplicates
+            );
+        }
+        println!(
+            "Totale operazioni duplicate ignorate: {}",
+            report.duplicates()
+        );
+        if !report.conflicts.is_empty() {
+            println!();
+            println!("ATTENZIONE: operazioni con lo stesso ID ma contenuto diverso:");
+            for conflict in report.conflicts.iter() {
+                println!(
+                    "{} ({}): mantenuta quella di {}, ignorata quella di {}",
+                    conflict.transaction_id,
+                    conflict.venue,
+                    conflict.kept.display(),
+                    conflict.discarded.display()
+                );
+            }
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_adjustments(&self, report: &AdjustmentsReport) -> anyhow::Result<()> {
+        let kind = |kind: AdjustmentKind| match kind {
+            AdjustmentKind::Add => "aggiunta",
+            AdjustmentKind::Drop => "rimossa",
+            AdjustmentKind::Override => "modificata",
+        };
+        println!("RETTIFICHE MANUALI:");
+        println!();
+        for adjustment in report.applied.iter() {
+            println!(
+                "{} {}: {}{}",
+                kind(adjustment.kind),
+                adjustment.transaction_id,
+                adjustment.change,
+                adjustment
+                    .reason
+                    .as_deref()
+                    .map(|x| format!(" ({})", x))
+                    .unwrap_or_default()
+            );
+        }
+        if !report.unmatched.is_empty() {
+            println!();
+            println!("ATTENZIONE: rettifiche non applicate, operazione non trovata:");
+            for adjustment in report.unmatched.iter() {
+                println!("{}", adjustment.transaction_id);
+            }
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_mapping(&self, report: &MappingReport) -> anyhow::Result<()> {
+        println!("PROVA DELLA MAPPATURA:");
+        println!();
+        for trade in report.trades.iter() {
+            println!(
+                "{} {} {:?} {:?} {} {} € {} ({:?})",
+                timezone::day(trade.timestamp()),
+                trade.transaction_id(),
+                trade.transaction_type(),
+                trade.in_out(),
+                trade
+                    .amount_asset()
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+                trade.asset(),
+                trade.amount_fiat(),
+                trade.fiat()
+            );
+        }
+        if !report.skipped.is_empty() {
+            println!();
+            println!("Righe ignorate:");
+            for row in report.skipped.iter() {
+                println!("riga {}: {}", row.line, row.reason);
+            }
+        }
+        println!();
+        println!(
+            "{} operazioni lette; {} righe ignorate",
+            report.trades.len(),
+            report.skipped.len()
+        );
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_query(&self, summary: &TradeSummary) -> anyhow::Result<()> {
+        println!("OPERAZIONI:");
+        println!();
+        println!(
+            "{:<10} {:<40} {:<10} {:<8} {:>18} {:<8} {:>12} {:<4}",
+            "Data", "ID", "Tipo", "In/Out", "Quantità", "Asset", "Importo", "Valuta"
+        );
+        for trade in summary.trades.iter() {
+            println!(
+                "{:<10} {:<40} {:<10} {:<8} {:>18} {:<8} {:>12} {:<4}",
+                timezone::day(trade.timestamp).format("%d/%m/%Y"),
+                trade.transaction_id,
+                trade.transaction_type,
+                trade.in_out,
+                trade
+                    .amount_asset
+                    .map(|x| x.to_string())
+                    .unwrap_or_default(),
+                trade.asset,
+                trade.amount_fiat.round_dp(2),
+                trade.fiat
+            );
+        }
+        println!();
+        println!("Totali per asset:");
+        for asset in summary.assets.iter() {
+            println!(
+                "{} ({}): {} operazioni; quantità {}; importo {}",
+                asset.asset,
+                asset.asset_class,
+                asset.trades,
+                asset.amount_asset,
+                asset.amount_fiat.round_dp(2)
+            );
+        }
+        println!();
+        println!("Totali per classe:");
+        for class in summary.classes.iter() {
+            println!(
+                "{}: {} operazioni; importo {}",
+                class.asset_class,
+                class.trades,
+                class.amount_fiat.round_dp(2)
+            );
+        }
+        println!();
+        println!("{} operazioni trovate", summary.trades.len());
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+
+    fn paginate_anonymized(&self, report: &AnonymizeReport, output: &Path) -> anyhow::Result<()> {
+        println!("CSV ANONIMIZZATO:");
+        println!();
+        println!(
+            "{} righe con dati personali sostituite",
+            report.redacted_lines
+        );
+        println!("{} operazioni con ID offuscato", report.trades);
+        if let Some(scale) = report.scale {
+            println!("importi moltiplicati per {}", scale);
+        }
+        println!("scritto in {}", output.display());
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+}
+
+impl PaginateValidation for Stdout {
+    fn paginate_validation(&self, report: &ValidationReport) -> anyhow::Result<()> {
+        println!("VALIDAZIONE DELLE OPERAZIONI:");
+        println!();
+        for issue in report.issues.iter() {
+            let problem = match &issue.kind {
+                ValidationIssueKind::NegativeBalance {
+                    available,
+                    required,
+                } => format!(
+                    "saldo negativo: venduti {} ma disponibili {}",
+                    required, available
+                ),
+                ValidationIssueKind::SellWithoutBuy { required } => {
+                    format!("venduti {} senza alcun acquisto precedente", required)
+                }
+                ValidationIssueKind::UnknownAsset => {
+                    String::from("asset non quotato su Bitpanda né mappato su Yahoo Finance")
+                }
+                ValidationIssueKind::TransferAsSplit { amount } => {
+                    format!(
+                        "trasferimento in entrata di {} trattato come frazionamento",
+                        amount
+                    )
+                }
+                ValidationIssueKind::NonEurTrade { fiat } => {
+                    format!("operazione in {:?} anziché in EUR", fiat)
+                }
+            };
+            println!(
+                "{} {} {}: {}",
+                timezone::day(issue.timestamp),
+                issue.transaction_id,
+                issue.asset,
+                problem
+            );
+        }
+        if !report.is_empty() {
+            println!();
+        }
+        println!(
+            "{} operazioni verificate; {} problemi trovati",
+            report.trades,
+            report.issues.len()
+        );
+        if report.is_blocking() {
+            println!(
+                "ATTENZIONE: il calcolo di plusvalenze e minusvalenze non può essere completato"
+            );
+        }
+        println!("--------------------------------------------");
+        println!();
+        Ok(())
+    }
+}
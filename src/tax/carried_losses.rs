@@ -0,0 +1,111 @@
+//! # Carried losses
+//!
+//! This module exposes the capital losses of the previous years, which can still offset the gains
+//! of the current one.
+//!
+//! > Le minusvalenze possono essere compensate con le plusvalenze realizzate nello stesso anno
+//! > o nei quattro anni successivi.
+
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::path::Path;
+
+/// The amount of years after the year of realization, during which a loss can be used
+pub const LOSS_CARRY_FORWARD_YEARS: i32 = 4;
+
+/// The capital losses not yet used, declared by the investor.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "losses": [
+///         { "year": 2019, "amount": 320.0 },
+///         { "year": 2021, "amount": 1200.0 }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct CarriedLosses {
+    #[serde(default)]
+    losses: Vec<CarriedLoss>,
+}
+
+/// A loss realized in `year` and not yet used
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CarriedLoss {
+    /// The year the loss has been realized
+    pub year: i32,
+    /// The amount of the loss not yet used (positive)
+    pub amount: Decimal,
+}
+
+impl CarriedLoss {
+    /// Returns the last year the loss can be used
+    pub fn expires_at(&self) -> i32 {
+        self.year + LOSS_CARRY_FORWARD_YEARS
+    }
+}
+
+impl From<Vec<CarriedLoss>> for CarriedLosses {
+    fn from(losses: Vec<CarriedLoss>) -> Self {
+        Self { losses }
+    }
+}
+
+impl CarriedLosses {
+    /// Load carried losses from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading carried losses from {}", path.display());
+        let file = File::open(path)?;
+        let carried_losses: Self = serde_json::from_reader(file)?;
+        info!("found {} carried losses", carried_losses.losses.len());
+        Ok(carried_losses)
+    }
+
+    /// Returns the losses which can be used in `year`, from the oldest to the newest
+    pub fn available(&self, year: i32) -> Vec<CarriedLoss> {
+        let mut losses: Vec<CarriedLoss> = self
+            .losses
+            .iter()
+            .filter(|x| x.year < year && x.expires_at() >= year)
+            .cloned()
+            .collect();
+        losses.sort_by_key(|x| x.year);
+        losses
+    }
+
+    /// Returns the total amount of the losses which can be used in `year`
+    pub fn available_amount(&self, year: i32) -> Decimal {
+        self.available(year).iter().map(|x| x.amount).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_get_available_carried_losses() {
+        crate::mock::log();
+        let losses: CarriedLosses = serde_json::from_str(
+            r#"{
+                "losses": [
+                    { "year": 2021, "amount": 1200.0 },
+                    { "year": 2017, "amount": 100.0 },
+                    { "year": 2018, "amount": 320.0 },
+                    { "year": 2022, "amount": 50.0 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let available = losses.available(2022);
+        assert_eq!(available.len(), 2);
+        assert_eq!(available[0].year, 2018);
+        assert_eq!(available[0].expires_at(), 2022);
+        assert_eq!(losses.available_amount(2022), dec!(1520.0));
+    }
+}
@@ -8,20 +8,47 @@ use std::slice::Iter;
 
 mod calculator;
 mod capital_diff;
+mod export;
+mod matched_lot;
 
-pub use calculator::Calculator;
+pub use calculator::{
+    Calculator, CostBasis, CostBasisReport, CostBasisSource, Inventory, InventoryMismatch,
+    OpenPosition, OpenPositionsReport, SelfTransfers, SelfTransfersReport, ValidationIssueKind,
+    ValidationReport,
+};
 pub use capital_diff::CapitalDiff;
+pub use matched_lot::MatchedLot;
 
 /// Gains and losses contains the different capital gains and losees calculated.
 /// Taxes, assets and original amounts are stored
 #[derive(Debug)]
 pub struct GainsAndLosses {
     capitals: Vec<CapitalDiff>,
+    /// All the lots matched by disposals, including the ones without gain or loss
+    lots: Vec<MatchedLot>,
 }
 
 impl From<Vec<CapitalDiff>> for GainsAndLosses {
     fn from(capitals: Vec<CapitalDiff>) -> Self {
-        Self { capitals }
+        let lots = capitals
+            .iter()
+            .flat_map(|x| x.lots().iter().cloned())
+            .collect();
+        Self { capitals, lots }
+    }
+}
+
+impl From<Vec<MatchedLot>> for GainsAndLosses {
+    /// Derive the gains and losses from the matched lots; a capital diff is created for each lot
+    fn from(lots: Vec<MatchedLot>) -> Self {
+        Self {
+            capitals: lots
+                .iter()
+                .cloned()
+                .filter_map(Self::lot_to_capital_diff)
+                .collect(),
+            lots,
+        }
     }
 }
 
@@ -31,6 +58,11 @@ impl GainsAndLosses {
         self.capitals.iter()
     }
 
+    /// Returns an iterator over all the matched lots, including the ones without gain or loss
+    pub fn lots(&self) -> Iter<'_, MatchedLot> {
+        self.lots.iter()
+    }
+
     /// Group gains and losses by the same assets and create a unique capital diff for them
     pub fn flatten(mut self) -> Self {
         // group capitals by asset
@@ -67,6 +99,34 @@ impl GainsAndLosses {
         self.capitals.iter().map(|x| x.tax()).sum()
     }
 
+    /// Convert a matched lot into a capital diff. Returns `None` if the lot has neither gain nor loss
+    fn lot_to_capital_diff(lot: MatchedLot) -> Option<CapitalDiff> {
+        let value = lot.value();
+        if value.is_zero() {
+            None
+        } else if value.is_sign_negative() {
+            Some(
+                CapitalDiff::loss(
+                    lot.asset.clone(),
+                    lot.asset_class,
+                    lot.tax_percentage,
+                    value,
+                )
+                .with_lots(vec![lot]),
+            )
+        } else {
+            Some(
+                CapitalDiff::gain(
+                    lot.asset.clone(),
+                    lot.asset_class,
+                    lot.tax_percentage,
+                    value,
+                )
+                .with_lots(vec![lot]),
+            )
+        }
+    }
+
     /// Group the list of capital diffs into a list of list of capitals diff where each list
     /// is grouped by the asset kind
     fn group_gains_and_losses_by_asset(capitals: Vec<CapitalDiff>) -> Vec<Vec<CapitalDiff>> {
@@ -103,6 +163,10 @@ impl GainsAndLosses {
             .next()
             .unwrap();
         let total_value: Decimal = capitals_diff.iter().map(|x| x.value()).sum();
+        let lots: Vec<MatchedLot> = capitals_diff
+            .iter()
+            .flat_map(|x| x.lots().iter().cloned())
+            .collect();
         debug!(
             "flattening capital diffs of {}; total value: {}",
             asset, total_value
@@ -117,12 +181,7 @@ impl GainsAndLosses {
                 .map(|x| x.tax_percentage())
                 .max()
                 .unwrap();
-            Some(CapitalDiff::gain(
-                asset,
-                asset_class,
-                tax_percentage,
-                total_value,
-            ))
+            Some(CapitalDiff::gain(asset, asset_class, tax_percentage, total_value).with_lots(lots))
         } else {
             let tax_percentage: Decimal = capitals_diff
                 .iter()
@@ -130,12 +189,7 @@ impl GainsAndLosses {
                 .map(|x| x.tax_percentage())
                 .max()
                 .unwrap();
-            Some(CapitalDiff::loss(
-                asset,
-                asset_class,
-                tax_percentage,
-                total_value,
-            ))
+            Some(CapitalDiff::loss(asset, asset_class, tax_percentage, total_value).with_lots(lots))
         }
     }
 }
@@ -226,6 +280,27 @@ mod test {
         assert_eq!(gain_and_losses.tax_to_pay(), dec!(441.0));
     }
 
+    #[test]
+    fn should_derive_gains_and_losses_from_lots() {
+        crate::mock::log();
+        let gains_and_losses = GainsAndLosses::from(vec![
+            lot(Metal::Gold, dec!(100.0), dec!(150.0)),
+            lot(Metal::Gold, dec!(200.0), dec!(180.0)),
+            lot(Metal::Silver, dec!(50.0), dec!(40.0)),
+            lot(Metal::Platinum, dec!(50.0), dec!(50.0)),
+        ])
+        .flatten();
+        assert_eq!(gains_and_losses.capitals.len(), 2);
+        assert_eq!(gains_and_losses.gains_value(), dec!(30.0));
+        assert_eq!(gains_and_losses.losses_value(), dec!(-10.0));
+        assert_eq!(gains_and_losses.lots().count(), 4);
+        let gold = gains_and_losses
+            .iter()
+            .find(|x| x.asset() == &Asset::Metal(Metal::Gold))
+            .unwrap();
+        assert_eq!(gold.lots().len(), 2);
+    }
+
     #[test]
     fn should_flat_gains_and_losses() {
         crate::mock::log();
@@ -291,4 +366,21 @@ mod test {
         assert_eq!(gain_and_losses.losses_value(), dec!(-200.0));
         assert_eq!(gain_and_losses.tax_to_pay(), dec!(351.0));
     }
+
+    fn lot(metal: Metal, cost: Decimal, proceeds: Decimal) -> MatchedLot {
+        use chrono::{FixedOffset, TimeZone};
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        MatchedLot {
+            asset: Asset::Metal(metal),
+            asset_class: AssetClass::Metal,
+            tax_percentage: dec!(26.0),
+            buy_transaction_id: String::from("T1"),
+            acquired_at: offset.with_ymd_and_hms(2022, 1, 10, 12, 0, 0).unwrap(),
+            sell_transaction_id: String::from("T2"),
+            disposed_at: offset.with_ymd_and_hms(2022, 3, 1, 12, 0, 0).unwrap(),
+            quantity: dec!(1.0),
+            cost,
+            proceeds,
+        }
+    }
 }
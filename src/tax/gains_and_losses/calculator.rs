@@ -2,48 +2,257 @@
 //!
 //! Gains and losses calculator
 
+mod cost_basis;
+mod inventory;
+mod open_position;
+mod self_transfer;
 mod ticker_whitelist;
+mod validation;
 mod wallet;
 
+use chrono::{DateTime, FixedOffset};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use super::{CapitalDiff, GainsAndLosses};
-use crate::database::TradeDatabase;
+use super::{GainsAndLosses, MatchedLot};
+use crate::database::{QuoteDatabase, TradeDatabase, Venue};
 use bitpanda_csv::Trade;
-use bitpanda_csv::{Asset, InOut, TransactionType};
+use bitpanda_csv::{Asset, Currency, Fiat, InOut, TransactionType};
 
+pub use cost_basis::{CostBasis, CostBasisReport, CostBasisSource};
+pub use inventory::{Inventory, InventoryLot, InventoryMismatch, ParkedLot};
+pub use open_position::{OpenPosition, OpenPositionsReport};
+use self_transfer::{ParkedTransfer, SelfTransferLedger};
+pub use self_transfer::{SelfTransfers, SelfTransfersReport};
 use ticker_whitelist::TickerWhitelist;
-use wallet::Wallet;
+pub use validation::{ValidationIssue, ValidationIssueKind, ValidationReport};
+use wallet::{Block, LotOrigin, Wallet};
 
 /// Gains and losses calculator from trades
 #[derive(Debug, Default)]
 pub struct Calculator {
     balance: HashMap<Asset, Wallet>,
+    self_transfers: SelfTransferLedger,
+    cost_basis: CostBasis,
+    cost_basis_report: CostBasisReport,
 }
 
 impl Calculator {
+    /// Set the withdrawals to treat as transfers to wallets owned by the investor
+    pub fn with_self_transfers(mut self, self_transfers: SelfTransfers) -> Self {
+        self.self_transfers.configure(self_transfers);
+        self
+    }
+
+    /// Set the cost basis of the assets deposited from outside Bitpanda
+    pub fn with_cost_basis(mut self, cost_basis: CostBasis) -> Self {
+        self.cost_basis = cost_basis;
+        self
+    }
+
+    /// Start the calculation from the lots held in the opening `inventory`, instead of requiring all the historical trades.
+    /// The lots parked by a self-transfer are parked again, so that they can be matched with their deposit
+    pub fn with_opening_inventory(mut self, inventory: Inventory) -> Self {
+        debug!(
+            "loading {} lots from inventory at {}",
+            inventory.lots.len(),
+            inventory.date
+        );
+        let mut parked: Vec<(ParkedLot, Asset, Vec<Block>)> = Vec::new();
+        for lot in inventory.lots.into_iter() {
+            let origin = LotOrigin::new(lot.transaction_id, lot.acquired_at);
+            match lot.parked {
+                None => self
+                    .get_wallet(lot.asset)
+                    .buy(lot.quantity, lot.cost, origin),
+                Some(parked_lot) => {
+                    let block = Block::new(lot.quantity, lot.cost).with_origin(origin);
+                    match parked
+                        .iter_mut()
+                        .find(|(x, _, _)| x.withdrawal == parked_lot.withdrawal)
+                    {
+                        Some((_, _, blocks)) => blocks.push(block),
+                        None => parked.push((parked_lot, lot.asset, vec![block])),
+                    }
+                }
+            }
+        }
+        for (lot, asset, blocks) in parked.into_iter() {
+            self.self_transfers.park_transfer(ParkedTransfer::new(
+                lot.withdrawal,
+                lot.withdrawn_at,
+                asset,
+                blocks,
+            ));
+        }
+        self
+    }
+
+    /// Get the lots held after the last calculation, as an inventory at `date`.
+    /// The lots parked by a self-transfer which hasn't been deposited back yet are included too
+    pub fn closing_inventory(&self, date: DateTime<FixedOffset>) -> Inventory {
+        let held = self
+            .balance
+            .iter()
+            .filter(|(asset, _)| !matches!(asset, Asset::Currency(Currency::Fiat(_))))
+            .flat_map(|(asset, wallet)| {
+                wallet
+                    .blocks()
+                    .iter()
+                    .map(move |block| (asset.clone(), block, None))
+            });
+        let parked = self.self_transfers.parked().iter().flat_map(|transfer| {
+            transfer.blocks().iter().map(|block| {
+                (
+                    transfer.asset.clone(),
+                    block,
+                    Some(ParkedLot {
+                        withdrawal: transfer.withdrawal.clone(),
+                        withdrawn_at: transfer.timestamp,
+                    }),
+                )
+            })
+        });
+        let mut lots: Vec<InventoryLot> = held
+            .chain(parked)
+            .filter(|(_, block, _)| !block.amount_asset().is_zero())
+            .map(|(asset, block, parked)| InventoryLot {
+                asset,
+                transaction_id: block.origin().transaction_id.clone(),
+                acquired_at: block.origin().acquired_at,
+                quantity: block.amount_asset(),
+                cost: block.amount_fiat(),
+                parked,
+            })
+            .collect();
+        lots.sort_by_key(|x| (x.asset.to_string(), x.acquired_at));
+        Inventory::new(date, lots)
+    }
+
+    /// Get the cost basis assigned to the external deposits during the last calculation
+    pub fn cost_basis_report(&self) -> &CostBasisReport {
+        &self.cost_basis_report
+    }
+
+    /// Get the report of the self-transfers tracked during the last calculation
+    pub fn self_transfers_report(&self) -> SelfTransfersReport {
+        self.self_transfers.report()
+    }
+
+    /// Get the positions still open after the last calculation, valued at the quotes' end of the time range
+    pub fn open_positions(&self, quotes: &QuoteDatabase) -> OpenPositionsReport {
+        let mut positions: Vec<OpenPosition> = self
+            .balance
+            .iter()
+            .filter(|(_, wallet)| !wallet.amount_asset().is_zero())
+            .map(|(asset, wallet)| OpenPosition {
+                asset: asset.clone(),
+                quantity: wallet.amount_asset(),
+                cost: wallet.amount_fiat(),
+                market_price: quotes.price(asset),
+                tax_percentage: self.tax_percentage(asset.clone()),
+            })
+            .collect();
+        positions.sort_by_key(|x| x.asset.to_string());
+        OpenPositionsReport { positions }
+    }
+
     /// Calculate gains and losses from trade database
     pub fn calculate(&mut self, trades: &TradeDatabase) -> anyhow::Result<GainsAndLosses> {
-        let mut stonks = vec![];
+        let mut lots = vec![];
         debug!(
             "calculating gains and losses for {} trades",
             trades.all().trades().len()
         );
+        self.self_transfers.match_venue_transfers(&trades.all());
         // iter trades (only BUY, SELL, DEPOSIT, WITHDRAWAL)
         for trade in trades.all().trades() {
-            // if the wallet update, produces a capital-diff, push it to gains and losses
-            if let Some(capital_diff) = self.update_wallet(trade)? {
-                stonks.push(capital_diff);
-            }
+            // if the wallet update matches some lots with a sell, push them to gains and losses
+            lots.extend(self.update_wallet(trade)?);
         }
 
-        Ok(GainsAndLosses::from(stonks).flatten())
+        Ok(GainsAndLosses::from(lots).flatten())
+    }
+
+    /// Replay the trades through the lot ledger, collecting the problems found instead of failing at the first one.
+    /// When a trade spends more than the quantity held, the wallet is emptied and the replay goes on
+    pub fn validate(&mut self, trades: &TradeDatabase) -> ValidationReport {
+        let set = trades.all();
+        debug!("validating {} trades", set.trades().len());
+        let mut report = ValidationReport {
+            trades: set.trades().len(),
+            issues: Vec::new(),
+        };
+        self.self_transfers.match_venue_transfers(&set);
+        let bitpanda_assets: HashSet<Asset> = trades
+            .by_venue()
+            .get(&Venue::Bitpanda)
+            .map(|db| {
+                db.all()
+                    .collect_assets()
+                    .into_iter()
+                    .map(|(asset, _)| asset)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut unknown_assets = HashSet::new();
+        for trade in set.trades() {
+            let issue = |kind| ValidationIssue {
+                transaction_id: trade.transaction_id().to_string(),
+                timestamp: trade.timestamp(),
+                asset: trade.asset(),
+                kind,
+            };
+            if trade.fiat() != Fiat::Eur {
+                report.issues.push(issue(ValidationIssueKind::NonEurTrade {
+                    fiat: trade.fiat(),
+                }));
+            }
+            if !QuoteDatabase::is_quotable(&trade.asset(), trade.asset_class(), &bitpanda_assets)
+                && unknown_assets.insert(trade.asset())
+            {
+                report.issues.push(issue(ValidationIssueKind::UnknownAsset));
+            }
+            let required = trade.amount_asset().unwrap_or_default();
+            match (trade.transaction_type(), trade.in_out()) {
+                (TransactionType::Deposit | TransactionType::Buy, _) => {}
+                (TransactionType::Transfer, InOut::Incoming) => {
+                    report
+                        .issues
+                        .push(issue(ValidationIssueKind::TransferAsSplit {
+                            amount: required,
+                        }));
+                }
+                _ => match self.balance.get(&trade.asset()).map(|x| x.amount_asset()) {
+                    None if !required.is_zero() => {
+                        report
+                            .issues
+                            .push(issue(ValidationIssueKind::SellWithoutBuy { required }));
+                        continue;
+                    }
+                    Some(available) if required > available => {
+                        report
+                            .issues
+                            .push(issue(ValidationIssueKind::NegativeBalance {
+                                available,
+                                required,
+                            }));
+                        self.balance.insert(trade.asset(), Wallet::default());
+                        continue;
+                    }
+                    _ => {}
+                },
+            }
+            if let Err(err) = self.update_wallet(trade) {
+                warn!("could not replay trade {}: {}", trade.transaction_id(), err);
+            }
+        }
+        report
     }
 
     /// Update wallet using trade.
-    /// Optionally produce a capital diff (after a sell)
-    fn update_wallet(&mut self, trade: &Trade) -> anyhow::Result<Option<CapitalDiff>> {
+    /// Returns the lots matched by the trade (only after a sell)
+    fn update_wallet(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
         debug!(
             "processing trade {} with asset {}",
             trade.transaction_id(),
@@ -65,11 +274,20 @@ impl Calculator {
     }
 
     /// Buy asset
-    fn buy_asset(&mut self, trade: &Trade) -> anyhow::Result<Option<CapitalDiff>> {
+    fn buy_asset(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
+        // deposit of assets previously withdrawn to a wallet of the investor
+        if let Some(blocks) = self.self_transfers.redeem(trade)? {
+            self.get_wallet(trade.asset()).restore(blocks);
+            return Ok(Vec::new());
+        }
+        if CostBasis::is_external_deposit(trade) {
+            return self.deposit_asset(trade);
+        }
         let wallet = self.get_wallet(trade.asset());
         wallet.buy(
             trade.amount_asset().unwrap_or_default(),
             trade.amount_fiat(),
+            LotOrigin::new(trade.transaction_id(), trade.timestamp()),
         );
         info!(
             "bought {} units of {} at € {}",
@@ -77,63 +295,103 @@ impl Calculator {
             trade.asset(),
             trade.amount_fiat()
         );
-        Ok(None)
+        Ok(Vec::new())
+    }
+
+    /// Deposit asset coming from outside Bitpanda, using the cost basis to value it
+    fn deposit_asset(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
+        let basis = self.cost_basis.cost_of(trade);
+        info!(
+            "deposited {} units of {} with cost basis € {} ({:?})",
+            basis.amount_asset, basis.asset, basis.cost, basis.source
+        );
+        let origin = LotOrigin::new(&basis.deposit, basis.acquired_at);
+        self.get_wallet(trade.asset())
+            .buy(basis.amount_asset, basis.cost, origin);
+        self.cost_basis_report.deposits.push(basis);
+        Ok(Vec::new())
     }
 
     /// Sell asset
-    fn sell_asset(&mut self, trade: &Trade) -> anyhow::Result<Option<CapitalDiff>> {
+    fn sell_asset(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
+        if self.self_transfers.is_self_transfer(trade) {
+            return self.park_asset(trade);
+        }
         let wallet = self.get_wallet(trade.asset());
-        // sell block
-        let buy_amount_fiat = wallet.sell(trade.amount_asset().unwrap_or_default())?;
+        // take blocks out of the wallet
+        let blocks = wallet.withdraw(trade.amount_asset().unwrap_or_default())?;
         if trade.transaction_type() == TransactionType::Sell {
-            // Calc loss/gain
-            let capital_diff = trade.amount_fiat() - buy_amount_fiat;
+            let lots = self.match_lots(trade, blocks);
             info!(
                 "sold {} units of {} at € {} (difference with buy price: € {})",
                 trade.amount_asset().unwrap_or_default(),
                 trade.asset(),
                 trade.amount_fiat(),
-                capital_diff
+                lots.iter().map(|x| x.value()).sum::<Decimal>()
             );
-            Ok(self.calc_capital_diff(capital_diff, trade))
+            Ok(lots)
         } else {
             info!("ignoring capital diff for withdrawal ({})", trade.asset());
-            Ok(None)
+            Ok(Vec::new())
         }
     }
 
+    /// Take the withdrawn lots out of the wallet and park them until they are deposited back
+    fn park_asset(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
+        let wallet = self.get_wallet(trade.asset());
+        let blocks = wallet.withdraw(trade.amount_asset().unwrap_or_default())?;
+        self.self_transfers.park(trade, blocks);
+
+        Ok(Vec::new())
+    }
+
     /// Perform a stock split on the trade asset
-    fn stock_split(&mut self, trade: &Trade) -> anyhow::Result<Option<CapitalDiff>> {
+    fn stock_split(&mut self, trade: &Trade) -> anyhow::Result<Vec<MatchedLot>> {
         let wallet = self.get_wallet(trade.asset());
         info!(
             "stock split for {}; new amount: {}",
             trade.asset(),
             trade.amount_asset().unwrap_or_default()
         );
-        wallet.stock_split(trade.amount_asset().unwrap_or_default());
+        wallet.stock_split(
+            trade.amount_asset().unwrap_or_default(),
+            LotOrigin::new(trade.transaction_id(), trade.timestamp()),
+        );
 
-        Ok(None)
+        Ok(Vec::new())
     }
 
-    /// Calculate capital diff from trade and diff amount
-    fn calc_capital_diff(&self, diff: Decimal, trade: &Trade) -> Option<CapitalDiff> {
-        if diff.is_zero() {
-            None
-        } else if diff.is_sign_negative() {
-            Some(CapitalDiff::loss(
-                trade.asset(),
-                trade.asset_class(),
-                self.tax_percentage(trade.asset()),
-                diff,
-            ))
-        } else {
-            Some(CapitalDiff::gain(
-                trade.asset(),
-                trade.asset_class(),
-                self.tax_percentage(trade.asset()),
-                diff,
-            ))
-        }
+    /// Match the blocks taken out of the wallet by the sell `trade`.
+    /// The sell proceeds are split among the blocks proportionally to the quantity; the last block gets the remainder
+    fn match_lots(&self, trade: &Trade, blocks: Vec<Block>) -> Vec<MatchedLot> {
+        let amount_asset: Decimal = blocks.iter().map(|x| x.amount_asset()).sum();
+        let tax_percentage = self.tax_percentage(trade.asset());
+        let mut remaining_proceeds = trade.amount_fiat();
+        let last = blocks.len().saturating_sub(1);
+        blocks
+            .into_iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let proceeds = if i == last || amount_asset.is_zero() {
+                    remaining_proceeds
+                } else {
+                    trade.amount_fiat() * block.amount_asset() / amount_asset
+                };
+                remaining_proceeds -= proceeds;
+                MatchedLot {
+                    asset: trade.asset(),
+                    asset_class: trade.asset_class(),
+                    tax_percentage,
+                    buy_transaction_id: block.origin().transaction_id.clone(),
+                    acquired_at: block.origin().acquired_at,
+                    sell_transaction_id: trade.transaction_id().to_string(),
+                    disposed_at: trade.timestamp(),
+                    quantity: block.amount_asset(),
+                    cost: block.amount_fiat(),
+                    proceeds,
+                }
+            })
+            .collect()
     }
 
     /// Return the tax percentage to apply to trade asset
@@ -163,7 +421,8 @@ mod test {
     use super::*;
     use crate::mock::database::DatabaseTradeMock;
 
-    use bitpanda_csv::{CryptoCurrency, Currency, Metal};
+    use bitpanda_csv::{AssetClass, CryptoCurrency, Metal, TradeGenerator};
+    use chrono::TimeZone;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -188,6 +447,343 @@ mod test {
         assert_eq!(gains_and_losses.gains_value().round_dp(2), dec!(17.16));
     }
 
+    #[test]
+    fn should_treat_redeposit_as_gain_without_self_transfers() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::self_transfer_mock();
+        let mut calculator = Calculator::default();
+        let gains_and_losses = calculator.calculate(&db).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(2997.0));
+    }
+
+    #[test]
+    fn should_keep_cost_basis_across_self_transfer() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::self_transfer_mock();
+        let self_transfers: SelfTransfers = serde_json::from_str(
+            r#"{
+                "auto_match": { "window_days": 30, "tolerance": 0.5 },
+                "transfers": [{ "withdrawal": "C00000000-0000-0000-0000-000000000002" }]
+            }"#,
+        )
+        .unwrap();
+        let mut calculator = Calculator::default().with_self_transfers(self_transfers);
+        let gains_and_losses = calculator.calculate(&db).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(997.0));
+        // the sold lot refers to the original buy
+        let lots: Vec<&MatchedLot> = gains_and_losses.lots().collect();
+        assert_eq!(lots.len(), 1);
+        assert_eq!(
+            lots[0].buy_transaction_id.as_str(),
+            "T00000000-0000-0000-0000-000000000001"
+        );
+        assert_eq!(
+            lots[0].sell_transaction_id.as_str(),
+            "T00000000-0000-0000-0000-000000000004"
+        );
+        assert_eq!(lots[0].quantity, dec!(0.0999));
+        assert_eq!(lots[0].holding_days(), 61);
+        let report = calculator.self_transfers_report();
+        assert_eq!(report.matched.len(), 1);
+        assert!(report.unmatched_withdrawals.is_empty());
+    }
+
+    #[test]
+    fn should_fail_on_rejected_self_transfer_deposit() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::excess_self_transfer_mock();
+        let self_transfers: SelfTransfers = serde_json::from_str(
+            r#"{
+                "transfers": [{
+                    "withdrawal": "C00000000-0000-0000-0000-000000000006",
+                    "deposit": "C00000000-0000-0000-0000-000000000007"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut calculator = Calculator::default().with_self_transfers(self_transfers);
+        assert!(calculator.calculate(&db).is_err());
+        // the deposit is not valued as an external deposit
+        assert!(calculator.cost_basis_report().deposits.is_empty());
+        let report = calculator.self_transfers_report();
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.unmatched_withdrawals.len(), 1);
+    }
+
+    #[test]
+    fn should_match_transfers_between_venues() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::venue_transfer_mock();
+        let mut calculator = Calculator::default();
+        let gains_and_losses = calculator.calculate(&db).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(1000.0));
+        let lots: Vec<&MatchedLot> = gains_and_losses.lots().collect();
+        assert_eq!(lots.len(), 1);
+        assert_eq!(
+            lots[0].buy_transaction_id.as_str(),
+            "T00000000-0000-0000-0000-000000000021"
+        );
+        let report = calculator.self_transfers_report();
+        assert_eq!(report.matched.len(), 1);
+        assert_eq!(report.matched[0].deposit.as_str(), "D-1");
+    }
+
+    #[test]
+    fn should_use_cost_basis_for_external_deposits() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::external_deposit_mock();
+        let cost_basis: CostBasis = serde_json::from_str(
+            r#"{
+                "deposits": [{
+                    "deposit": "C00000000-0000-0000-0000-000000000011",
+                    "acquired_at": "2021-05-03T10:00:00+02:00",
+                    "cost": 3200.0
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut calculator = Calculator::default().with_cost_basis(cost_basis);
+        let gains_and_losses = calculator.calculate(&db).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(800.0));
+        let lot = gains_and_losses.lots().next().unwrap();
+        assert_eq!(
+            lot.acquired_at.to_rfc3339().as_str(),
+            "2021-05-03T10:00:00+02:00"
+        );
+        assert_eq!(calculator.cost_basis_report().deposits.len(), 1);
+        assert_eq!(calculator.cost_basis_report().undeclared().count(), 0);
+    }
+
+    #[test]
+    fn should_report_open_positions() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let mut calculator = Calculator::default();
+        calculator.calculate(&db).unwrap();
+        let mut prices = HashMap::new();
+        prices.insert(Asset::Ticker(String::from("AMZN")), dec!(90.0));
+        let report = calculator.open_positions(&QuoteDatabase::from(prices));
+        assert_eq!(report.positions.len(), 5);
+        let amazon = report
+            .positions
+            .iter()
+            .find(|x| x.asset == Asset::Ticker(String::from("AMZN")))
+            .unwrap();
+        assert_eq!(amazon.quantity, dec!(1.0));
+        assert_eq!(amazon.cost.round_dp(2), dec!(171.42));
+        assert_eq!(amazon.unrealized().unwrap().round_dp(2), dec!(-81.42));
+        assert_eq!(report.unpriced().count(), 4);
+    }
+
+    #[test]
+    fn should_chain_closing_inventory_as_opening_inventory() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::self_transfer_mock();
+        let date = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 3, 31, 23, 59, 59)
+            .unwrap();
+        // calculate first period: only the buy
+        let first_period = TradeDatabase::from(
+            db.select(crate::database::TradeQuery::default().before(date))
+                .trades()
+                .iter()
+                .map(|x| (*x).clone())
+                .collect::<Vec<Trade>>(),
+        );
+        let mut calculator = Calculator::default();
+        calculator.calculate(&first_period).unwrap();
+        let inventory = calculator.closing_inventory(date);
+        assert_eq!(inventory.lots.len(), 1);
+        assert_eq!(inventory.lots[0].quantity, dec!(0.1));
+        assert_eq!(inventory.lots[0].cost, dec!(2000.0));
+        assert_eq!(
+            inventory.lots[0].transaction_id.as_str(),
+            "T00000000-0000-0000-0000-000000000001"
+        );
+        // calculate second period starting from inventory
+        let second_period = TradeDatabase::from(
+            db.select(crate::database::TradeQuery::default().after(date))
+                .trades()
+                .iter()
+                .map(|x| (*x).clone())
+                .collect::<Vec<Trade>>(),
+        );
+        let self_transfers: SelfTransfers = serde_json::from_str(
+            r#"{
+                "transfers": [{
+                    "withdrawal": "C00000000-0000-0000-0000-000000000002",
+                    "deposit": "C00000000-0000-0000-0000-000000000003"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut calculator = Calculator::default()
+            .with_opening_inventory(inventory)
+            .with_self_transfers(self_transfers);
+        let gains_and_losses = calculator.calculate(&second_period).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(997.0));
+        assert!(calculator.closing_inventory(date).lots.is_empty());
+    }
+
+    #[test]
+    fn should_carry_parked_lots_in_closing_inventory() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::self_transfer_mock();
+        // the period ends after the withdrawal, but before the deposit
+        let date = FixedOffset::east_opt(7200)
+            .unwrap()
+            .with_ymd_and_hms(2022, 4, 2, 23, 59, 59)
+            .unwrap();
+        let self_transfers: SelfTransfers = serde_json::from_str(
+            r#"{
+                "transfers": [{
+                    "withdrawal": "C00000000-0000-0000-0000-000000000002",
+                    "deposit": "C00000000-0000-0000-0000-000000000003"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let first_period = TradeDatabase::from(
+            db.select(crate::database::TradeQuery::default().before(date))
+                .trades()
+                .iter()
+                .map(|x| (*x).clone())
+                .collect::<Vec<Trade>>(),
+        );
+        let mut calculator = Calculator::default().with_self_transfers(self_transfers.clone());
+        calculator.calculate(&first_period).unwrap();
+        let inventory = calculator.closing_inventory(date);
+        assert_eq!(inventory.lots.len(), 1);
+        assert_eq!(inventory.lots[0].quantity, dec!(0.1));
+        assert_eq!(inventory.lots[0].cost, dec!(2000.0));
+        assert_eq!(
+            inventory.lots[0]
+                .parked
+                .as_ref()
+                .unwrap()
+                .withdrawal
+                .as_str(),
+            "C00000000-0000-0000-0000-000000000002"
+        );
+        // parked lots are not held in the wallet
+        assert!(inventory.quantities().is_empty());
+        // the deposit of the second period brings the parked lots back
+        let second_period = TradeDatabase::from(
+            db.select(crate::database::TradeQuery::default().after(date))
+                .trades()
+                .iter()
+                .map(|x| (*x).clone())
+                .collect::<Vec<Trade>>(),
+        );
+        let mut calculator = Calculator::default()
+            .with_self_transfers(self_transfers)
+            .with_opening_inventory(inventory);
+        let gains_and_losses = calculator.calculate(&second_period).unwrap();
+        assert_eq!(gains_and_losses.gains_value(), dec!(997.0));
+        assert_eq!(calculator.self_transfers_report().matched.len(), 1);
+        assert!(calculator.cost_basis_report().deposits.is_empty());
+    }
+
+    #[test]
+    fn should_validate_trades() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let report = Calculator::default().validate(&db);
+        assert_eq!(report.trades, db.all().trades().len());
+        assert!(!report.is_blocking());
+        // stock split
+        let db = DatabaseTradeMock::google_stock_split_mock();
+        let report = Calculator::default().validate(&db);
+        assert!(report
+            .issues
+            .iter()
+            .any(|x| matches!(x.kind, ValidationIssueKind::TransferAsSplit { .. })));
+    }
+
+    #[test]
+    fn should_report_unknown_assets() {
+        crate::mock::log();
+        let db = DatabaseTradeMock::mock();
+        let timestamp = db.all().trades()[0].timestamp();
+        let isin = Asset::Ticker(String::from("US0378331005"));
+        let db = db.extended(vec![
+            (
+                Venue::TradeRepublic,
+                TradeGenerator::buy(
+                    timestamp,
+                    dec!(141.0),
+                    Fiat::Eur,
+                    dec!(1.0),
+                    isin.clone(),
+                    AssetClass::Stock,
+                    dec!(141.0),
+                ),
+            ),
+            (
+                Venue::Kraken,
+                TradeGenerator::buy(
+                    timestamp,
+                    dec!(100.0),
+                    Fiat::Eur,
+                    dec!(0.1),
+                    Asset::Currency(Currency::Crypto(CryptoCurrency::Eth)),
+                    AssetClass::Cryptocurrency,
+                    dec!(1000.0),
+                ),
+            ),
+        ]);
+        let report = Calculator::default().validate(&db);
+        let unknown: Vec<&Asset> = report
+            .issues
+            .iter()
+            .filter(|x| x.kind == ValidationIssueKind::UnknownAsset)
+            .map(|x| &x.asset)
+            .collect();
+        assert_eq!(unknown, vec![&isin]);
+    }
+
+    #[test]
+    fn should_report_sells_exceeding_balance() {
+        crate::mock::log();
+        let trades: Vec<Trade> = DatabaseTradeMock::mock()
+            .all()
+            .trades()
+            .iter()
+            .map(|x| (*x).clone())
+            .collect();
+        let sell = trades
+            .iter()
+            .find(|x| x.transaction_type() == TransactionType::Sell)
+            .unwrap()
+            .clone();
+        // remove the buys of the sold asset
+        let db = TradeDatabase::from(
+            trades
+                .into_iter()
+                .filter(|x| {
+                    x.asset() != sell.asset() || x.transaction_type() != TransactionType::Buy
+                })
+                .collect::<Vec<Trade>>(),
+        );
+        let mut calculator = Calculator::default();
+        assert!(calculator.calculate(&db).is_err());
+        let report = Calculator::default().validate(&db);
+        assert!(report.is_blocking());
+        let issue = report
+            .issues
+            .iter()
+            .find(|x| x.transaction_id == sell.transaction_id())
+            .unwrap();
+        assert_eq!(issue.asset, sell.asset());
+        assert_eq!(
+            issue.kind,
+            ValidationIssueKind::SellWithoutBuy {
+                required: sell.amount_asset().unwrap()
+            }
+        );
+    }
+
     #[test]
     fn should_tell_tax_percentage() {
         crate::mock::log();
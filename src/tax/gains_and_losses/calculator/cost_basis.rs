@@ -0,0 +1,296 @@
+//! # Cost basis
+//!
+//! This module exposes the types to assign a cost to the crypto assets deposited on Bitpanda
+//! from outside, since the CSV doesn't tell how much the investor paid for them.
+
+use bitpanda_csv::{Asset, AssetClass, Trade, TransactionType};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use crate::database::{QuoteDatabase, TradeDatabase, TradeQuery};
+
+/// The cost basis declared by the investor for the deposits coming from outside Bitpanda.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "deposits": [
+///         {
+///             "deposit": "C2cbcc5dd-67c1-4ded-8020-000000000000",
+///             "acquired_at": "2021-05-03T10:00:00+02:00",
+///             "cost": 1500.0
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct CostBasis {
+    /// Cost basis declared by the investor
+    #[serde(default)]
+    deposits: Vec<DeclaredCostBasis>,
+    /// Market price of the deposited asset at the deposit date, for deposits without a declared cost basis
+    #[serde(skip)]
+    market_prices: HashMap<String, Decimal>,
+}
+
+/// Cost basis declared for a single deposit
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeclaredCostBasis {
+    /// Transaction ID of the deposit
+    deposit: String,
+    /// When the assets were originally acquired
+    acquired_at: DateTime<FixedOffset>,
+    /// The FIAT amount paid for the deposited assets
+    cost: Decimal,
+}
+
+impl CostBasis {
+    /// Load cost basis from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading cost basis from {}", path.display());
+        let file = File::open(path)?;
+        let cost_basis: Self = serde_json::from_reader(file)?;
+        info!(
+            "found cost basis for {} deposits",
+            cost_basis.deposits.len()
+        );
+        Ok(cost_basis)
+    }
+
+    /// Returns whether `trade` is a deposit of assets coming from outside Bitpanda
+    pub fn is_external_deposit(trade: &Trade) -> bool {
+        Self::external_deposits().matches(trade)
+    }
+
+    /// Query of the deposits of assets coming from outside Bitpanda
+    fn external_deposits() -> TradeQuery {
+        TradeQuery::default()
+            .transaction_type(TransactionType::Deposit)
+            .not(TradeQuery::default().asset_class(AssetClass::Fiat))
+    }
+
+    /// Collect the market price at the deposit date for all the external deposits without a declared cost basis.
+    /// The price reported in the CSV is preferred; otherwise the price is taken from the quotes, if they cover
+    /// the deposit date. Deposits without a price are left without a cost basis.
+    pub fn resolve_market_prices(&mut self, trades: &TradeDatabase, quotes: &QuoteDatabase) {
+        let set =
+            trades.select(Self::external_deposits().not(
+                TradeQuery::default().transaction_ids(self.deposits.iter().map(|x| &x.deposit)),
+            ));
+        for trade in set.trades() {
+            let price = trade
+                .asset_market_price()
+                .filter(|price| !price.is_zero())
+                .or_else(|| quotes.quoted_price_at(&trade.asset(), trade.timestamp()));
+            match price {
+                Some(price) => {
+                    debug!(
+                        "market price of {} at {}: € {}",
+                        trade.asset(),
+                        trade.timestamp(),
+                        price
+                    );
+                    self.market_prices
+                        .insert(trade.transaction_id().to_string(), price);
+                }
+                None => warn!(
+                    "could not find market price of {} at {}",
+                    trade.asset(),
+                    trade.timestamp()
+                ),
+            }
+        }
+    }
+
+    /// Get the cost basis for the external deposit `trade`
+    pub fn cost_of(&self, trade: &Trade) -> DepositCostBasis {
+        let amount_asset = trade.amount_asset().unwrap_or_default();
+        let (acquired_at, cost, source) =
+            if let Some(declared) = self.declared(trade.transaction_id()) {
+                (
+                    declared.acquired_at,
+                    declared.cost,
+                    CostBasisSource::Declared,
+                )
+            } else if let Some(price) = self.market_prices.get(trade.transaction_id()) {
+                (
+                    trade.timestamp(),
+                    amount_asset * price,
+                    CostBasisSource::MarketValue,
+                )
+            } else {
+                (
+                    trade.timestamp(),
+                    trade.amount_fiat(),
+                    CostBasisSource::Missing,
+                )
+            };
+        DepositCostBasis {
+            deposit: trade.transaction_id().to_string(),
+            asset: trade.asset(),
+            amount_asset,
+            acquired_at,
+            cost,
+            source,
+        }
+    }
+
+    /// Get the cost basis declared for the deposit with the provided transaction ID
+    fn declared(&self, deposit: &str) -> Option<&DeclaredCostBasis> {
+        self.deposits.iter().find(|x| x.deposit == deposit)
+    }
+}
+
+/// Where the cost basis of a deposit comes from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CostBasisSource {
+    /// Declared by the investor
+    Declared,
+    /// Market value of the assets at the deposit date
+    MarketValue,
+    /// No cost basis available; the FIAT amount reported in the CSV is used
+    Missing,
+}
+
+/// The cost basis assigned to an external deposit
+#[derive(Debug, Clone)]
+pub struct DepositCostBasis {
+    /// Transaction ID of the deposit
+    pub deposit: String,
+    /// Deposited asset
+    pub asset: Asset,
+    /// Deposited quantity
+    pub amount_asset: Decimal,
+    /// When the assets were acquired
+    pub acquired_at: DateTime<FixedOffset>,
+    /// The FIAT cost of the deposited assets
+    pub cost: Decimal,
+    /// Where the cost comes from
+    pub source: CostBasisSource,
+}
+
+/// Cost basis assigned to the external deposits during the calculation
+#[derive(Debug, Default, Clone)]
+pub struct CostBasisReport {
+    pub deposits: Vec<DepositCostBasis>,
+}
+
+impl CostBasisReport {
+    /// Iterate over the deposits without a cost basis declared by the investor
+    pub fn undeclared(&self) -> impl Iterator<Item = &DepositCostBasis> {
+        self.deposits
+            .iter()
+            .filter(|x| x.source != CostBasisSource::Declared)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::finance::{Quote, Quotes};
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{CryptoCurrency, Currency};
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_use_declared_cost_basis() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::external_deposit_mock();
+        let set = trades.all();
+        let deposit = set.trades()[0];
+        let cost_basis: CostBasis = serde_json::from_str(&format!(
+            r#"{{
+                "deposits": [{{
+                    "deposit": "{}",
+                    "acquired_at": "2021-05-03T10:00:00+02:00",
+                    "cost": 1500.0
+                }}]
+            }}"#,
+            deposit.transaction_id()
+        ))
+        .unwrap();
+        let basis = cost_basis.cost_of(deposit);
+        assert_eq!(basis.source, CostBasisSource::Declared);
+        assert_eq!(basis.cost, dec!(1500.0));
+        assert_eq!(basis.amount_asset, dec!(0.1));
+        assert_eq!(
+            basis.acquired_at.to_rfc3339().as_str(),
+            "2021-05-03T10:00:00+02:00"
+        );
+    }
+
+    #[test]
+    fn should_use_market_value_as_cost_basis() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::external_deposit_mock();
+        let set = trades.all();
+        let deposit = set.trades()[0];
+        let mut cost_basis = CostBasis::default();
+        cost_basis
+            .market_prices
+            .insert(deposit.transaction_id().to_string(), dec!(30000.0));
+        let basis = cost_basis.cost_of(deposit);
+        assert_eq!(basis.source, CostBasisSource::MarketValue);
+        assert_eq!(basis.cost, dec!(3000.0));
+        assert_eq!(basis.acquired_at, deposit.timestamp());
+    }
+
+    #[test]
+    fn should_report_missing_cost_basis() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::external_deposit_mock();
+        let set = trades.all();
+        let deposit = set.trades()[0];
+        let basis = CostBasis::default().cost_of(deposit);
+        assert_eq!(basis.source, CostBasisSource::Missing);
+        assert_eq!(basis.cost, Decimal::ZERO);
+        let report = CostBasisReport {
+            deposits: vec![basis],
+        };
+        assert_eq!(report.undeclared().count(), 1);
+    }
+
+    #[test]
+    fn should_not_price_deposit_before_quotations() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::external_deposit_mock();
+        let deposit = trades.all().trades()[0].clone();
+        let quotes = |since: u32| {
+            let mut history = HashMap::new();
+            history.insert(
+                Asset::Currency(Currency::Crypto(CryptoCurrency::Btc)),
+                Quotes::from(vec![
+                    Quote::eur(date(since), dec!(30000.0)),
+                    Quote::eur(date(since + 1), dec!(35000.0)),
+                ]),
+            );
+            QuoteDatabase::from_history(history, deposit.timestamp())
+        };
+        // quotations start after the deposit (2022-04-05)
+        let mut cost_basis = CostBasis::default();
+        cost_basis.resolve_market_prices(&trades, &quotes(5));
+        assert_eq!(
+            cost_basis.cost_of(&deposit).source,
+            CostBasisSource::Missing
+        );
+        // quotations cover the deposit
+        let mut cost_basis = CostBasis::default();
+        cost_basis.resolve_market_prices(&trades, &quotes(3));
+        let basis = cost_basis.cost_of(&deposit);
+        assert_eq!(basis.source, CostBasisSource::MarketValue);
+        assert_eq!(basis.cost, dec!(3500.0));
+    }
+
+    fn date(month: u32) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc
+            .with_ymd_and_hms(2022, month, 1, 0, 0, 0)
+            .unwrap()
+    }
+}
@@ -0,0 +1,305 @@
+//! # Inventory
+//!
+//! This module exposes the inventory of the lots held at a certain date, which can be exported at the end of
+//! the year and used as the opening positions for the next one.
+
+use bitpanda_csv::{Asset, Currency};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use crate::database::WalletDatabase;
+
+/// Current version of the inventory file format
+pub const INVENTORY_VERSION: u32 = 1;
+
+/// The lots held by the investor at `date`.
+///
+/// Stored as a JSON file like:
+///
+/// ```json
+/// {
+///     "version": 1,
+///     "date": "2022-12-31T23:59:59+01:00",
+///     "lots": [
+///         {
+///             "asset": "BTC",
+///             "transaction_id": "T2cbcc5dd-67c1-4ded-8020-000000000000",
+///             "acquired_at": "2021-05-03T10:00:00+02:00",
+///             "quantity": "0.1",
+///             "cost": "1500.0"
+///         },
+///         {
+///             "asset": "ETH",
+///             "transaction_id": "T8a2d1c4e-1b3f-4e5a-9c7d-000000000000",
+///             "acquired_at": "2021-06-10T12:00:00+02:00",
+///             "quantity": "1.0",
+///             "cost": "2000.0",
+///             "parked": {
+///                 "withdrawal": "Cd0386774-b60a-4f60-bc1e-000000000000",
+///                 "withdrawn_at": "2022-11-20T09:00:00+01:00"
+///             }
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Version of the file format
+    pub version: u32,
+    /// The date the inventory refers to
+    pub date: DateTime<FixedOffset>,
+    /// Lots held at `date`
+    pub lots: Vec<InventoryLot>,
+}
+
+/// A lot held by the investor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryLot {
+    /// Held asset
+    #[serde(with = "asset_format")]
+    pub asset: Asset,
+    /// Transaction ID of the trade which brought the lot into the wallet
+    pub transaction_id: String,
+    /// When the lot has been acquired
+    pub acquired_at: DateTime<FixedOffset>,
+    /// Held quantity
+    pub quantity: Decimal,
+    /// The FIAT amount paid for `quantity`
+    pub cost: Decimal,
+    /// Set if the lot has been withdrawn with a self-transfer and hasn't been deposited back yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parked: Option<ParkedLot>,
+}
+
+/// The self-transfer withdrawal which took a lot out of the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParkedLot {
+    /// Transaction ID of the withdrawal
+    pub withdrawal: String,
+    /// Withdrawal time
+    pub withdrawn_at: DateTime<FixedOffset>,
+}
+
+/// A difference between the quantity of an asset in the closing inventory and the one expected from the opening
+/// inventory and the CSV trades
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryMismatch {
+    pub asset: Asset,
+    /// Quantity according to the closing inventory
+    pub inventory: Decimal,
+    /// Quantity according to the opening inventory plus the CSV trades of the period
+    pub csv: Decimal,
+}
+
+impl Inventory {
+    /// Instantiate a new `Inventory` at `date`
+    pub fn new(date: DateTime<FixedOffset>, lots: Vec<InventoryLot>) -> Self {
+        Self {
+            version: INVENTORY_VERSION,
+            date,
+            lots,
+        }
+    }
+
+    /// Load inventory from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading inventory from {}", path.display());
+        let file = File::open(path)?;
+        let inventory: Self = serde_json::from_reader(file)?;
+        if inventory.version > INVENTORY_VERSION {
+            anyhow::bail!(
+                "unsupported inventory version {} (max supported: {})",
+                inventory.version,
+                INVENTORY_VERSION
+            );
+        }
+        info!(
+            "found {} lots in inventory at {}",
+            inventory.lots.len(),
+            inventory.date
+        );
+        Ok(inventory)
+    }
+
+    /// Write inventory as JSON to file at `path`
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        debug!("writing inventory to {}", path.display());
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Returns the quantity held for each asset, excluding the lots parked outside of the wallet
+    pub fn quantities(&self) -> HashMap<Asset, Decimal> {
+        let mut quantities = HashMap::new();
+        for lot in self.lots.iter().filter(|x| x.parked.is_none()) {
+            *quantities.entry(lot.asset.clone()).or_insert(Decimal::ZERO) += lot.quantity;
+        }
+        quantities
+    }
+
+    /// Returns the assets of the lots, including the parked ones
+    pub fn assets(&self) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = Vec::new();
+        for lot in self.lots.iter() {
+            if !assets.contains(&lot.asset) {
+                assets.push(lot.asset.clone());
+            }
+        }
+        assets
+    }
+
+    /// Compare the quantity of each asset in this closing inventory with the quantity in the `opening` inventory plus
+    /// the `balances` of the CSV trades made since then, so that both sides cover the same trades.
+    /// FIAT currencies are ignored, since they are not tracked in lots
+    pub fn reconcile(
+        &self,
+        opening: &Inventory,
+        balances: &WalletDatabase,
+    ) -> Vec<InventoryMismatch> {
+        let quantities = self.quantities();
+        let opening_quantities = opening.quantities();
+        let mut assets: Vec<&Asset> = quantities
+            .keys()
+            .chain(opening_quantities.keys())
+            .chain(balances.iter().map(|(asset, _)| asset))
+            .filter(|asset| !matches!(asset, Asset::Currency(Currency::Fiat(_))))
+            .collect();
+        assets.sort_by_key(|x| x.to_string());
+        assets.dedup();
+        assets
+            .into_iter()
+            .filter_map(|asset| {
+                let inventory = quantities.get(asset).copied().unwrap_or_default();
+                let csv = opening_quantities.get(asset).copied().unwrap_or_default()
+                    + balances.balance(asset).unwrap_or_default();
+                if inventory == csv {
+                    None
+                } else {
+                    warn!(
+                        "inventory quantity of {} ({}) doesn't match CSV balance ({})",
+                        asset, inventory, csv
+                    );
+                    Some(InventoryMismatch {
+                        asset: asset.clone(),
+                        inventory,
+                        csv,
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+/// Serialize assets the same way they are written in the Bitpanda CSV, so that they can be deserialized
+/// with the `Deserialize` implementation of `Asset`
+mod asset_format {
+
+    use bitpanda_csv::Asset;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::database::asset_name;
+
+    pub fn serialize<S>(asset: &Asset, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match asset {
+            Asset::HongKong(id) => serializer.serialize_i64(*id),
+            asset => serializer.serialize_str(&asset_name(asset)),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Asset, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Asset::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use bitpanda_csv::{CryptoCurrency, Metal};
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_serialize_and_deserialize_inventory() {
+        crate::mock::log();
+        let inventory = Inventory::new(
+            date(),
+            vec![
+                lot(Asset::Currency(Currency::Crypto(CryptoCurrency::Btc))),
+                lot(Asset::Metal(Metal::Gold)),
+                lot(Asset::HongKong(1177)),
+                lot(Asset::Ticker(String::from("AMZN"))),
+            ],
+        );
+        let json = serde_json::to_string(&inventory).unwrap();
+        let decoded: Inventory = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.version, INVENTORY_VERSION);
+        assert_eq!(decoded.date, date());
+        assert_eq!(
+            decoded
+                .lots
+                .iter()
+                .map(|x| x.asset.clone())
+                .collect::<Vec<Asset>>(),
+            inventory
+                .lots
+                .iter()
+                .map(|x| x.asset.clone())
+                .collect::<Vec<Asset>>()
+        );
+        assert_eq!(decoded.lots[0].quantity, dec!(1.5));
+    }
+
+    #[test]
+    fn should_reconcile_inventory_with_csv_balances() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::mock();
+        let balances = WalletDatabase::load(&trades.all());
+        let amazon = Asset::Ticker(String::from("AMZN"));
+        // 2 AMZN held before the trades, which buy 3 and sell 2
+        let mut opening_lot = lot(amazon.clone());
+        opening_lot.quantity = dec!(2.0);
+        let opening = Inventory::new(date(), vec![opening_lot]);
+        let mut closing_lot = lot(amazon.clone());
+        closing_lot.quantity = dec!(3.0);
+        let inventory = Inventory::new(date(), vec![closing_lot]);
+        let mismatches = inventory.reconcile(&opening, &balances);
+        assert!(!mismatches.iter().any(|x| x.asset == amazon));
+        let tesla = mismatches
+            .iter()
+            .find(|x| x.asset == Asset::Ticker(String::from("TSLA")))
+            .unwrap();
+        assert_eq!(tesla.inventory, Decimal::ZERO);
+        assert_eq!(tesla.csv, dec!(1.0));
+    }
+
+    fn lot(asset: Asset) -> InventoryLot {
+        InventoryLot {
+            asset,
+            transaction_id: String::from("T1"),
+            acquired_at: date(),
+            quantity: dec!(1.5),
+            cost: dec!(100.0),
+            parked: None,
+        }
+    }
+
+    fn date() -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 12, 31, 23, 59, 59)
+            .unwrap()
+    }
+}
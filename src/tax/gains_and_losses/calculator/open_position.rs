@@ -0,0 +1,113 @@
+//! # Open position
+//!
+//! This module exposes the types to describe the assets still held by the investor at the end of the time range
+
+use bitpanda_csv::Asset;
+use rust_decimal::Decimal;
+
+/// An open position is the quantity of an asset still held in the wallet
+#[derive(Debug, Clone)]
+pub struct OpenPosition {
+    /// Held asset
+    pub asset: Asset,
+    /// Held quantity
+    pub quantity: Decimal,
+    /// The FIAT amount paid for the held quantity
+    pub cost: Decimal,
+    /// Market price of the asset at the end of the time range; `None` if the price is not available
+    pub market_price: Option<Decimal>,
+    /// The percentage which would be applied to the gain if the position was sold
+    pub tax_percentage: Decimal,
+}
+
+impl OpenPosition {
+    /// Returns the market value of the position
+    pub fn market_value(&self) -> Option<Decimal> {
+        self.market_price.map(|price| price * self.quantity)
+    }
+
+    /// Returns the unrealized gain (if positive) or loss (if negative) of the position
+    pub fn unrealized(&self) -> Option<Decimal> {
+        self.market_value().map(|value| value - self.cost)
+    }
+
+    /// Returns the tax which would be paid if the position was sold at market price.
+    /// Losses don't pay any tax
+    pub fn latent_tax(&self) -> Option<Decimal> {
+        self.unrealized().map(|diff| {
+            if diff.is_sign_positive() {
+                (diff * self.tax_percentage) / dec!(100.0)
+            } else {
+                Decimal::ZERO
+            }
+        })
+    }
+}
+
+/// Report of the open positions at the end of the time range
+#[derive(Debug, Default, Clone)]
+pub struct OpenPositionsReport {
+    pub positions: Vec<OpenPosition>,
+}
+
+impl OpenPositionsReport {
+    /// Returns the total cost of the open positions
+    pub fn cost(&self) -> Decimal {
+        self.positions.iter().map(|x| x.cost).sum()
+    }
+
+    /// Returns the total unrealized gain or loss of the positions with a market price
+    pub fn unrealized(&self) -> Decimal {
+        self.positions.iter().filter_map(|x| x.unrealized()).sum()
+    }
+
+    /// Returns the total latent tax of the positions with a market price
+    pub fn latent_tax(&self) -> Decimal {
+        self.positions.iter().filter_map(|x| x.latent_tax()).sum()
+    }
+
+    /// Iterate over the positions without a market price
+    pub fn unpriced(&self) -> impl Iterator<Item = &OpenPosition> {
+        self.positions.iter().filter(|x| x.market_price.is_none())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use bitpanda_csv::Metal;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_calc_unrealized_gains_and_latent_tax() {
+        crate::mock::log();
+        let report = OpenPositionsReport {
+            positions: vec![
+                position(Metal::Gold, dec!(100.0), Some(dec!(60.0))),
+                position(Metal::Silver, dec!(100.0), Some(dec!(40.0))),
+                position(Metal::Platinum, dec!(100.0), None),
+            ],
+        };
+        assert_eq!(report.positions[0].market_value(), Some(dec!(120.0)));
+        assert_eq!(report.positions[0].unrealized(), Some(dec!(20.0)));
+        assert_eq!(report.positions[0].latent_tax(), Some(dec!(5.2)));
+        assert_eq!(report.positions[1].unrealized(), Some(dec!(-20.0)));
+        assert_eq!(report.positions[1].latent_tax(), Some(Decimal::ZERO));
+        assert_eq!(report.cost(), dec!(300.0));
+        assert_eq!(report.unrealized(), Decimal::ZERO);
+        assert_eq!(report.latent_tax(), dec!(5.2));
+        assert_eq!(report.unpriced().count(), 1);
+    }
+
+    fn position(metal: Metal, cost: Decimal, market_price: Option<Decimal>) -> OpenPosition {
+        OpenPosition {
+            asset: Asset::Metal(metal),
+            quantity: dec!(2.0),
+            cost,
+            market_price,
+            tax_percentage: dec!(26.0),
+        }
+    }
+}
@@ -0,0 +1,615 @@
+//! # Self transfer
+//!
+//! This module exposes the types to track crypto withdrawals which are transfers towards wallets
+//! owned by the investor. The assets withdrawn are not sold, so their lots are parked until they
+//! come back to Bitpanda with a deposit.
+
+use bitpanda_csv::{Asset, AssetClass, Trade, TransactionType};
+use chrono::{DateTime, Duration, FixedOffset};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use super::wallet::Block;
+use crate::database::{TradeQuery, TradeSet, Venue};
+
+/// Maximum hours between the withdrawal from a venue and the deposit of the same quantity into another venue,
+/// to treat them as a transfer between two accounts of the investor
+const VENUE_TRANSFER_WINDOW_HOURS: i64 = 24;
+
+/// Describes which withdrawals are self-transfers and how they must be matched with the deposits.
+///
+/// Loaded from a JSON file like:
+///
+/// ```json
+/// {
+///     "auto_match": { "window_days": 30, "tolerance": 1.0 },
+///     "transfers": [
+///         { "withdrawal": "C04e9125e-9688-4fbb-b23b-000000000000", "deposit": "C1a2b3c4d-..." },
+///         { "withdrawal": "Cd0386774-b60a-4f60-bc1e-000000000000" }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct SelfTransfers {
+    /// If set, transfers without an explicit deposit are matched automatically
+    #[serde(default)]
+    auto_match: Option<AutoMatch>,
+    /// Withdrawals marked as self-transfers
+    #[serde(default)]
+    transfers: Vec<SelfTransfer>,
+}
+
+/// Rules to automatically match a deposit with a parked withdrawal
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoMatch {
+    /// Maximum amount of days between withdrawal and deposit
+    window_days: i64,
+    /// Maximum percentage of the withdrawn amount which can be lost along the transfer (e.g. network fees)
+    #[serde(default)]
+    tolerance: Decimal,
+}
+
+/// A withdrawal marked as self-transfer by the user
+#[derive(Debug, Clone, Deserialize)]
+pub struct SelfTransfer {
+    /// Transaction ID of the withdrawal
+    withdrawal: String,
+    /// Transaction ID of the deposit which brings the assets back, if known
+    #[serde(default)]
+    deposit: Option<String>,
+}
+
+impl SelfTransfers {
+    /// Load self transfers from JSON file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        debug!("loading self transfers from {}", path.display());
+        let file = File::open(path)?;
+        let self_transfers: Self = serde_json::from_reader(file)?;
+        info!(
+            "found {} withdrawals marked as self-transfers",
+            self_transfers.transfers.len()
+        );
+        Ok(self_transfers)
+    }
+
+    /// Returns whether `trade` is a withdrawal marked as self-transfer
+    pub fn is_self_transfer(&self, trade: &Trade) -> bool {
+        trade.transaction_type() == TransactionType::Withdrawal
+            && trade.asset_class() != AssetClass::Fiat
+            && self.marked(trade.transaction_id()).is_some()
+    }
+
+    /// Get the self-transfer marked for the withdrawal with the provided transaction id
+    fn marked(&self, withdrawal: &str) -> Option<&SelfTransfer> {
+        self.transfers.iter().find(|x| x.withdrawal == withdrawal)
+    }
+
+    /// Returns whether the deposit with the provided transaction ID has been explicitly associated to a withdrawal
+    fn is_explicit_deposit(&self, deposit: &str) -> bool {
+        self.transfers
+            .iter()
+            .any(|x| x.deposit.as_deref() == Some(deposit))
+    }
+}
+
+/// A withdrawal whose lots are parked outside of Bitpanda
+#[derive(Debug, Clone)]
+pub struct ParkedTransfer {
+    /// Transaction ID of the withdrawal
+    pub withdrawal: String,
+    /// Withdrawal time
+    pub timestamp: DateTime<FixedOffset>,
+    /// Withdrawn asset
+    pub asset: Asset,
+    /// Lots taken from the wallet
+    blocks: Vec<Block>,
+}
+
+impl ParkedTransfer {
+    /// Instantiate a new `ParkedTransfer` for the `blocks` taken from the wallet by `withdrawal`
+    pub fn new(
+        withdrawal: impl ToString,
+        timestamp: DateTime<FixedOffset>,
+        asset: Asset,
+        blocks: Vec<Block>,
+    ) -> Self {
+        Self {
+            withdrawal: withdrawal.to_string(),
+            timestamp,
+            asset,
+            blocks,
+        }
+    }
+
+    /// Lots taken from the wallet
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Withdrawn quantity, which is the quantity of the parked lots
+    pub fn amount_asset(&self) -> Decimal {
+        self.blocks.iter().map(|x| x.amount_asset()).sum()
+    }
+
+    /// Total cost of the parked lots
+    pub fn amount_fiat(&self) -> Decimal {
+        self.blocks.iter().map(|x| x.amount_fiat()).sum()
+    }
+}
+
+/// A parked withdrawal which has been brought back to Bitpanda by a deposit
+#[derive(Debug, Clone)]
+pub struct MatchedTransfer {
+    /// The parked withdrawal
+    pub parked: ParkedTransfer,
+    /// Transaction ID of the deposit
+    pub deposit: String,
+    /// Deposited quantity
+    pub amount_asset: Decimal,
+}
+
+/// A deposit associated by the user to a withdrawal, which brings back more than what has been withdrawn
+#[derive(Debug, Clone)]
+pub struct RejectedTransfer {
+    /// Transaction ID of the withdrawal
+    pub withdrawal: String,
+    /// Transaction ID of the deposit
+    pub deposit: String,
+    /// Transferred asset
+    pub asset: Asset,
+    /// Withdrawn quantity
+    pub withdrawn: Decimal,
+    /// Deposited quantity
+    pub deposited: Decimal,
+}
+
+/// Outcome of the self-transfers tracking
+#[derive(Debug, Default, Clone)]
+pub struct SelfTransfersReport {
+    /// Withdrawals which have been matched with a deposit
+    pub matched: Vec<MatchedTransfer>,
+    /// Withdrawals whose assets have never come back
+    pub unmatched_withdrawals: Vec<ParkedTransfer>,
+    /// Deposits associated by the user to a withdrawal which have never been matched
+    pub unmatched_deposits: Vec<String>,
+    /// Deposits associated by the user to a withdrawal whose quantity is greater than the withdrawn one
+    pub rejected: Vec<RejectedTransfer>,
+}
+
+/// Keeps track of the parked lots while trades are processed
+#[derive(Debug, Default)]
+pub struct SelfTransferLedger {
+    config: SelfTransfers,
+    parked: Vec<ParkedTransfer>,
+    matched: Vec<MatchedTransfer>,
+    rejected: Vec<RejectedTransfer>,
+}
+
+impl From<SelfTransfers> for SelfTransferLedger {
+    fn from(config: SelfTransfers) -> Self {
+        Self {
+            config,
+            parked: Vec::new(),
+            matched: Vec::new(),
+            rejected: Vec::new(),
+        }
+    }
+}
+
+impl SelfTransferLedger {
+    /// Set the withdrawals marked as self-transfers, keeping the lots already parked
+    pub fn configure(&mut self, config: SelfTransfers) {
+        self.config = config;
+    }
+
+    /// Get the withdrawals whose lots are still parked
+    pub fn parked(&self) -> &[ParkedTransfer] {
+        &self.parked
+    }
+
+    /// Returns whether `trade` is a withdrawal marked as self-transfer
+    pub fn is_self_transfer(&self, trade: &Trade) -> bool {
+        self.config.is_self_transfer(trade)
+    }
+
+    /// Mark as self-transfers the withdrawals from a venue whose quantity is deposited into another venue
+    /// (e.g. from Bitpanda to One Trading), unless the user already associated them to a deposit
+    pub fn match_venue_transfers(&mut self, set: &TradeSet) {
+        let crypto_transfers = |transaction_type: TransactionType| {
+            TradeQuery::default()
+                .transaction_type(transaction_type)
+                .not(TradeQuery::default().asset_class(AssetClass::Fiat))
+        };
+        let withdrawals = crypto_transfers(TransactionType::Withdrawal);
+        let deposits = crypto_transfers(TransactionType::Deposit);
+        let trades: Vec<(Venue, &Trade)> = set.with_venues().collect();
+        let mut consumed: HashSet<&str> = HashSet::new();
+        for (withdrawal_venue, withdrawal) in trades.iter().filter(|(_, x)| withdrawals.matches(x))
+        {
+            let explicit = self
+                .config
+                .marked(withdrawal.transaction_id())
+                .map(|x| x.deposit.is_some())
+                .unwrap_or_default();
+            if explicit {
+                continue;
+            }
+            let Some((deposit_venue, deposit)) = trades.iter().find(|(venue, deposit)| {
+                deposits.matches(deposit)
+                    && venue != withdrawal_venue
+                    && deposit.asset() == withdrawal.asset()
+                    && deposit.amount_asset() == withdrawal.amount_asset()
+                    && deposit.timestamp() >= withdrawal.timestamp()
+                    && deposit.timestamp() - withdrawal.timestamp()
+                        <= Duration::hours(VENUE_TRANSFER_WINDOW_HOURS)
+                    && !self.config.is_explicit_deposit(deposit.transaction_id())
+                    && !consumed.contains(deposit.transaction_id())
+            }) else {
+                continue;
+            };
+            debug!(
+                "{} from {} and {} into {} are a transfer between venues",
+                withdrawal.transaction_id(),
+                withdrawal_venue,
+                deposit.transaction_id(),
+                deposit_venue
+            );
+            consumed.insert(deposit.transaction_id());
+            self.config
+                .transfers
+                .retain(|x| x.withdrawal != withdrawal.transaction_id());
+            self.config.transfers.push(SelfTransfer {
+                withdrawal: withdrawal.transaction_id().to_string(),
+                deposit: Some(deposit.transaction_id().to_string()),
+            });
+        }
+    }
+
+    /// Park the lots withdrawn by `trade`. The parked quantity is the one of the withdrawn blocks
+    pub fn park(&mut self, trade: &Trade, blocks: Vec<Block>) {
+        let parked = ParkedTransfer::new(
+            trade.transaction_id(),
+            trade.timestamp(),
+            trade.asset(),
+            blocks,
+        );
+        info!(
+            "parking {} units of {} withdrawn with {} (cost € {})",
+            parked.amount_asset(),
+            parked.asset,
+            parked.withdrawal,
+            parked.amount_fiat()
+        );
+        self.parked.push(parked);
+    }
+
+    /// Park again the lots of a withdrawal made before the calculated period (e.g. restored from the opening inventory)
+    pub fn park_transfer(&mut self, parked: ParkedTransfer) {
+        debug!(
+            "parking {} units of {} withdrawn with {} before the calculated period",
+            parked.amount_asset(),
+            parked.asset,
+            parked.withdrawal
+        );
+        self.parked.push(parked);
+    }
+
+    /// Try to match the deposit `trade` with a parked withdrawal.
+    /// If a match is found, returns the parked lots, scaled to the deposited quantity.
+    /// The cost of the lots is kept, so the assets lost along the transfer increase the cost of the remaining ones.
+    ///
+    /// A deposit associated by the user to a withdrawal is rejected if it brings back more than what has been withdrawn:
+    /// it can't be treated as an external deposit either, so an error is returned.
+    pub fn redeem(&mut self, trade: &Trade) -> anyhow::Result<Option<Vec<Block>>> {
+        if trade.transaction_type() != TransactionType::Deposit
+            || trade.asset_class() == AssetClass::Fiat
+        {
+            return Ok(None);
+        }
+        let amount_asset = trade.amount_asset().unwrap_or_default();
+        let index = match self.explicit_match(trade) {
+            Some(index) => {
+                let parked = &self.parked[index];
+                if amount_asset > parked.amount_asset() {
+                    self.rejected.push(RejectedTransfer {
+                        withdrawal: parked.withdrawal.clone(),
+                        deposit: trade.transaction_id().to_string(),
+                        asset: parked.asset.clone(),
+                        withdrawn: parked.amount_asset(),
+                        deposited: amount_asset,
+                    });
+                    anyhow::bail!(
+                        "deposit {} brings back {} units of {}, but only {} have been withdrawn with {}; fix the self-transfers",
+                        trade.transaction_id(),
+                        amount_asset,
+                        parked.asset,
+                        parked.amount_asset(),
+                        parked.withdrawal
+                    );
+                }
+                if amount_asset < parked.amount_asset() {
+                    warn!(
+                        "deposit {} brings back {} units of {} out of the {} withdrawn with {}",
+                        trade.transaction_id(),
+                        amount_asset,
+                        parked.asset,
+                        parked.amount_asset(),
+                        parked.withdrawal
+                    );
+                }
+                index
+            }
+            None => match self.auto_match(trade) {
+                Some(index) => index,
+                None => return Ok(None),
+            },
+        };
+        let parked = self.parked.remove(index);
+        info!(
+            "deposit {} brings back {} units of {} withdrawn with {}",
+            trade.transaction_id(),
+            amount_asset,
+            parked.asset,
+            parked.withdrawal
+        );
+        let blocks = Self::scale_blocks(&parked, amount_asset);
+        self.matched.push(MatchedTransfer {
+            parked,
+            deposit: trade.transaction_id().to_string(),
+            amount_asset,
+        });
+        Ok(Some(blocks))
+    }
+
+    /// Get the report of the self-transfers
+    pub fn report(&self) -> SelfTransfersReport {
+        let unmatched_deposits = self
+            .config
+            .transfers
+            .iter()
+            .filter_map(|x| x.deposit.as_ref())
+            .filter(|deposit| !self.matched.iter().any(|x| &x.deposit == *deposit))
+            .filter(|deposit| !self.rejected.iter().any(|x| &x.deposit == *deposit))
+            .cloned()
+            .collect();
+        SelfTransfersReport {
+            matched: self.matched.clone(),
+            unmatched_withdrawals: self.parked.clone(),
+            unmatched_deposits,
+            rejected: self.rejected.clone(),
+        }
+    }
+
+    /// Find the parked withdrawal which the user associated to this deposit
+    fn explicit_match(&self, trade: &Trade) -> Option<usize> {
+        self.parked.iter().position(|parked| {
+            self.config
+                .marked(&parked.withdrawal)
+                .and_then(|x| x.deposit.as_deref())
+                == Some(trade.transaction_id())
+                && parked.asset == trade.asset()
+        })
+    }
+
+    /// Find the oldest parked withdrawal which matches the deposit by asset, amount and time window
+    fn auto_match(&self, trade: &Trade) -> Option<usize> {
+        let rules = self.config.auto_match.as_ref()?;
+        if self.config.is_explicit_deposit(trade.transaction_id()) {
+            return None;
+        }
+        let amount_asset = trade.amount_asset().unwrap_or_default();
+        self.parked.iter().position(|parked| {
+            let explicit = self
+                .config
+                .marked(&parked.withdrawal)
+                .map(|x| x.deposit.is_some())
+                .unwrap_or_default();
+            let parked_amount = parked.amount_asset();
+            let min_amount = parked_amount - (parked_amount * rules.tolerance / dec!(100.0));
+            !explicit
+                && parked.asset == trade.asset()
+                && trade.timestamp() >= parked.timestamp
+                && trade.timestamp() - parked.timestamp <= Duration::days(rules.window_days)
+                && amount_asset <= parked_amount
+                && amount_asset >= min_amount
+        })
+    }
+
+    /// Scale the quantity of the parked blocks to `amount_asset`, keeping their cost
+    fn scale_blocks(parked: &ParkedTransfer, amount_asset: Decimal) -> Vec<Block> {
+        let parked_amount = parked.amount_asset();
+        if parked_amount.is_zero() || parked_amount == amount_asset {
+            return parked.blocks.clone();
+        }
+        parked
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut block = block.clone();
+                block.set_amount_asset(block.amount_asset() * amount_asset / parked_amount);
+                block
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::mock::database::DatabaseTradeMock;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_deserialize_self_transfers() {
+        crate::mock::log();
+        let config: SelfTransfers = serde_json::from_str(
+            r#"{
+                "auto_match": { "window_days": 30, "tolerance": 1.0 },
+                "transfers": [
+                    { "withdrawal": "W1", "deposit": "D1" },
+                    { "withdrawal": "W2" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.auto_match.as_ref().unwrap().window_days, 30);
+        assert_eq!(config.auto_match.as_ref().unwrap().tolerance, dec!(1.0));
+        assert_eq!(config.transfers.len(), 2);
+        assert_eq!(config.transfers[0].deposit.as_deref(), Some("D1"));
+        assert!(config.transfers[1].deposit.is_none());
+    }
+
+    #[test]
+    fn should_match_venue_deposit_once() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::double_venue_withdrawal_mock();
+        let set = trades.all();
+        let mut ledger = SelfTransferLedger::default();
+        ledger.match_venue_transfers(&set);
+        assert_eq!(ledger.config.transfers.len(), 1);
+        assert_eq!(
+            ledger.config.transfers[0].withdrawal,
+            set.trades()[1].transaction_id()
+        );
+        assert_eq!(
+            ledger.config.transfers[0].deposit.as_deref(),
+            Some(set.trades()[3].transaction_id())
+        );
+    }
+
+    #[test]
+    fn should_auto_match_deposit() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::self_transfer_mock();
+        let set = trades.all();
+        let withdrawal = set.trades()[1];
+        let deposit = set.trades()[2];
+        let mut ledger = SelfTransferLedger::from(
+            serde_json::from_str::<SelfTransfers>(&format!(
+                r#"{{
+                    "auto_match": {{ "window_days": 10, "tolerance": 1.0 }},
+                    "transfers": [{{ "withdrawal": "{}" }}]
+                }}"#,
+                withdrawal.transaction_id()
+            ))
+            .unwrap(),
+        );
+        assert!(ledger.is_self_transfer(withdrawal));
+        ledger.park(withdrawal, vec![Block::new(dec!(0.1), dec!(2000.0))]);
+        let blocks = ledger.redeem(deposit).unwrap().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].amount_asset(), dec!(0.0999));
+        assert_eq!(blocks[0].amount_fiat(), dec!(2000.0));
+        let report = ledger.report();
+        assert_eq!(report.matched.len(), 1);
+        assert!(report.unmatched_withdrawals.is_empty());
+        assert!(report.unmatched_deposits.is_empty());
+    }
+
+    #[test]
+    fn should_not_auto_match_deposit_out_of_window() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::self_transfer_mock();
+        let set = trades.all();
+        let withdrawal = set.trades()[1];
+        let deposit = set.trades()[2];
+        let mut ledger = SelfTransferLedger::from(
+            serde_json::from_str::<SelfTransfers>(&format!(
+                r#"{{
+                    "auto_match": {{ "window_days": 1, "tolerance": 1.0 }},
+                    "transfers": [{{ "withdrawal": "{}" }}]
+                }}"#,
+                withdrawal.transaction_id()
+            ))
+            .unwrap(),
+        );
+        ledger.park(withdrawal, vec![Block::new(dec!(0.1), dec!(2000.0))]);
+        assert!(ledger.redeem(deposit).unwrap().is_none());
+        let report = ledger.report();
+        assert!(report.matched.is_empty());
+        assert_eq!(report.unmatched_withdrawals.len(), 1);
+        assert_eq!(report.unmatched_withdrawals[0].amount_fiat(), dec!(2000.0));
+    }
+
+    #[test]
+    fn should_report_unmatched_explicit_deposit() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::self_transfer_mock();
+        let set = trades.all();
+        let withdrawal = set.trades()[1];
+        let deposit = set.trades()[2];
+        let mut ledger = SelfTransferLedger::from(
+            serde_json::from_str::<SelfTransfers>(&format!(
+                r#"{{
+                    "transfers": [{{ "withdrawal": "{}", "deposit": "unexisting" }}]
+                }}"#,
+                withdrawal.transaction_id()
+            ))
+            .unwrap(),
+        );
+        ledger.park(withdrawal, vec![Block::new(dec!(0.1), dec!(2000.0))]);
+        assert!(ledger.redeem(deposit).unwrap().is_none());
+        let report = ledger.report();
+        assert_eq!(report.unmatched_withdrawals.len(), 1);
+        assert_eq!(report.unmatched_deposits, vec![String::from("unexisting")]);
+    }
+
+    #[test]
+    fn should_reject_explicit_deposit_greater_than_withdrawal() {
+        crate::mock::log();
+        let config = |withdrawal: &Trade, deposit: &Trade| {
+            serde_json::from_str::<SelfTransfers>(&format!(
+                r#"{{
+                    "transfers": [{{ "withdrawal": "{}", "deposit": "{}" }}]
+                }}"#,
+                withdrawal.transaction_id(),
+                deposit.transaction_id()
+            ))
+            .unwrap()
+        };
+        // the deposit brings back 0.0999 out of 0.1
+        let trades = DatabaseTradeMock::self_transfer_mock();
+        let set = trades.all();
+        let (withdrawal, deposit) = (set.trades()[1], set.trades()[2]);
+        let mut ledger = SelfTransferLedger::from(config(withdrawal, deposit));
+        ledger.park(withdrawal, vec![Block::new(dec!(0.1), dec!(2000.0))]);
+        assert!(ledger.redeem(deposit).unwrap().is_some());
+        assert!(ledger.report().rejected.is_empty());
+        // only 0.05 have been withdrawn, but the deposit brings back 0.0999
+        let trades = DatabaseTradeMock::excess_self_transfer_mock();
+        let set = trades.all();
+        let (withdrawal, deposit) = (set.trades()[1], set.trades()[2]);
+        let mut ledger = SelfTransferLedger::from(config(withdrawal, deposit));
+        ledger.park(withdrawal, vec![Block::new(dec!(0.05), dec!(1000.0))]);
+        assert!(ledger.redeem(deposit).is_err());
+        let report = ledger.report();
+        assert!(report.matched.is_empty());
+        assert_eq!(report.unmatched_withdrawals.len(), 1);
+        assert!(report.unmatched_deposits.is_empty());
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].withdrawn, dec!(0.05));
+        assert_eq!(report.rejected[0].deposited, dec!(0.0999));
+    }
+
+    #[test]
+    fn should_park_the_quantity_of_the_withdrawn_blocks() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::self_transfer_mock();
+        let set = trades.all();
+        let withdrawal = set.trades()[1];
+        let mut ledger = SelfTransferLedger::default();
+        ledger.park(
+            withdrawal,
+            vec![
+                Block::new(dec!(0.06), dec!(1200.0)),
+                Block::new(dec!(0.04), dec!(900.0)),
+            ],
+        );
+        assert_eq!(ledger.parked()[0].amount_asset(), dec!(0.1));
+        assert_eq!(ledger.parked()[0].amount_fiat(), dec!(2100.0));
+    }
+}
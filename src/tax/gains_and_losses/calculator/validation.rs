@@ -0,0 +1,69 @@
+//! # Validation
+//!
+//! This module exposes the report of the problems found replaying the trades through the lot ledger
+
+use bitpanda_csv::{Asset, Fiat};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+
+/// Report of the problems found while replaying the trades
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    /// Trades checked
+    pub trades: usize,
+    /// Problems found, sorted by the time of the trade
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// A problem found in a trade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// Transaction ID of the trade
+    pub transaction_id: String,
+    /// Time of the trade
+    pub timestamp: DateTime<FixedOffset>,
+    /// Traded asset
+    pub asset: Asset,
+    /// The problem found
+    pub kind: ValidationIssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// The trade spends more than the quantity held at that point in time
+    NegativeBalance {
+        available: Decimal,
+        required: Decimal,
+    },
+    /// The trade spends an asset which has never been bought or deposited before
+    SellWithoutBuy { required: Decimal },
+    /// The quotations of the asset can't be looked up: it has no Yahoo Finance symbol mapped to it and it isn't listed
+    /// on Bitpanda
+    UnknownAsset,
+    /// An incoming transfer, which has been treated as a stock split
+    TransferAsSplit { amount: Decimal },
+    /// The trade is not settled in EUR
+    NonEurTrade { fiat: Fiat },
+}
+
+impl ValidationReport {
+    /// Returns whether no problem has been found
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns whether some problem prevents the gains and losses calculation
+    pub fn is_blocking(&self) -> bool {
+        self.issues.iter().any(|issue| issue.kind.is_blocking())
+    }
+}
+
+impl ValidationIssueKind {
+    /// Returns whether the problem makes the gains and losses calculation fail
+    pub fn is_blocking(&self) -> bool {
+        matches!(
+            self,
+            Self::NegativeBalance { .. } | Self::SellWithoutBuy { .. }
+        )
+    }
+}
@@ -1,5 +1,6 @@
 //! # Wallet
 
+use chrono::{DateTime, FixedOffset};
 use rust_decimal::Decimal;
 
 /// A wallet represents the quantity of an asset hold by the investor
@@ -10,8 +11,14 @@ pub struct Wallet {
 
 impl Wallet {
     /// Buy (add) a block to the wallet
-    pub fn buy(&mut self, amount_asset: Decimal, amount_fiat: Decimal) {
-        self.blocks.push(Block::new(amount_asset, amount_fiat));
+    pub fn buy(&mut self, amount_asset: Decimal, amount_fiat: Decimal, origin: LotOrigin) {
+        self.blocks
+            .push(Block::new(amount_asset, amount_fiat).with_origin(origin));
+    }
+
+    /// Returns the blocks held in the wallet, from the oldest to the newest
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
     }
 
     /// Returns the total fiat amount of the wallet
@@ -24,10 +31,10 @@ impl Wallet {
         self.blocks.iter().map(|x| x.amount_asset).sum()
     }
 
-    /// Sell an asset amount hold in the wallet.
-    /// Returns the FIAT amount sold (NOTE: refers to the buy price, not to the sell price)
+    /// Take an asset amount out of the wallet, keeping the blocks it was made of.
+    /// Returns the blocks taken from the wallet, sorted as they were in the wallet.
     /// Returns error if `amount_asset > self.amount_asset()`
-    pub fn sell(&mut self, amount_asset: Decimal) -> anyhow::Result<Decimal> {
+    pub fn withdraw(&mut self, amount_asset: Decimal) -> anyhow::Result<Vec<Block>> {
         debug!(
             "spending {} in wallet (current amount: {})",
             amount_asset,
@@ -44,7 +51,7 @@ impl Wallet {
         let blocks = self.blocks.clone();
         self.blocks = Vec::with_capacity(blocks.len());
         let mut remaining_assets_to_sell = amount_asset;
-        let mut amount_fiat = Decimal::ZERO;
+        let mut withdrawn = Vec::new();
         // sell blocks
         for block in blocks.into_iter() {
             if remaining_assets_to_sell.is_zero() {
@@ -56,8 +63,8 @@ impl Wallet {
                     "sold entire block ({}; € {})",
                     block.amount_asset, block.amount_fiat
                 );
-                amount_fiat += block.amount_fiat;
                 remaining_assets_to_sell -= block.amount_asset;
+                withdrawn.push(block);
             } else {
                 // remaining_assets_to_sell < block.amount_asset
                 let (unsold_block, sold_block) =
@@ -73,27 +80,59 @@ impl Wallet {
                 remaining_assets_to_sell = Decimal::ZERO;
                 // push unsold block to wallet
                 self.blocks.push(unsold_block);
-                // increment amount fiat by sold block value
-                amount_fiat += sold_block.amount_fiat;
+                withdrawn.push(sold_block);
             }
         }
-        debug!(
-            "sold {} assets, which is worth € {}",
-            amount_asset, amount_fiat
-        );
-        Ok(amount_fiat)
+        Ok(withdrawn)
+    }
+
+    /// Put back into the wallet blocks previously taken out with `withdraw`.
+    /// Each restored block is placed before the first block acquired after it, so that the oldest
+    /// blocks are still spent first.
+    pub fn restore(&mut self, blocks: Vec<Block>) {
+        debug!("restoring {} blocks into wallet", blocks.len());
+        for block in blocks.into_iter() {
+            let index = self
+                .blocks
+                .iter()
+                .position(|x| x.origin.acquired_at > block.origin.acquired_at)
+                .unwrap_or(self.blocks.len());
+            self.blocks.insert(index, block);
+        }
     }
 
     /// Perform a stock split on the wallet.
-    /// All the currents blocks are replaced by a new block, which is so composed:
-    ///
-    /// - the fiat amount is equal to the sum of all the current block's fiat amount
-    /// - the asset amount is `amount_asset` + `self.amount_asset`
-    pub fn stock_split(&mut self, amount_asset: Decimal) {
-        let amount_fiat = self.amount_fiat();
-        let new_amount_asset = self.amount_asset() + amount_asset;
-        debug!("stock split: ({}; € {})", new_amount_asset, amount_fiat);
-        self.blocks = vec![Block::new(new_amount_asset, amount_fiat)];
+    /// `amount_asset` new shares are spread over the current blocks, proportionally to their quantity.
+    /// Each block keeps its fiat amount and its origin, so holding periods survive the split.
+    /// The last block takes the rounding remainder, so that the wallet grows exactly by `amount_asset`.
+    /// If the wallet is empty, the new shares have no cost and are acquired with the split (`origin`)
+    pub fn stock_split(&mut self, amount_asset: Decimal, origin: LotOrigin) {
+        let current_amount_asset = self.amount_asset();
+        if current_amount_asset.is_zero() {
+            warn!(
+                "stock split {} on an empty wallet: {} shares acquired at no cost",
+                origin.transaction_id, amount_asset
+            );
+            self.blocks
+                .push(Block::new(amount_asset, Decimal::ZERO).with_origin(origin));
+            return;
+        }
+        let ratio = (current_amount_asset + amount_asset) / current_amount_asset;
+        let mut remaining = amount_asset;
+        let last = self.blocks.len() - 1;
+        for (i, block) in self.blocks.iter_mut().enumerate() {
+            let added = if i == last {
+                remaining
+            } else {
+                block.amount_asset * ratio - block.amount_asset
+            };
+            remaining -= added;
+            block.amount_asset += added;
+            debug!(
+                "stock split: ({}; € {})",
+                block.amount_asset, block.amount_fiat
+            );
+        }
     }
 
     /// Sell partial block. Starting from the amount_asset; which must be LESS THAN block.amount_asset, returns two blocks.
@@ -106,12 +145,33 @@ impl Wallet {
 }
 
 /// A wallet block represents a spendible amount of a certain asset
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Block {
     /// The quantity of the block
     amount_asset: Decimal,
     /// The FIAT value of this block
     amount_fiat: Decimal,
+    /// Where the block comes from
+    origin: LotOrigin,
+}
+
+/// Describes the trade which brought a lot into the wallet
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LotOrigin {
+    /// Transaction ID of the buy (or deposit)
+    pub transaction_id: String,
+    /// When the lot has been acquired
+    pub acquired_at: DateTime<FixedOffset>,
+}
+
+impl LotOrigin {
+    /// Instantiate a new `LotOrigin`
+    pub fn new(transaction_id: impl ToString, acquired_at: DateTime<FixedOffset>) -> Self {
+        Self {
+            transaction_id: transaction_id.to_string(),
+            acquired_at,
+        }
+    }
 }
 
 impl Block {
@@ -120,9 +180,37 @@ impl Block {
         Self {
             amount_asset,
             amount_fiat,
+            origin: LotOrigin::default(),
         }
     }
 
+    /// Set the origin of the block
+    pub fn with_origin(mut self, origin: LotOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Where the block comes from
+    pub fn origin(&self) -> &LotOrigin {
+        &self.origin
+    }
+
+    /// The quantity of the block
+    pub fn amount_asset(&self) -> Decimal {
+        self.amount_asset
+    }
+
+    /// The FIAT value of this block
+    pub fn amount_fiat(&self) -> Decimal {
+        self.amount_fiat
+    }
+
+    /// Change the quantity of the block, keeping its FIAT value.
+    /// Used when the quantity shrinks (e.g. network fees on a transfer) but the cost sustained doesn't change
+    pub fn set_amount_asset(&mut self, amount_asset: Decimal) {
+        self.amount_asset = amount_asset;
+    }
+
     /// Sell a fraction of the block. The amount is subtracted from this block and the fraction is returned
     pub fn sell_fraction(&mut self, amount_asset: Decimal) -> Self {
         // calc new amount_fiat => self.amount_fiat : self.amount_asset = x : amount_asset
@@ -131,7 +219,7 @@ impl Block {
         self.amount_fiat -= fraction_amount_fiat;
         self.amount_asset -= amount_asset;
         // return fraction
-        Block::new(amount_asset, fraction_amount_fiat)
+        Block::new(amount_asset, fraction_amount_fiat).with_origin(self.origin.clone())
     }
 }
 
@@ -153,8 +241,9 @@ mod test {
     #[test]
     fn should_sell_block_fraction() {
         crate::mock::log();
-        let mut block = Block::new(dec!(2.25), dec!(186.32));
+        let mut block = Block::new(dec!(2.25), dec!(186.32)).with_origin(origin("T1"));
         let fraction = block.sell_fraction(dec!(0.75));
+        assert_eq!(fraction.origin(), &origin("T1"));
         assert_eq!(block.amount_asset, dec!(1.5));
         assert_eq!(block.amount_fiat.round_dp(2), dec!(124.21));
         assert_eq!(fraction.amount_asset, dec!(0.75));
@@ -166,72 +255,187 @@ mod test {
         crate::mock::log();
         let mut wallet = Wallet::default();
         assert!(wallet.blocks.is_empty());
-        wallet.buy(dec!(2.0), dec!(186.32));
-        wallet.buy(dec!(0.5), dec!(68.78));
-        wallet.buy(dec!(1.25), dec!(104.32));
+        wallet.buy(dec!(2.0), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.5), dec!(68.78), LotOrigin::default());
+        wallet.buy(dec!(1.25), dec!(104.32), LotOrigin::default());
         assert_eq!(wallet.blocks.len(), 3);
         assert_eq!(wallet.amount_asset(), dec!(3.75));
         assert_eq!(wallet.amount_fiat(), dec!(359.42));
     }
 
     #[test]
-    fn should_sell_wallet_blocks_entire_block() {
+    fn should_withdraw_wallet_blocks_entire_block() {
         crate::mock::log();
         let mut wallet = Wallet::default();
-        wallet.buy(dec!(2.0), dec!(186.32));
-        wallet.buy(dec!(0.5), dec!(68.78));
-        wallet.buy(dec!(1.25), dec!(104.32));
-        // sell
-        assert_eq!(wallet.sell(dec!(2.0)).unwrap(), dec!(186.32));
+        wallet.buy(dec!(2.0), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.5), dec!(68.78), LotOrigin::default());
+        wallet.buy(dec!(1.25), dec!(104.32), LotOrigin::default());
+        // withdraw
+        assert_eq!(
+            withdrawn_fiat(wallet.withdraw(dec!(2.0)).unwrap()),
+            dec!(186.32)
+        );
         assert_eq!(wallet.amount_asset(), dec!(1.75));
         assert_eq!(wallet.amount_fiat(), dec!(173.10));
     }
 
     #[test]
-    fn should_sell_wallet_blocks_entire_wallet() {
+    fn should_withdraw_wallet_blocks_entire_wallet() {
         crate::mock::log();
         let mut wallet = Wallet::default();
-        wallet.buy(dec!(2.0), dec!(186.32));
-        wallet.buy(dec!(0.5), dec!(68.78));
-        wallet.buy(dec!(1.25), dec!(104.32));
-        // sell
-        assert_eq!(wallet.sell(dec!(3.75)).unwrap(), dec!(359.42));
+        wallet.buy(dec!(2.0), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.5), dec!(68.78), LotOrigin::default());
+        wallet.buy(dec!(1.25), dec!(104.32), LotOrigin::default());
+        // withdraw
+        assert_eq!(
+            withdrawn_fiat(wallet.withdraw(dec!(3.75)).unwrap()),
+            dec!(359.42)
+        );
         assert_eq!(wallet.amount_asset(), dec!(0));
         assert_eq!(wallet.amount_fiat(), dec!(0));
     }
 
     #[test]
-    fn should_sell_wallet_blocks_partial() {
+    fn should_withdraw_wallet_blocks_partial() {
         crate::mock::log();
         let mut wallet = Wallet::default();
-        wallet.buy(dec!(2.0), dec!(186.32));
-        wallet.buy(dec!(0.5), dec!(68.78));
-        wallet.buy(dec!(1.25), dec!(104.32));
-        // sell
-        assert_eq!(wallet.sell(dec!(2.40)).unwrap(), dec!(241.344)); // 55.024
+        wallet.buy(dec!(2.0), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.5), dec!(68.78), LotOrigin::default());
+        wallet.buy(dec!(1.25), dec!(104.32), LotOrigin::default());
+        // withdraw
+        assert_eq!(
+            withdrawn_fiat(wallet.withdraw(dec!(2.40)).unwrap()),
+            dec!(241.344)
+        ); // 55.024
         assert_eq!(wallet.amount_asset(), dec!(1.35));
         assert_eq!(wallet.amount_fiat(), dec!(118.076));
     }
 
     #[test]
-    fn should_fail_selling_wallet_blocks_if_more_than_balance() {
+    fn should_fail_withdrawing_wallet_blocks_if_more_than_balance() {
         crate::mock::log();
         let mut wallet = Wallet::default();
-        wallet.buy(dec!(2.0), dec!(186.32));
-        wallet.buy(dec!(0.5), dec!(68.78));
-        assert!(wallet.sell(dec!(5.0)).is_err());
+        wallet.buy(dec!(2.0), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.5), dec!(68.78), LotOrigin::default());
+        assert!(wallet.withdraw(dec!(5.0)).is_err());
+        // the wallet is left untouched
+        assert_eq!(wallet.amount_asset(), dec!(2.5));
+    }
+
+    #[test]
+    fn should_withdraw_and_restore_blocks() {
+        crate::mock::log();
+        let mut wallet = Wallet::default();
+        wallet.buy(dec!(2.0), dec!(186.32), origin_at("T1", 2022, 1, 1));
+        wallet.buy(dec!(0.5), dec!(68.78), origin_at("T2", 2022, 1, 2));
+        wallet.buy(dec!(1.25), dec!(104.32), origin_at("T3", 2022, 1, 3));
+        let blocks = wallet.withdraw(dec!(2.40)).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].amount_fiat(), dec!(186.32));
+        assert_eq!(blocks[1].amount_asset(), dec!(0.40));
+        assert_eq!(wallet.amount_asset(), dec!(1.35));
+        // buy something else, then restore
+        wallet.buy(dec!(1.0), dec!(100.0), origin_at("T4", 2022, 1, 4));
+        wallet.restore(blocks);
+        assert_eq!(wallet.amount_asset(), dec!(4.75));
+        assert_eq!(wallet.amount_fiat(), dec!(459.42));
+        // restored blocks are sold first
+        let sold = wallet.withdraw(dec!(2.0)).unwrap();
+        assert_eq!(sold.len(), 1);
+        assert_eq!(sold[0].origin(), &origin_at("T1", 2022, 1, 1));
+        assert_eq!(sold[0].amount_fiat(), dec!(186.32));
+    }
+
+    #[test]
+    fn should_restore_blocks_by_acquisition_date() {
+        crate::mock::log();
+        let mut wallet = Wallet::default();
+        wallet.buy(dec!(1.0), dec!(100.0), origin_at("T1", 2022, 1, 1));
+        let blocks = wallet.withdraw(dec!(1.0)).unwrap();
+        // a lot acquired before the withdrawn one is deposited in the meantime
+        wallet.buy(dec!(2.0), dec!(150.0), origin_at("D1", 2021, 6, 1));
+        wallet.buy(dec!(0.5), dec!(60.0), origin_at("T2", 2022, 3, 1));
+        wallet.restore(blocks);
+        let origins: Vec<&str> = wallet
+            .blocks()
+            .iter()
+            .map(|x| x.origin().transaction_id.as_str())
+            .collect();
+        assert_eq!(origins, vec!["D1", "T1", "T2"]);
     }
 
     #[test]
     fn should_perform_stock_split() {
         crate::mock::log();
         let mut wallet = Wallet::default();
-        wallet.buy(dec!(0.025), dec!(186.32));
-        wallet.buy(dec!(0.01), dec!(68.78));
-        wallet.buy(dec!(0.015), dec!(104.32));
+        wallet.buy(dec!(0.025), dec!(186.32), LotOrigin::default());
+        wallet.buy(dec!(0.01), dec!(68.78), LotOrigin::default());
+        wallet.buy(dec!(0.015), dec!(104.32), LotOrigin::default());
 
-        wallet.stock_split(dec!(1.34));
+        wallet.stock_split(dec!(1.34), origin("S1"));
         assert_eq!(wallet.amount_asset(), dec!(1.39));
         assert_eq!(wallet.amount_fiat(), dec!(359.42));
     }
+
+    #[test]
+    fn should_keep_blocks_and_origins_on_stock_split() {
+        crate::mock::log();
+        let mut wallet = Wallet::default();
+        wallet.buy(dec!(1), dec!(100), origin("T1"));
+        wallet.buy(dec!(3), dec!(240), origin("T2"));
+
+        wallet.stock_split(dec!(76), origin("S1"));
+        let blocks = wallet.blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].origin(), &origin("T1"));
+        assert_eq!(blocks[0].amount_asset(), dec!(20));
+        assert_eq!(blocks[0].amount_fiat(), dec!(100));
+        assert_eq!(blocks[1].origin(), &origin("T2"));
+        assert_eq!(blocks[1].amount_asset(), dec!(60));
+        assert_eq!(blocks[1].amount_fiat(), dec!(240));
+    }
+
+    #[test]
+    fn should_give_split_origin_on_empty_wallet_stock_split() {
+        crate::mock::log();
+        let mut wallet = Wallet::default();
+        wallet.stock_split(dec!(10), origin_at("S1", 2022, 7, 18));
+        let blocks = wallet.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].origin(), &origin_at("S1", 2022, 7, 18));
+        assert_eq!(blocks[0].amount_asset(), dec!(10));
+        assert_eq!(blocks[0].amount_fiat(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn should_keep_lot_origin_when_withdrawing() {
+        crate::mock::log();
+        let mut wallet = Wallet::default();
+        wallet.buy(dec!(2.0), dec!(186.32), origin("T1"));
+        wallet.buy(dec!(0.5), dec!(68.78), origin("T2"));
+        let blocks = wallet.withdraw(dec!(2.25)).unwrap();
+        assert_eq!(blocks[0].origin(), &origin("T1"));
+        assert_eq!(blocks[1].origin(), &origin("T2"));
+        assert_eq!(blocks[1].amount_asset(), dec!(0.25));
+    }
+
+    /// FIAT amount of the withdrawn blocks (which refers to the buy price)
+    fn withdrawn_fiat(blocks: Vec<Block>) -> Decimal {
+        blocks.iter().map(|x| x.amount_fiat()).sum()
+    }
+
+    fn origin(transaction_id: &str) -> LotOrigin {
+        origin_at(transaction_id, 2022, 1, 1)
+    }
+
+    fn origin_at(transaction_id: &str, year: i32, month: u32, day: u32) -> LotOrigin {
+        use chrono::TimeZone;
+        LotOrigin::new(
+            transaction_id,
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(year, month, day, 12, 0, 0)
+                .unwrap(),
+        )
+    }
 }
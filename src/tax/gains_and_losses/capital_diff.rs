@@ -3,6 +3,8 @@
 use bitpanda_csv::{Asset, AssetClass};
 use rust_decimal::Decimal;
 
+use super::MatchedLot;
+
 /// Capital diff defines a gain or a loss in the investor's capital
 #[derive(Debug, Clone)]
 pub struct CapitalDiff {
@@ -18,6 +20,8 @@ pub struct CapitalDiff {
     tax_percentage: Decimal,
     /// The value of the capital difference (if positive is a gain, if negative is a loss)
     value: Decimal,
+    /// The lots which made up the capital difference
+    lots: Vec<MatchedLot>,
 }
 
 #[derive(Debug, Eq, Copy, Clone, PartialEq)]
@@ -44,6 +48,7 @@ impl CapitalDiff {
             tax,
             tax_percentage,
             value,
+            lots: Vec::new(),
         }
     }
 
@@ -62,9 +67,16 @@ impl CapitalDiff {
             tax: Decimal::ZERO,
             tax_percentage,
             value,
+            lots: Vec::new(),
         }
     }
 
+    /// Set the lots which made up the capital diff
+    pub fn with_lots(mut self, lots: Vec<MatchedLot>) -> Self {
+        self.lots = lots;
+        self
+    }
+
     /// Returns whether this capital diff is a gain
     pub fn is_gain(&self) -> bool {
         self.diff == Diff::Gain
@@ -99,6 +111,11 @@ impl CapitalDiff {
     pub fn value(&self) -> Decimal {
         self.value
     }
+
+    /// Returns the lots which made up the capital diff
+    pub fn lots(&self) -> &[MatchedLot] {
+        &self.lots
+    }
 }
 
 #[cfg(test)]
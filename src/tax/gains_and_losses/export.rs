@@ -0,0 +1,149 @@
+//! # Export
+//!
+//! This module exposes the export of gains and losses to CSV, so that they can be audited
+
+use rust_decimal::Decimal;
+use std::io::Write;
+
+use super::{CapitalDiff, GainsAndLosses, MatchedLot};
+
+/// A CSV row describing a matched lot
+#[derive(Serialize)]
+struct MatchedLotRecord {
+    asset: String,
+    asset_class: String,
+    buy_transaction_id: String,
+    acquired_at: String,
+    sell_transaction_id: String,
+    disposed_at: String,
+    quantity: Decimal,
+    cost: Decimal,
+    proceeds: Decimal,
+    value: Decimal,
+    holding_days: i64,
+    tax_percentage: Decimal,
+}
+
+impl From<&MatchedLot> for MatchedLotRecord {
+    fn from(lot: &MatchedLot) -> Self {
+        Self {
+            asset: lot.asset.to_string(),
+            asset_class: format!("{:?}", lot.asset_class),
+            buy_transaction_id: lot.buy_transaction_id.clone(),
+            acquired_at: lot.acquired_at.to_rfc3339(),
+            sell_transaction_id: lot.sell_transaction_id.clone(),
+            disposed_at: lot.disposed_at.to_rfc3339(),
+            quantity: lot.quantity,
+            cost: lot.cost.round_dp(2),
+            proceeds: lot.proceeds.round_dp(2),
+            value: lot.value().round_dp(2),
+            holding_days: lot.holding_days(),
+            tax_percentage: lot.tax_percentage,
+        }
+    }
+}
+
+/// A CSV row describing the capital diff of an asset
+#[derive(Serialize)]
+struct CapitalDiffRecord {
+    asset: String,
+    asset_class: String,
+    lots: usize,
+    cost: Decimal,
+    proceeds: Decimal,
+    value: Decimal,
+    tax_percentage: Decimal,
+    tax: Decimal,
+}
+
+impl From<&CapitalDiff> for CapitalDiffRecord {
+    fn from(diff: &CapitalDiff) -> Self {
+        Self {
+            asset: diff.asset().to_string(),
+            asset_class: format!("{:?}", diff.asset_class()),
+            lots: diff.lots().len(),
+            cost: diff
+                .lots()
+                .iter()
+                .map(|x| x.cost)
+                .sum::<Decimal>()
+                .round_dp(2),
+            proceeds: diff
+                .lots()
+                .iter()
+                .map(|x| x.proceeds)
+                .sum::<Decimal>()
+                .round_dp(2),
+            value: diff.value().round_dp(2),
+            tax_percentage: diff.tax_percentage(),
+            tax: diff.tax().round_dp(2),
+        }
+    }
+}
+
+impl GainsAndLosses {
+    /// Write all the matched lots as CSV to `writer`
+    pub fn export_lots(&self, writer: impl Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for lot in self.lots() {
+            writer.serialize(MatchedLotRecord::from(lot))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the gains and losses of each asset as CSV to `writer`
+    pub fn export_capital_diffs(&self, writer: impl Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for diff in self.iter() {
+            writer.serialize(CapitalDiffRecord::from(diff))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use bitpanda_csv::{Asset, AssetClass, Metal};
+    use chrono::{FixedOffset, TimeZone};
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_export_lots_and_capital_diffs() {
+        crate::mock::log();
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let gains_and_losses = GainsAndLosses::from(vec![MatchedLot {
+            asset: Asset::Metal(Metal::Gold),
+            asset_class: AssetClass::Metal,
+            tax_percentage: dec!(26.0),
+            buy_transaction_id: String::from("T1"),
+            acquired_at: offset.with_ymd_and_hms(2022, 1, 10, 12, 0, 0).unwrap(),
+            sell_transaction_id: String::from("T2"),
+            disposed_at: offset.with_ymd_and_hms(2022, 3, 1, 12, 0, 0).unwrap(),
+            quantity: dec!(1.5),
+            cost: dec!(80.0),
+            proceeds: dec!(100.0),
+        }])
+        .flatten();
+        let mut lots = Vec::new();
+        gains_and_losses.export_lots(&mut lots).unwrap();
+        assert_eq!(
+            String::from_utf8(lots).unwrap(),
+            "asset,asset_class,buy_transaction_id,acquired_at,sell_transaction_id,disposed_at,quantity,cost,proceeds,value,holding_days,tax_percentage
+XAU,Metal,T1,2022-01-10T12:00:00+01:00,T2,2022-03-01T12:00:00+01:00,1.5,80.0,100.0,20.0,50,26.0
+"
+        );
+        let mut diffs = Vec::new();
+        gains_and_losses.export_capital_diffs(&mut diffs).unwrap();
+        assert_eq!(
+            String::from_utf8(diffs).unwrap(),
+            "asset,asset_class,lots,cost,proceeds,value,tax_percentage,tax
+XAU,Metal,1,80.0,100.0,20.0,26.0,5.20
+"
+        );
+    }
+}
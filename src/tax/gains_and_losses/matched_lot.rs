@@ -0,0 +1,72 @@
+//! # Matched lot
+
+use bitpanda_csv::{Asset, AssetClass};
+use chrono::{DateTime, FixedOffset};
+use rust_decimal::Decimal;
+
+/// A matched lot describes which buy lot has been consumed by a disposal and for how much
+#[derive(Debug, Clone)]
+pub struct MatchedLot {
+    /// The asset the lot is referred to
+    pub asset: Asset,
+    /// The kind of asset
+    pub asset_class: AssetClass,
+    /// The percentage applied to the gain to calculate the tax
+    pub tax_percentage: Decimal,
+    /// Transaction ID of the trade which brought the lot into the wallet
+    pub buy_transaction_id: String,
+    /// When the lot has been acquired
+    pub acquired_at: DateTime<FixedOffset>,
+    /// Transaction ID of the sell
+    pub sell_transaction_id: String,
+    /// When the lot has been sold
+    pub disposed_at: DateTime<FixedOffset>,
+    /// The quantity of the asset sold from the lot
+    pub quantity: Decimal,
+    /// The FIAT amount paid to acquire `quantity`
+    pub cost: Decimal,
+    /// The FIAT amount obtained by selling `quantity`
+    pub proceeds: Decimal,
+}
+
+impl MatchedLot {
+    /// Returns the capital difference of the lot (if positive is a gain, if negative is a loss)
+    pub fn value(&self) -> Decimal {
+        self.proceeds - self.cost
+    }
+
+    /// Returns the amount of days the lot has been held
+    pub fn holding_days(&self) -> i64 {
+        (self.disposed_at.date_naive() - self.acquired_at.date_naive()).num_days()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use bitpanda_csv::Metal;
+    use chrono::TimeZone;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_calc_matched_lot_value_and_holding_days() {
+        crate::mock::log();
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let lot = MatchedLot {
+            asset: Asset::Metal(Metal::Gold),
+            asset_class: AssetClass::Metal,
+            tax_percentage: dec!(26.0),
+            buy_transaction_id: String::from("T1"),
+            acquired_at: offset.with_ymd_and_hms(2022, 1, 10, 23, 0, 0).unwrap(),
+            sell_transaction_id: String::from("T2"),
+            disposed_at: offset.with_ymd_and_hms(2022, 3, 1, 8, 0, 0).unwrap(),
+            quantity: dec!(1.5),
+            cost: dec!(80.0),
+            proceeds: dec!(100.0),
+        };
+        assert_eq!(lot.value(), dec!(20.0));
+        assert_eq!(lot.holding_days(), 50);
+    }
+}
@@ -0,0 +1,233 @@
+//! # Loss harvesting
+//!
+//! This module exposes the tax-loss harvesting advisor, which suggests the open positions to sell before the end of
+//! the year to offset the gains left after netting the realized and the carried losses.
+
+use bitpanda_csv::Asset;
+use rust_decimal::Decimal;
+
+use super::loss_netting::OFFSETTABLE_TAX_PERCENTAGE;
+use super::{CarriedLoss, LossNetting, OpenPosition, OpenPositionsReport};
+
+/// Tax-loss harvesting advisor
+pub struct LossHarvesting<'a> {
+    open_positions: &'a OpenPositionsReport,
+    netting: &'a LossNetting,
+}
+
+/// The advice produced by the `LossHarvesting` advisor
+#[derive(Debug, Default, Clone)]
+pub struct HarvestingAdvice {
+    /// Realized gains at 26% which can be offset by losses
+    pub offsettable_gains: Decimal,
+    /// Realized gains which can't be offset by losses (ETFs)
+    pub non_offsettable_gains: Decimal,
+    /// Realized losses of the year (positive)
+    pub realized_losses: Decimal,
+    /// Carried losses which can be used in the year (positive)
+    pub carried_losses_available: Decimal,
+    /// Carried losses used to offset the gains (positive)
+    pub carried_losses_used: Decimal,
+    /// Offsettable gains still to be taxed after using realized and carried losses
+    pub taxable_gains: Decimal,
+    /// Positions which, if sold now, would offset the taxable gains
+    pub suggestions: Vec<HarvestSuggestion>,
+    /// Carried losses which expire at the end of the year
+    pub expiring: Vec<ExpiringLoss>,
+}
+
+/// A position to sell to realize a loss
+#[derive(Debug, Clone)]
+pub struct HarvestSuggestion {
+    pub asset: Asset,
+    /// Quantity held
+    pub quantity: Decimal,
+    /// Loss which would be realized by selling the whole position (positive)
+    pub unrealized_loss: Decimal,
+    /// Amount of taxable gains offset by the loss
+    pub offset: Decimal,
+    /// Tax which would be saved
+    pub tax_saving: Decimal,
+}
+
+/// A carried loss which expires at the end of the year
+#[derive(Debug, Clone)]
+pub struct ExpiringLoss {
+    pub loss: CarriedLoss,
+    /// The amount of the loss which won't be used by the gains of the year
+    pub unused: Decimal,
+}
+
+impl HarvestingAdvice {
+    /// Returns the total tax saved by following all the suggestions
+    pub fn tax_saving(&self) -> Decimal {
+        self.suggestions.iter().map(|x| x.tax_saving).sum()
+    }
+}
+
+impl<'a> LossHarvesting<'a> {
+    pub fn new(open_positions: &'a OpenPositionsReport, netting: &'a LossNetting) -> Self {
+        Self {
+            open_positions,
+            netting,
+        }
+    }
+
+    /// Calculate the harvesting advice
+    pub fn advise(&self) -> HarvestingAdvice {
+        let mut expiring = Vec::new();
+        for (loss, used) in self.netting.carried_losses.iter() {
+            if loss.expires_at() == self.netting.year {
+                let unused = loss.amount - used;
+                info!(
+                    "carried loss of {} expires this year; € {} unused",
+                    loss.year, unused
+                );
+                expiring.push(ExpiringLoss {
+                    loss: loss.clone(),
+                    unused,
+                });
+            }
+        }
+        let suggestions = self.suggestions(self.netting.taxable_gains);
+
+        HarvestingAdvice {
+            offsettable_gains: self.netting.offsettable_gains,
+            non_offsettable_gains: self.netting.non_offsettable_gains,
+            realized_losses: self.netting.realized_losses,
+            carried_losses_available: self.netting.carried_losses_available(),
+            carried_losses_used: self.netting.carried_losses_used(),
+            taxable_gains: self.netting.taxable_gains,
+            suggestions,
+            expiring,
+        }
+    }
+
+    /// Returns the loss (positive) which would be realized by selling the whole position, if any
+    fn unrealized_loss(position: &OpenPosition) -> Option<Decimal> {
+        position
+            .unrealized()
+            .filter(|diff| diff.is_sign_negative() && !diff.is_zero())
+            .map(|diff| diff.abs())
+    }
+
+    /// Select the positions with an unrealized loss, starting from the biggest, until the taxable gains are offset
+    fn suggestions(&self, taxable_gains: Decimal) -> Vec<HarvestSuggestion> {
+        let mut positions: Vec<(&Asset, Decimal, Decimal)> = self
+            .open_positions
+            .positions
+            .iter()
+            .filter_map(|x| Self::unrealized_loss(x).map(|loss| (&x.asset, x.quantity, loss)))
+            .collect();
+        positions.sort_by_key(|x| std::cmp::Reverse(x.2));
+        let mut remaining = taxable_gains;
+        let mut suggestions = Vec::new();
+        for (asset, quantity, unrealized_loss) in positions {
+            if remaining.is_zero() {
+                break;
+            }
+            let offset = unrealized_loss.min(remaining);
+            remaining -= offset;
+            suggestions.push(HarvestSuggestion {
+                asset: asset.clone(),
+                quantity,
+                unrealized_loss,
+                offset,
+                tax_saving: (offset * OFFSETTABLE_TAX_PERCENTAGE) / dec!(100.0),
+            });
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tax::{CapitalDiff, CarriedLosses, GainsAndLosses};
+
+    use bitpanda_csv::{AssetClass, Metal};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_suggest_positions_to_harvest() {
+        crate::mock::log();
+        let gains_and_losses = GainsAndLosses::from(vec![
+            CapitalDiff::gain(
+                Asset::Ticker(String::from("AMZN")),
+                AssetClass::Stock,
+                dec!(26.0),
+                dec!(1000.0),
+            ),
+            CapitalDiff::gain(
+                Asset::Ticker(String::from("SPY")),
+                AssetClass::Etf,
+                dec!(26.0),
+                dec!(500.0),
+            ),
+            CapitalDiff::loss(
+                Asset::Ticker(String::from("SPY500")),
+                AssetClass::Etf,
+                dec!(26.0),
+                dec!(-100.0),
+            ),
+        ]);
+        let open_positions = OpenPositionsReport {
+            positions: vec![
+                position(Metal::Gold, dec!(500.0), dec!(100.0)),
+                position(Metal::Silver, dec!(500.0), dec!(300.0)),
+                position(Metal::Platinum, dec!(500.0), dec!(800.0)),
+                position(Metal::Palladium, dec!(500.0), dec!(450.0)),
+            ],
+        };
+        let carried_losses: CarriedLosses = serde_json::from_str(
+            r#"{ "losses": [{ "year": 2018, "amount": 200.0 }, { "year": 2020, "amount": 50.0 }] }"#,
+        )
+        .unwrap();
+        let netting = LossNetting::new(&gains_and_losses, &carried_losses, 2022);
+        let advice = LossHarvesting::new(&open_positions, &netting).advise();
+        assert_eq!(advice.offsettable_gains, dec!(1000.0));
+        assert_eq!(advice.non_offsettable_gains, dec!(500.0));
+        assert_eq!(advice.realized_losses, dec!(100.0));
+        assert_eq!(advice.carried_losses_available, dec!(250.0));
+        assert_eq!(advice.carried_losses_used, dec!(250.0));
+        assert_eq!(advice.taxable_gains, dec!(650.0));
+        assert_eq!(advice.expiring.len(), 1);
+        assert_eq!(advice.expiring[0].unused, Decimal::ZERO);
+        // gold first (-400), then silver (-200), then palladium (-50)
+        assert_eq!(advice.suggestions.len(), 3);
+        assert_eq!(advice.suggestions[0].asset, Asset::Metal(Metal::Gold));
+        assert_eq!(advice.suggestions[0].offset, dec!(400.0));
+        assert_eq!(advice.suggestions[2].asset, Asset::Metal(Metal::Palladium));
+        assert_eq!(advice.suggestions[2].offset, dec!(50.0));
+        assert_eq!(advice.tax_saving(), dec!(169.0));
+    }
+
+    #[test]
+    fn should_report_unused_expiring_losses() {
+        crate::mock::log();
+        let gains_and_losses = GainsAndLosses::from(vec![CapitalDiff::gain(
+            Asset::Ticker(String::from("AMZN")),
+            AssetClass::Stock,
+            dec!(26.0),
+            dec!(100.0),
+        )]);
+        let carried_losses: CarriedLosses =
+            serde_json::from_str(r#"{ "losses": [{ "year": 2018, "amount": 300.0 }] }"#).unwrap();
+        let netting = LossNetting::new(&gains_and_losses, &carried_losses, 2022);
+        let advice = LossHarvesting::new(&OpenPositionsReport::default(), &netting).advise();
+        assert_eq!(advice.taxable_gains, Decimal::ZERO);
+        assert!(advice.suggestions.is_empty());
+        assert_eq!(advice.expiring[0].unused, dec!(200.0));
+    }
+
+    fn position(metal: Metal, cost: Decimal, market_price: Decimal) -> OpenPosition {
+        OpenPosition {
+            asset: Asset::Metal(metal),
+            quantity: dec!(1.0),
+            cost,
+            market_price: Some(market_price),
+            tax_percentage: dec!(26.0),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! # Loss netting
+//!
+//! This module exposes the netting of the capital gains of the year with the realized and the carried losses,
+//! which determines the tax to pay on the capital gains.
+//!
+//! Netting rules:
+//!
+//! - losses ("redditi diversi") can offset only the gains which are "redditi diversi" too (stocks, crypto, metals...);
+//! - gains of ETFs are "redditi di capitale", so they can't absorb any loss (while ETF losses can offset other gains);
+//! - losses of the current year are used first, then the carried losses, from the oldest to the newest.
+
+use bitpanda_csv::AssetClass;
+use rust_decimal::Decimal;
+
+use super::{CarriedLoss, CarriedLosses, GainsAndLosses};
+
+/// The tax percentage of the gains which can be offset by losses
+pub const OFFSETTABLE_TAX_PERCENTAGE: Decimal = dec!(26.0);
+
+/// Outcome of netting the capital gains of `year` with the realized losses and the carried ones
+#[derive(Debug, Clone)]
+pub struct LossNetting {
+    /// The year of the gains
+    pub year: i32,
+    /// Realized gains at 26% which can be offset by losses
+    pub offsettable_gains: Decimal,
+    /// Realized gains which can't be offset by losses (ETFs)
+    pub non_offsettable_gains: Decimal,
+    /// Realized losses of the year (positive)
+    pub realized_losses: Decimal,
+    /// Offsettable gains still to be taxed after using realized and carried losses
+    pub taxable_gains: Decimal,
+    /// Carried losses available in the year, from the oldest, with the amount used of each
+    pub carried_losses: Vec<(CarriedLoss, Decimal)>,
+    /// Tax on the gains, before netting the losses
+    gross_tax: Decimal,
+}
+
+impl LossNetting {
+    /// Net the offsettable gains with the realized losses of the year first, then with the carried losses from the
+    /// oldest
+    pub fn new(
+        gains_and_losses: &GainsAndLosses,
+        carried_losses: &CarriedLosses,
+        year: i32,
+    ) -> Self {
+        let offsettable_gains: Decimal = gains_and_losses
+            .iter()
+            .filter(|x| x.is_gain() && Self::is_offsettable(x.asset_class(), x.tax_percentage()))
+            .map(|x| x.value())
+            .sum();
+        let non_offsettable_gains: Decimal = gains_and_losses
+            .iter()
+            .filter(|x| x.is_gain() && !Self::is_offsettable(x.asset_class(), x.tax_percentage()))
+            .map(|x| x.value())
+            .sum();
+        let realized_losses = gains_and_losses.losses_value().abs();
+        debug!(
+            "offsettable gains: € {}; non offsettable gains: € {}; realized losses: € {}",
+            offsettable_gains, non_offsettable_gains, realized_losses
+        );
+        let mut taxable_gains = (offsettable_gains - realized_losses).max(Decimal::ZERO);
+        let carried_losses = carried_losses
+            .available(year)
+            .into_iter()
+            .map(|loss| {
+                let used = loss.amount.min(taxable_gains);
+                taxable_gains -= used;
+                (loss, used)
+            })
+            .collect();
+        Self {
+            year,
+            offsettable_gains,
+            non_offsettable_gains,
+            realized_losses,
+            taxable_gains,
+            carried_losses,
+            gross_tax: gains_and_losses.tax_to_pay(),
+        }
+    }
+
+    /// Returns the total amount of the carried losses which can be used in the year (positive)
+    pub fn carried_losses_available(&self) -> Decimal {
+        self.carried_losses
+            .iter()
+            .map(|(loss, _)| loss.amount)
+            .sum()
+    }
+
+    /// Returns the carried losses used to offset the gains of the year which are left after the realized losses (positive)
+    pub fn carried_losses_used(&self) -> Decimal {
+        self.carried_losses.iter().map(|(_, used)| *used).sum()
+    }
+
+    /// Returns the tax on the capital gains of the year, once both the realized and the carried losses have been used
+    pub fn tax_to_pay(&self) -> Decimal {
+        let offset = self.offsettable_gains - self.taxable_gains;
+        let tax_saving = (offset * OFFSETTABLE_TAX_PERCENTAGE) / dec!(100.0);
+        (self.gross_tax - tax_saving).max(Decimal::ZERO)
+    }
+
+    /// Returns the losses which can be carried to the next years: the realized losses not offset by the gains of
+    /// the year and the carried losses neither used nor expired
+    pub fn carry_forward(&self) -> CarriedLosses {
+        let mut losses: Vec<CarriedLoss> = self
+            .carried_losses
+            .iter()
+            .filter(|(loss, used)| *used < loss.amount && loss.expires_at() > self.year)
+            .map(|(loss, used)| CarriedLoss {
+                year: loss.year,
+                amount: loss.amount - used,
+            })
+            .collect();
+        let unused_losses = (self.realized_losses - self.offsettable_gains).max(Decimal::ZERO);
+        if !unused_losses.is_zero() {
+            losses.push(CarriedLoss {
+                year: self.year,
+                amount: unused_losses,
+            });
+        }
+        CarriedLosses::from(losses)
+    }
+
+    /// Returns whether the gains of this kind of asset can be offset by losses
+    fn is_offsettable(asset_class: AssetClass, tax_percentage: Decimal) -> bool {
+        asset_class != AssetClass::Etf && tax_percentage == OFFSETTABLE_TAX_PERCENTAGE
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::tax::CapitalDiff;
+
+    use bitpanda_csv::Asset;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_net_realized_and_carried_losses() {
+        crate::mock::log();
+        let gains_and_losses = GainsAndLosses::from(vec![
+            CapitalDiff::gain(
+                Asset::Ticker(String::from("AMZN")),
+                AssetClass::Stock,
+                dec!(26.0),
+                dec!(1000.0),
+            ),
+            CapitalDiff::gain(
+                Asset::Ticker(String::from("SPY")),
+                AssetClass::Etf,
+                dec!(26.0),
+                dec!(500.0),
+            ),
+            CapitalDiff::loss(
+                Asset::Ticker(String::from("SPY500")),
+                AssetClass::Etf,
+                dec!(26.0),
+                dec!(-100.0),
+            ),
+        ]);
+        // without carried losses: 390 on the gains, minus 26 saved with the realized losses
+        let netting = LossNetting::new(&gains_and_losses, &CarriedLosses::default(), 2022);
+        assert_eq!(netting.offsettable_gains, dec!(1000.0));
+        assert_eq!(netting.non_offsettable_gains, dec!(500.0));
+        assert_eq!(netting.taxable_gains, dec!(900.0));
+        assert_eq!(netting.carried_losses_used(), Decimal::ZERO);
+        assert_eq!(netting.tax_to_pay(), dec!(364.0));
+        // with carried losses: 65 more saved with the carried losses
+        let carried_losses: CarriedLosses = serde_json::from_str(
+            r#"{ "losses": [{ "year": 2018, "amount": 200.0 }, { "year": 2020, "amount": 50.0 }] }"#,
+        )
+        .unwrap();
+        let netting = LossNetting::new(&gains_and_losses, &carried_losses, 2022);
+        assert_eq!(netting.carried_losses_available(), dec!(250.0));
+        assert_eq!(netting.carried_losses_used(), dec!(250.0));
+        assert_eq!(netting.taxable_gains, dec!(650.0));
+        assert_eq!(netting.tax_to_pay(), dec!(299.0));
+    }
+
+    #[test]
+    fn should_carry_forward_unused_losses() {
+        crate::mock::log();
+        let amazon = Asset::Ticker(String::from("AMZN"));
+        // losses of the year exceed the gains
+        let gains_and_losses = GainsAndLosses::from(vec![
+            CapitalDiff::gain(amazon.clone(), AssetClass::Stock, dec!(26.0), dec!(100.0)),
+            CapitalDiff::loss(amazon.clone(), AssetClass::Stock, dec!(26.0), dec!(-300.0)),
+        ]);
+        let carried_losses: CarriedLosses = serde_json::from_str(
+            r#"{ "losses": [{ "year": 2018, "amount": 50.0 }, { "year": 2020, "amount": 80.0 }] }"#,
+        )
+        .unwrap();
+        let carried = LossNetting::new(&gains_and_losses, &carried_losses, 2022).carry_forward();
+        assert_eq!(
+            carried.available(2023),
+            vec![
+                CarriedLoss {
+                    year: 2020,
+                    amount: dec!(80.0)
+                },
+                CarriedLoss {
+                    year: 2022,
+                    amount: dec!(200.0)
+                }
+            ]
+        );
+        // gains use the carried losses from the oldest
+        let gains_and_losses = GainsAndLosses::from(vec![CapitalDiff::gain(
+            amazon,
+            AssetClass::Stock,
+            dec!(26.0),
+            dec!(600.0),
+        )]);
+        let carried_losses: CarriedLosses = serde_json::from_str(
+            r#"{ "losses": [{ "year": 2021, "amount": 500.0 }, { "year": 2019, "amount": 300.0 }] }"#,
+        )
+        .unwrap();
+        let carried = LossNetting::new(&gains_and_losses, &carried_losses, 2022).carry_forward();
+        assert_eq!(
+            carried.available(2023),
+            vec![CarriedLoss {
+                year: 2021,
+                amount: dec!(200.0)
+            }]
+        );
+    }
+}
@@ -2,14 +2,28 @@
 //!
 //! This module expose the tax calculators for Italian taxation ruleset
 
+mod carried_losses;
 mod gains_and_losses;
-pub use gains_and_losses::{Calculator as GainsAndLossesCalculator, CapitalDiff, GainsAndLosses};
+mod loss_harvesting;
+mod loss_netting;
+mod year_to_date;
+pub use carried_losses::{CarriedLoss, CarriedLosses};
+pub use gains_and_losses::{
+    Calculator as GainsAndLossesCalculator, CapitalDiff, CostBasis, CostBasisReport,
+    CostBasisSource, GainsAndLosses, Inventory, InventoryMismatch, OpenPosition,
+    OpenPositionsReport, SelfTransfers, SelfTransfersReport, ValidationIssueKind, ValidationReport,
+};
+pub use loss_harvesting::{HarvestingAdvice, LossHarvesting};
+pub use loss_netting::LossNetting;
+pub use year_to_date::YearToDateEstimate;
 
-use crate::database::{QuoteDatabase, TradeDatabase, TradeQuery, WalletDatabase};
-use bitpanda_csv::{Asset, Currency, Fiat};
+use crate::database::{QuoteDatabase, TradeDatabase, TradeQuery, Venue, WalletDatabase};
+use crate::timezone;
+use bitpanda_csv::{Asset, Currency, Fiat, Trade};
 
-use chrono::{DateTime, Datelike, FixedOffset, LocalResult, TimeZone};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
 use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
 
 /// Italian fiscal taxes calculator
 ///
@@ -23,6 +37,10 @@ pub struct Taxes<'a> {
     quotes: &'a QuoteDatabase,
     since: DateTime<FixedOffset>,
     to: DateTime<FixedOffset>,
+    /// Opening date of the Bitpanda account, if known
+    account_opened_at: Option<NaiveDate>,
+    /// Quantities held at the beginning of the time range, according to the opening inventory
+    opening_holdings: HashMap<Asset, Decimal>,
 }
 
 impl<'a> Taxes<'a> {
@@ -38,9 +56,46 @@ impl<'a> Taxes<'a> {
             quotes,
             since,
             to,
+            account_opened_at: None,
+            opening_holdings: HashMap::new(),
         }
     }
 
+    /// Set the opening date of the Bitpanda account, so that the days before it count as days without any balance
+    pub fn with_account_opened_at(mut self, account_opened_at: Option<NaiveDate>) -> Self {
+        self.account_opened_at = account_opened_at;
+        self
+    }
+
+    /// Set the opening inventory, whose lots are held since the beginning of the time range.
+    /// The lots parked outside of the wallet by a self-transfer are not held
+    pub fn with_opening_inventory(mut self, inventory: Option<&Inventory>) -> Self {
+        self.opening_holdings = inventory.map(Inventory::quantities).unwrap_or_default();
+        self
+    }
+
+    /// Returns the amount of days of the time range, which divides the sum of the daily balances
+    pub fn period_days(&self) -> i64 {
+        self.days().len() as i64
+    }
+
+    /// Returns the days of the time range.
+    /// Days are the Italian ones, so that daylight saving time is taken into account
+    fn days(&self) -> Vec<NaiveDate> {
+        let last_day = timezone::day(self.to);
+        timezone::day(self.since)
+            .iter_days()
+            .take_while(|day| *day <= last_day)
+            .collect()
+    }
+
+    /// Returns whether the account was already open on `day`
+    fn is_account_open(&self, day: NaiveDate) -> bool {
+        self.account_opened_at
+            .map(|opened_at| day >= opened_at)
+            .unwrap_or(true)
+    }
+
     /// Calculate the tax on the foreign bank account (Bitpanda is located in Austria)
     ///
     /// > Le persone fisiche residenti in Italia che hanno prodotti finanziari,
@@ -48,6 +103,9 @@ impl<'a> Taxes<'a> {
     /// > Sono tenuti a versare anche l’IVAFE, ossia l’Imposta sul Valore delle Attività Finanziarie all’Estero.
     /// > Tale imposta è applicata in modo
     /// > proporzionale al 2 per mille annuo del valore delle attività finanziarie.
+    ///
+    /// The IVAFE is due for the holding days only: the average balance already counts the days when nothing has been
+    /// held (e.g. before the account opening) as zero, so it is pro-rated by it.
     pub fn ivafe(&self, average_balance: Decimal) -> Decimal {
         debug!("average balance for this year is {}", average_balance);
         if average_balance < dec!(5000.0) {
@@ -61,12 +119,47 @@ impl<'a> Taxes<'a> {
         }
     }
 
+    /// Estimate the taxes of the current year, from the beginning of the year to the end of the time range.
+    ///
+    /// The balance at the end of the time range is valued at the current prices.
+    pub fn year_to_date(
+        &self,
+        average_balance: Decimal,
+        netting: &LossNetting,
+        open_positions: &OpenPositionsReport,
+    ) -> anyhow::Result<YearToDateEstimate> {
+        let current_balance = self.balance()?;
+        info!("balance at {}: € {}", self.to, current_balance);
+        Ok(YearToDateEstimate::new(
+            self.to,
+            average_balance,
+            current_balance,
+            netting.tax_to_pay(),
+            open_positions.latent_tax(),
+            |balance| self.ivafe(balance),
+        ))
+    }
+
+    /// Net the capital gains of the year with the realized losses and with the losses of the previous years, getting the
+    /// tax to pay on the capital gains
+    pub fn net_losses(
+        &self,
+        gains_and_losses: &GainsAndLosses,
+        carried_losses: &CarriedLosses,
+    ) -> LossNetting {
+        LossNetting::new(gains_and_losses, carried_losses, self.to.year())
+    }
+
     /// Calculate the capital gains and losses. Taxes are already calculated.
     ///
     /// > plusvalenze: reddito dovuto alla vendita a un prezzo superiore di quello di acquisto, ossia un guadagno
     /// > minusvalenze: controvalore derivante dalla vendita di uno strumento finanziario a un prezzo inferiore rispetto a quello d’acquisto, ossia una perdita
-    pub fn capital_gains_and_losses(&self) -> anyhow::Result<GainsAndLosses> {
-        let mut calculator = GainsAndLossesCalculator::default();
+    ///
+    /// The calculator is provided by the caller, so that its state can be inspected after the calculation.
+    pub fn capital_gains_and_losses(
+        &self,
+        calculator: &mut GainsAndLossesCalculator,
+    ) -> anyhow::Result<GainsAndLosses> {
         calculator.calculate(self.trades)
     }
 
@@ -78,65 +171,146 @@ impl<'a> Taxes<'a> {
     /// > Il calcolo della giacenza media annua si determina dividendo la somma delle giacenze giornaliere per 365,
     /// > indipendentemente dal numero di giorni in cui il deposito/conto risulta attivo.
     /// > Per giacenze giornaliere si intendono i saldi giornalieri per valuta.
+    ///
+    /// The balance of each day is the FIAT balance plus the assets held at the end of the day, valued at the quote of
+    /// that day. The assets held are the ones of the opening inventory plus the ones traded since the beginning of the
+    /// time range. The days before the account opening have no balance, but they are still counted in the divisor.
     pub fn average_balance(&self) -> anyhow::Result<Decimal> {
-        let mut date = match (*self.since.offset()).with_ymd_and_hms(
-            self.since.year(),
-            self.since.month(),
-            self.since.day(),
-            23,
-            59,
-            59,
-        ) {
-            LocalResult::Single(date) => date,
-            _ => anyhow::bail!("invalid date"),
-        };
+        let days = self.days();
+        if days.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+        // replay the trades once, getting the holdings at the end of each day from the wallet
+        let wallet = WalletDatabase::load(&self.trades.all());
         let mut total_balance = Decimal::ZERO;
-        // Iterate over the days in the time range
-        while date <= self.to {
-            let fiat_balance = self
-                .trades
-                .select(TradeQuery::default().before(date))
-                .fiat_balance(Fiat::Eur);
-            info!(
-                "FIAT balance at {} ({}): {}",
-                date,
-                date.ordinal(),
-                fiat_balance
-            );
-            total_balance += fiat_balance;
-            // calculate balance at date for each asset; get wallet at date first
-            let trades_wno_eur = self.trades.select(
-                TradeQuery::default().asset_neq(Asset::Currency(Currency::Fiat(Fiat::Eur))),
-            );
-            let wallet = WalletDatabase::load(&trades_wno_eur);
-            let wallet_balance = self.wallet_balance(wallet)?;
-            info!(
-                "wallet balance at {} ({}): {}",
+        for day in days.iter() {
+            if !self.is_account_open(*day) {
+                continue;
+            }
+            let date = timezone::end_of_day(*day).min(self.to);
+            let fiat_balance = wallet.fiat_balance_at(Fiat::Eur, date);
+            let assets_balance = self.wallet_balance_at(&wallet, date)?;
+            debug!(
+                "balance at {} ({}): FIAT {}; assets {}",
                 date,
                 date.ordinal(),
-                wallet_balance
+                fiat_balance,
+                assets_balance
             );
-            total_balance += wallet_balance;
-            // incr total balance, days and date
-            date += chrono::Duration::days(1);
+            total_balance += fiat_balance + assets_balance;
+        }
+        Ok(total_balance / Decimal::from(days.len()))
+    }
+
+    /// Calculate the average balance along the year of each venue where the trades have been made.
+    /// The average balance of all the venues is the sum of the average balances of each venue.
+    /// The lots of the opening inventory aren't tied to a venue, so they are held in Bitpanda
+    pub fn average_balance_by_venue(&self) -> anyhow::Result<Vec<(Venue, Decimal)>> {
+        let mut venues = self.trades.by_venue();
+        if venues.is_empty() || !self.opening_holdings.is_empty() {
+            venues
+                .entry(Venue::Bitpanda)
+                .or_insert_with(|| TradeDatabase::from(Vec::<Trade>::new()));
+        }
+        let mut balances = Vec::with_capacity(venues.len());
+        for (venue, trades) in venues.iter() {
+            // the opening date refers to the Bitpanda account
+            let (account_opened_at, opening_holdings) = match venue {
+                Venue::Bitpanda => (self.account_opened_at, self.opening_holdings.clone()),
+                _ => (None, HashMap::new()),
+            };
+            let mut taxes = Taxes::new(trades, self.quotes, self.since, self.to)
+                .with_account_opened_at(account_opened_at);
+            taxes.opening_holdings = opening_holdings;
+            let average_balance = taxes.average_balance()?;
+            debug!("average balance at {}: € {}", venue, average_balance);
+            balances.push((*venue, average_balance));
         }
-        Ok(total_balance / Decimal::from(self.to.ordinal()))
+        Ok(balances)
     }
 
-    /// Get wallet balance from wallet
+    /// Get the balance (FIAT and assets) at the end of the time range
+    fn balance(&self) -> anyhow::Result<Decimal> {
+        let fiat_balance = self
+            .trades
+            .select(TradeQuery::default().before(self.to))
+            .fiat_balance(Fiat::Eur);
+        let wallet = WalletDatabase::load(
+            &self.trades.select(
+                TradeQuery::default()
+                    .before(self.to)
+                    .asset_neq(Asset::Currency(Currency::Fiat(Fiat::Eur))),
+            ),
+        );
+        Ok(fiat_balance + self.wallet_balance(wallet)?)
+    }
+
+    /// Get the value at `date` of the assets (but EUR) held in `wallet` and in the opening inventory at that date,
+    /// at the quote of that date
+    fn wallet_balance_at(
+        &self,
+        wallet: &WalletDatabase,
+        date: DateTime<FixedOffset>,
+    ) -> anyhow::Result<Decimal> {
+        let mut balance = Decimal::ZERO;
+        for asset in self.held_assets(wallet) {
+            // FIAT is held net of the deposit fees
+            let quantity = match asset {
+                Asset::Currency(Currency::Fiat(Fiat::Eur)) => continue,
+                Asset::Currency(Currency::Fiat(fiat)) => wallet.fiat_balance_at(*fiat, date),
+                asset => self.opening_quantity(asset) + wallet.quantity_at(asset, date),
+            };
+            if quantity.is_zero() {
+                continue;
+            }
+            let asset_price = match self
+                .quotes
+                .price_at(asset, date)
+                .or_else(|| self.quotes.price(asset))
+            {
+                Some(price) => price,
+                None => anyhow::bail!("could not find any price for asset {}", asset),
+            };
+            balance += quantity * asset_price;
+        }
+        Ok(balance)
+    }
+
+    /// Get wallet balance from wallet and from the opening inventory
     fn wallet_balance(&self, wallet: WalletDatabase) -> anyhow::Result<Decimal> {
         let mut wallet_balance = Decimal::ZERO;
-        for (asset, quantity) in wallet.iter() {
+        for asset in self.held_assets(&wallet) {
+            let quantity = self.opening_quantity(asset) + wallet.balance(asset).unwrap_or_default();
+            if quantity.is_zero() {
+                continue;
+            }
             let asset_price = match self.quotes.price(asset) {
                 Some(price) => price,
                 None => anyhow::bail!("could not find any price for asset {}", asset),
             };
-            let asset_balance = *quantity * asset_price;
+            let asset_balance = quantity * asset_price;
             debug!("asset balance for {}: € {}", asset, asset_balance);
             wallet_balance += asset_balance;
         }
         Ok(wallet_balance)
     }
+
+    /// Returns the assets traded in `wallet` or held in the opening inventory
+    fn held_assets<'w>(&'w self, wallet: &'w WalletDatabase) -> HashSet<&'w Asset> {
+        wallet
+            .iter()
+            .map(|(asset, _)| asset)
+            .chain(self.opening_holdings.keys())
+            .collect()
+    }
+
+    /// Returns the quantity of `asset` held in the opening inventory
+    fn opening_quantity(&self, asset: &Asset) -> Decimal {
+        self.opening_holdings
+            .get(asset)
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
@@ -145,42 +319,233 @@ mod test {
     use super::*;
 
     use crate::mock::database::{DatabaseQuoteMock, DatabaseTradeMock};
+    use crate::module730::Module730;
+
+    use crate::finance::{Quote, Quotes};
+    use bitpanda_csv::{AssetClass, CryptoCurrency, Trade, TradeGenerator};
+    use chrono::{TimeZone, Utc};
+    use std::str::FromStr;
 
-    #[tokio::test]
-    async fn should_init_taxes() {
+    #[test]
+    fn should_init_taxes() {
         crate::mock::log();
         let trades = DatabaseTradeMock::mock();
-        let quotes = DatabaseQuoteMock::mock().await;
+        let quotes = DatabaseQuoteMock::mock();
         let _ = mocked(&trades, &quotes);
     }
 
-    #[tokio::test]
-    async fn should_calc_ivafe() {
+    #[test]
+    fn should_calc_ivafe() {
         crate::mock::log();
         let trades = DatabaseTradeMock::mock();
-        let quotes = DatabaseQuoteMock::mock().await;
+        let quotes = DatabaseQuoteMock::mock();
         let tax = mocked(&trades, &quotes);
         let avg_balance = tax.average_balance().unwrap();
-        assert_eq!(tax.ivafe(avg_balance), dec!(20.16));
+        // € 9626.33 * 0.2%
+        assert_eq!(tax.ivafe(avg_balance), dec!(19.25));
     }
 
-    #[tokio::test]
-    async fn should_return_ivafe_0_if_below_5000() {
+    #[test]
+    fn should_return_ivafe_0_if_below_5000() {
         crate::mock::log();
-        let trades = TradeDatabase::from(vec![]);
-        let quotes = DatabaseQuoteMock::mock().await;
+        let trades = TradeDatabase::from(Vec::<Trade>::new());
+        let quotes = DatabaseQuoteMock::mock();
         let tax = mocked(&trades, &quotes);
         let avg_balance = tax.average_balance().unwrap();
         assert_eq!(tax.ivafe(avg_balance), Decimal::ZERO);
     }
 
-    #[tokio::test]
-    async fn should_calc_average_balance() {
+    #[test]
+    fn should_calc_average_balance() {
         crate::mock::log();
         let trades = DatabaseTradeMock::mock();
-        let quotes = DatabaseQuoteMock::mock().await;
+        let quotes = DatabaseQuoteMock::mock();
         let tax = mocked(&trades, &quotes);
-        assert_eq!(tax.average_balance().unwrap().round_dp(2), dec!(10077.96));
+        // the holdings of each day, at the constant quotes:
+        // - 01/01 - 05/01 (5 days): nothing
+        // - 06/01 - 30/06 (176 days): € 10000
+        // - 01/07 - 04/07 (4 days): € 7928.02 + TSLA 200 + AMZN 300 + ADBE 600 + BTC 270.9066 = 9298.9266
+        // - 05/07 - 12/08 (39 days): € 7341.62 + ... + 1177 400 = 9112.5266
+        // - 13/08 - 15/08 (3 days): € 7934.88, BTC sold = 9434.88
+        // - 16/08 - 19/08 (4 days): € 8269.38, AMZN 100 = 9569.38
+        // - 20/08 - 29/08 (10 days): $ 1000 (900) = 10469.38
+        // - 30/08 - 21/09 (23 days): € 7403.97, PYPL 300 = 9903.97
+        // - 22/09 - 23/09 (2 days): € 7717.01, ADBE 400 = 10017.01
+        // - 24/09 - 09/12 (77 days): € 7217.01 = 9517.01
+        // - 10/12 - 11/12 (2 days): € 7782.54, PYPL sold = 9782.54
+        // - 12/12 - 31/12 (20 days): € 7377.54, ADA 100 = 9477.54
+        // 3513611.1838 / 365
+        assert_eq!(tax.average_balance().unwrap().round_dp(2), dec!(9626.33));
+        // a buy in the middle of the year, whose quote doubles on the same day
+        let btc = Asset::Currency(Currency::Crypto(CryptoCurrency::Btc));
+        let trades = TradeDatabase::from(vec![
+            TradeGenerator::deposit(
+                DateTime::from_str("2022-01-01T09:00:00Z").unwrap(),
+                dec!(10180.00),
+                Fiat::Eur,
+            ),
+            TradeGenerator::buy(
+                DateTime::from_str("2022-07-01T09:00:00Z").unwrap(),
+                dec!(5000.00),
+                Fiat::Eur,
+                dec!(0.25),
+                btc.clone(),
+                AssetClass::Cryptocurrency,
+                dec!(20000.00),
+            ),
+        ]);
+        let quotes = QuoteDatabase::from_history(
+            std::collections::HashMap::from([(
+                btc,
+                Quotes::from(vec![
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+                        dec!(20000),
+                    ),
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2022, 7, 1, 0, 0, 0).unwrap(),
+                        dec!(40000),
+                    ),
+                ]),
+            )]),
+            Utc.with_ymd_and_hms(2022, 12, 31, 0, 0, 0).unwrap().into(),
+        );
+        let tax = mocked(&trades, &quotes);
+        // 181 days with € 10000, 184 days with € 5000 and 0.25 BTC at 40000
+        assert_eq!(tax.average_balance().unwrap().round_dp(2), dec!(12520.55));
+    }
+
+    #[test]
+    fn should_hold_opening_inventory_in_average_balance() {
+        crate::mock::log();
+        let trades = TradeDatabase::from(Vec::<Trade>::new());
+        let quotes = DatabaseQuoteMock::mock();
+        // 0.5 BTC held since the previous year; the parked lot is outside of the wallet
+        let inventory: Inventory = serde_json::from_str(
+            r#"{
+                "version": 1,
+                "date": "2021-12-31T23:59:59+01:00",
+                "lots": [
+                    {
+                        "asset": "BTC",
+                        "transaction_id": "T1",
+                        "acquired_at": "2021-05-03T10:00:00+02:00",
+                        "quantity": "0.5",
+                        "cost": "15000.0"
+                    },
+                    {
+                        "asset": "BTC",
+                        "transaction_id": "T2",
+                        "acquired_at": "2021-06-10T12:00:00+02:00",
+                        "quantity": "1.0",
+                        "cost": "30000.0",
+                        "parked": {
+                            "withdrawal": "C1",
+                            "withdrawn_at": "2021-11-20T09:00:00+01:00"
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let tax = mocked(&trades, &quotes).with_opening_inventory(Some(&inventory));
+        // 0.5 BTC at 20000 for the whole year
+        assert_eq!(tax.average_balance().unwrap(), dec!(10000));
+        assert_eq!(
+            tax.average_balance_by_venue().unwrap(),
+            vec![(Venue::Bitpanda, dec!(10000))]
+        );
+        assert_eq!(tax.balance().unwrap(), dec!(10000));
+    }
+
+    #[test]
+    fn should_calc_average_balance_by_venue() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::mock();
+        let quotes = DatabaseQuoteMock::mock();
+        let tax = mocked(&trades, &quotes);
+        let balances = tax.average_balance_by_venue().unwrap();
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].0, Venue::Bitpanda);
+        assert_eq!(balances[0].1, tax.average_balance().unwrap());
+    }
+
+    #[test]
+    fn should_prorate_ivafe_on_holding_days() {
+        crate::mock::log();
+        let trades = deposit_mock();
+        let quotes = QuoteDatabase::from(std::collections::HashMap::new());
+        let ivafe = |tax: Taxes| tax.ivafe(tax.average_balance().unwrap());
+        assert_eq!(ivafe(mocked(&trades, &quotes)), dec!(20.0));
+        // € 10000 held for 184 days of 365
+        let tax =
+            mocked(&trades, &quotes).with_account_opened_at(NaiveDate::from_ymd_opt(2022, 7, 1));
+        assert_eq!(ivafe(tax), dec!(10.08));
+        // opened before the time range
+        let tax =
+            mocked(&trades, &quotes).with_account_opened_at(NaiveDate::from_ymd_opt(2020, 7, 1));
+        assert_eq!(ivafe(tax), dec!(20.0));
+        // opened after the time range
+        let tax =
+            mocked(&trades, &quotes).with_account_opened_at(NaiveDate::from_ymd_opt(2023, 2, 1));
+        assert_eq!(ivafe(tax), Decimal::ZERO);
+    }
+
+    #[test]
+    fn should_count_days_before_account_opening_as_zero_balance() {
+        crate::mock::log();
+        let trades = deposit_mock();
+        let quotes = QuoteDatabase::from(std::collections::HashMap::new());
+        let tax =
+            mocked(&trades, &quotes).with_account_opened_at(NaiveDate::from_ymd_opt(2022, 7, 1));
+        let balances = tax.average_balance_by_venue().unwrap();
+        let ivafe = tax.ivafe(balances.iter().map(|(_, x)| *x).sum());
+        let gains_and_losses = GainsAndLosses::from(Vec::<CapitalDiff>::new());
+        let m730 = Module730::prepare(
+            &balances,
+            tax.period_days(),
+            ivafe,
+            &gains_and_losses,
+            &tax.net_losses(&gains_and_losses, &CarriedLosses::default()),
+        )
+        .unwrap();
+        // € 10000 held for 184 days of 365
+        assert_eq!(m730.quadro_rw.rows[0].column8.value, dec!(5041.10));
+        assert_eq!(m730.quadro_rw.rows[0].column11.value, dec!(10.08));
+    }
+
+    #[test]
+    fn should_value_holdings_at_quote_of_each_day() {
+        crate::mock::log();
+        let trades = DatabaseTradeMock::carried_loss_mock();
+        let quotes = QuoteDatabase::from_history(
+            std::collections::HashMap::from([(
+                Asset::Currency(Currency::Crypto(CryptoCurrency::Btc)),
+                Quotes::from(vec![
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+                        dec!(20000),
+                    ),
+                    Quote::eur(
+                        Utc.with_ymd_and_hms(2022, 3, 1, 0, 0, 0).unwrap(),
+                        dec!(10000),
+                    ),
+                ]),
+            )]),
+            Utc.with_ymd_and_hms(2022, 12, 31, 0, 0, 0).unwrap().into(),
+        );
+        let tax = mocked(&trades, &quotes);
+        // 22 days with € 5000, 28 with € 3000 and 0.1 BTC at 20000, 92 with 0.1 BTC at 10000, 214 with € 4500
+        assert_eq!(tax.average_balance().unwrap().round_dp(2), dec!(4331.51));
+    }
+
+    /// A deposit of € 10000 (net of the 1.8% fee) on the first day of 2022
+    fn deposit_mock() -> TradeDatabase {
+        TradeDatabase::from(vec![TradeGenerator::deposit(
+            DateTime::from_str("2022-01-01T09:00:00Z").unwrap(),
+            dec!(10180.00),
+            Fiat::Eur,
+        )])
     }
 
     fn mocked<'a>(trades: &'a TradeDatabase, quotes: &'a QuoteDatabase) -> Taxes<'a> {
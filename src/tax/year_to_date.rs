@@ -0,0 +1,131 @@
+//! # Year to date
+//!
+//! This module exposes the tax estimate for the current, unfinished year.
+//!
+//! IVAFE is due pro rata for the days elapsed, while the year-end liabilities are projected assuming the
+//! current balance and prices are kept until the end of the year.
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
+use rust_decimal::Decimal;
+
+/// Tax estimate from the beginning of the year to `date`
+#[derive(Debug, Clone)]
+pub struct YearToDateEstimate {
+    /// The date the estimate refers to
+    pub date: DateTime<FixedOffset>,
+    /// Days elapsed since the beginning of the year
+    pub elapsed_days: u32,
+    /// Days in the year
+    pub year_days: u32,
+    /// Average balance along the days elapsed
+    pub average_balance: Decimal,
+    /// Balance at `date`
+    pub current_balance: Decimal,
+    /// Average balance of the year, if the current balance is kept until the end of the year
+    pub projected_average_balance: Decimal,
+    /// IVAFE due for the days elapsed
+    pub ivafe_to_date: Decimal,
+    /// IVAFE due for the whole year, if the current balance is kept until the end of the year
+    pub projected_ivafe: Decimal,
+    /// Tax on the gains realized so far
+    pub realized_tax: Decimal,
+    /// Tax on the gains of the open positions, if they were sold at the current prices
+    pub latent_tax: Decimal,
+}
+
+impl YearToDateEstimate {
+    /// Calculate the estimate at `date`, using `ivafe` to calculate the IVAFE for an yearly average balance
+    pub fn new<F>(
+        date: DateTime<FixedOffset>,
+        average_balance: Decimal,
+        current_balance: Decimal,
+        realized_tax: Decimal,
+        latent_tax: Decimal,
+        ivafe: F,
+    ) -> Self
+    where
+        F: Fn(Decimal) -> Decimal,
+    {
+        let elapsed_days = date.ordinal();
+        let year_days = year_days(date.year());
+        let elapsed = Decimal::from(elapsed_days);
+        let days = Decimal::from(year_days);
+        let projected_average_balance =
+            (average_balance * elapsed + current_balance * (days - elapsed)) / days;
+        Self {
+            date,
+            elapsed_days,
+            year_days,
+            average_balance,
+            current_balance,
+            projected_average_balance,
+            ivafe_to_date: (ivafe(average_balance) * elapsed / days).round_dp(2),
+            projected_ivafe: ivafe(projected_average_balance),
+            realized_tax,
+            latent_tax,
+        }
+    }
+
+    /// Returns the taxes to pay at the end of the year, if the open positions are kept
+    pub fn projected_liabilities(&self) -> Decimal {
+        self.realized_tax + self.projected_ivafe
+    }
+
+    /// Returns the taxes to pay at the end of the year, if all the open positions are sold at the current prices
+    pub fn projected_liabilities_if_sold(&self) -> Decimal {
+        self.projected_liabilities() + self.latent_tax
+    }
+}
+
+/// Returns the amount of days in `year`
+pub fn year_days(year: i32) -> u32 {
+    NaiveDate::from_ymd_opt(year, 12, 31)
+        .map(|date| date.ordinal())
+        .unwrap_or(365)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_estimate_year_to_date_taxes() {
+        crate::mock::log();
+        // 2022-07-02 is the 183rd day of the year
+        let date = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2022, 7, 2, 12, 0, 0)
+            .unwrap();
+        let estimate = YearToDateEstimate::new(
+            date,
+            dec!(10000.0),
+            dec!(12000.0),
+            dec!(100.0),
+            dec!(50.0),
+            |balance| balance * dec!(0.002),
+        );
+        assert_eq!(estimate.elapsed_days, 183);
+        assert_eq!(estimate.year_days, 365);
+        assert_eq!(estimate.ivafe_to_date, dec!(10.03));
+        assert_eq!(
+            estimate.projected_average_balance.round_dp(2),
+            dec!(10997.26)
+        );
+        assert_eq!(estimate.projected_ivafe.round_dp(2), dec!(21.99));
+        assert_eq!(estimate.projected_liabilities().round_dp(2), dec!(121.99));
+        assert_eq!(
+            estimate.projected_liabilities_if_sold().round_dp(2),
+            dec!(171.99)
+        );
+    }
+
+    #[test]
+    fn should_get_year_days() {
+        assert_eq!(year_days(2022), 365);
+        assert_eq!(year_days(2024), 366);
+    }
+}
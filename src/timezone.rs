@@ -0,0 +1,114 @@
+//! # Timezone
+//!
+//! This module exposes the Europe/Rome timezone, which is the timezone used to determine the day and the year
+//! trades belong to.
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+pub use chrono_tz::Europe::Rome;
+
+/// Returns the first instant of `date` in Italy
+pub fn start_of_day(date: NaiveDate) -> DateTime<FixedOffset> {
+    Rome.from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .expect("midnight is always a valid time in Europe/Rome")
+        .fixed_offset()
+}
+
+/// Returns the last second of `date` in Italy
+pub fn end_of_day(date: NaiveDate) -> DateTime<FixedOffset> {
+    Rome.from_local_datetime(&date.and_hms_opt(23, 59, 59).unwrap())
+        .latest()
+        .expect("23:59:59 is always a valid time in Europe/Rome")
+        .fixed_offset()
+}
+
+/// Returns the day of `date` in Italy
+pub fn day(date: DateTime<FixedOffset>) -> NaiveDate {
+    date.with_timezone(&Rome).date_naive()
+}
+
+/// Returns the time in Italy of the UTC `datetime`
+pub fn from_utc(datetime: NaiveDateTime) -> DateTime<FixedOffset> {
+    Rome.from_utc_datetime(&datetime).fixed_offset()
+}
+
+/// Returns the current time in Italy
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&Rome).fixed_offset()
+}
+
+/// Returns the first and the last instant of `year` in Italy
+pub fn year_range(year: i32) -> anyhow::Result<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    match (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year, 12, 31),
+    ) {
+        (Some(first), Some(last)) => Ok((start_of_day(first), end_of_day(last))),
+        _ => anyhow::bail!("invalid year {}", year),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_follow_daylight_saving_time() {
+        crate::mock::log();
+        let utc = |m, d, h, min| {
+            NaiveDate::from_ymd_opt(2022, m, d)
+                .unwrap()
+                .and_hms_opt(h, min, 0)
+                .unwrap()
+        };
+        assert_eq!(
+            from_utc(utc(1, 15, 12, 0)).to_rfc3339(),
+            "2022-01-15T13:00:00+01:00"
+        );
+        assert_eq!(
+            from_utc(utc(7, 15, 12, 0)).to_rfc3339(),
+            "2022-07-15T14:00:00+02:00"
+        );
+        // DST starts on the last Sunday of March at 01:00 UTC
+        assert_eq!(
+            from_utc(utc(3, 27, 0, 59)).to_rfc3339(),
+            "2022-03-27T01:59:00+01:00"
+        );
+        assert_eq!(
+            from_utc(utc(3, 27, 1, 0)).to_rfc3339(),
+            "2022-03-27T03:00:00+02:00"
+        );
+        // DST ends on the last Sunday of October at 01:00 UTC
+        assert_eq!(
+            from_utc(utc(10, 30, 0, 59)).to_rfc3339(),
+            "2022-10-30T02:59:00+02:00"
+        );
+        assert_eq!(
+            from_utc(utc(10, 30, 1, 0)).to_rfc3339(),
+            "2022-10-30T02:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn should_get_day_boundaries() {
+        crate::mock::log();
+        let summer = NaiveDate::from_ymd_opt(2022, 7, 15).unwrap();
+        assert_eq!(
+            start_of_day(summer).to_rfc3339(),
+            "2022-07-15T00:00:00+02:00"
+        );
+        assert_eq!(end_of_day(summer).to_rfc3339(), "2022-07-15T23:59:59+02:00");
+        // a trade at 23:30 UTC in summer belongs to the next day
+        let trade = Utc
+            .with_ymd_and_hms(2022, 7, 15, 23, 30, 0)
+            .unwrap()
+            .fixed_offset();
+        assert_eq!(day(trade), NaiveDate::from_ymd_opt(2022, 7, 16).unwrap());
+        let (since, to) = year_range(2022).unwrap();
+        assert_eq!(since.to_rfc3339(), "2022-01-01T00:00:00+01:00");
+        assert_eq!(to.to_rfc3339(), "2022-12-31T23:59:59+01:00");
+    }
+}